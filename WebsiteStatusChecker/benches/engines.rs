@@ -0,0 +1,97 @@
+//! Compares the `threads` and `async` engines against a local mock server.
+//!
+//! Run with `cargo bench --bench engines`. `WSC_BENCH_TARGETS` (default 2000) sets how
+//! many targets are checked and `WSC_BENCH_LATENCY_MS` (default 20) how long the mock
+//! server waits before answering each request.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use website_status_checker::{Checker, Engine, Target};
+
+
+fn env_or(key: &str, default: u64) -> u64 {
+    std::env::var(key).ok().and_then(|value| value.parse().ok()).unwrap_or(default)
+}
+
+
+/// Starts an HTTP/1.1 server that answers every request with `200 ok` after `latency`.
+fn start_mock_server(latency: Duration) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind mock server");
+    let addr = listener.local_addr().unwrap();
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            thread::spawn(move || serve_connection(stream, latency));
+        }
+    });
+
+    addr
+}
+
+
+fn serve_connection(stream: TcpStream, latency: Duration) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut writer = stream;
+    let mut line = String::new();
+
+    loop {
+        // Skip the request line and headers; none of the benchmark requests have a body
+        loop {
+            line.clear();
+            match reader.read_line(&mut line) {
+                Ok(0) | Err(_) => return,
+                Ok(_) if line == "\r\n" => break,
+                Ok(_) => {}
+            }
+        }
+
+        thread::sleep(latency);
+        let response = "HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok";
+        if writer.write_all(response.as_bytes()).is_err() {
+            return;
+        }
+    }
+}
+
+
+fn bench(name: &str, checker: &Checker, targets: &[Target]) {
+    let start = Instant::now();
+    let results = checker.run(targets.to_vec());
+    let elapsed = start.elapsed();
+
+    let up = results.iter().filter(|result| result.is_up()).count();
+    println!(
+        "{:<28} {:>6} checks in {:>8.2?} ({:>8.1} checks/s, {} failed)",
+        name,
+        results.len(),
+        elapsed,
+        results.len() as f64 / elapsed.as_secs_f64(),
+        results.len() - up,
+    );
+}
+
+
+fn main() {
+    let count = env_or("WSC_BENCH_TARGETS", 2000);
+    let latency = Duration::from_millis(env_or("WSC_BENCH_LATENCY_MS", 20));
+    let addr = start_mock_server(latency);
+
+    let targets: Vec<Target> = (0..count)
+        .map(|i| Target::new(format!("http://{}/{}", addr, i)).with_timeout(Duration::from_secs(30)))
+        .collect();
+
+    println!("{} targets, {:?} server latency\n", count, latency);
+
+    for workers in [4, 64, 256] {
+        let checker = Checker::builder().engine(Engine::Threads).workers(workers).build().unwrap();
+        bench(&format!("threads (workers={})", workers), &checker, &targets);
+    }
+
+    for concurrency in [64, 256, 1000] {
+        let checker = Checker::builder().engine(Engine::Async).concurrency(concurrency).build().unwrap();
+        bench(&format!("async (concurrency={})", concurrency), &checker, &targets);
+    }
+}
@@ -0,0 +1,254 @@
+//! Alerts when a target goes down or comes back up.
+//!
+//! Each target starts out up. After `fail_after` failed checks in a row it goes down,
+//! and after `recover_after` successful checks in a row it is up again; each of those
+//! transitions is an [`AlertEvent`] handed to every [`Notifier`]. Checks aborted at
+//! shutdown don't count either way.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::mpsc::{self, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::check::CheckResult;
+use crate::error::ErrorKind;
+use crate::report::Reporter;
+use crate::schema::AlertRecord;
+use crate::stats::short;
+
+
+/// Failed checks in a row before a target is down, by default.
+pub const DEFAULT_FAIL_AFTER: u32 = 3;
+
+/// Successful checks in a row before a down target is up again, by default.
+pub const DEFAULT_RECOVER_AFTER: u32 = 2;
+
+
+/// How many checks in a row it takes to change a target's state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AlertPolicy {
+    pub fail_after: u32,
+    pub recover_after: u32,
+}
+
+
+impl Default for AlertPolicy {
+    fn default() -> Self {
+        AlertPolicy { fail_after: DEFAULT_FAIL_AFTER, recover_after: DEFAULT_RECOVER_AFTER }
+    }
+}
+
+
+/// Whether a target is considered up or down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertState {
+    Up,
+    Down,
+}
+
+
+impl fmt::Display for AlertState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AlertState::Up => "up",
+            AlertState::Down => "down",
+        })
+    }
+}
+
+
+/// A target changing state.
+///
+/// Serializes to the alert object described in [`crate::schema`].
+#[derive(Debug, Clone, Serialize)]
+#[serde(into = "AlertRecord")]
+pub struct AlertEvent {
+    /// The state the target is in now.
+    pub state: AlertState,
+    /// The first failed check of the outage.
+    pub down_since: DateTime<Utc>,
+    /// How long the target was down; set when it recovers.
+    pub downtime: Option<Duration>,
+    /// The checks in a row that caused the transition.
+    pub consecutive: u32,
+    /// The check that caused the transition.
+    pub result: CheckResult,
+}
+
+
+impl AlertEvent {
+    /// The target's name if it has one, otherwise the URL.
+    pub fn label(&self) -> &str {
+        self.result.label()
+    }
+
+    /// A one-line summary, e.g. `shop is DOWN after 3 failed checks: timeout: ...`.
+    pub fn message(&self) -> String {
+        match (self.state, &self.result.error) {
+            (AlertState::Down, Some(err)) => format!(
+                "{} is DOWN after {} failed checks: {}: {}",
+                self.label(),
+                self.consecutive,
+                err.kind(),
+                err
+            ),
+            (AlertState::Down, None) => format!("{} is DOWN after {} failed checks", self.label(), self.consecutive),
+            (AlertState::Up, _) => format!(
+                "{} is UP again after {} down",
+                self.label(),
+                self.downtime.map_or("-".to_string(), short)
+            ),
+        }
+    }
+}
+
+
+/// Delivers alerts somewhere: a webhook, a chat channel, an inbox or a local command.
+///
+/// Implement it to add a destination; see [`crate::notify`] for the built-in ones.
+pub trait Notifier: fmt::Debug + Send + Sync {
+    /// Delivers one event. Errors are logged and don't stop the run.
+    fn notify(&self, event: &AlertEvent) -> Result<(), String>;
+
+    /// Identifies the notifier in log messages.
+    fn describe(&self) -> String;
+}
+
+
+/// The `[alerts]` settings of a run.
+#[derive(Debug, Clone, Default)]
+pub struct Alerts {
+    pub policy: AlertPolicy,
+    pub notifiers: Vec<Arc<dyn Notifier>>,
+}
+
+
+/// A target's state and the checks in a row that agree with or against it.
+#[derive(Debug, Clone)]
+struct Tracker {
+    state: AlertState,
+    failures: u32,
+    successes: u32,
+    first_failure: Option<DateTime<Utc>>,
+    down_since: Option<DateTime<Utc>>,
+}
+
+
+impl Default for Tracker {
+    fn default() -> Self {
+        Tracker { state: AlertState::Up, failures: 0, successes: 0, first_failure: None, down_since: None }
+    }
+}
+
+
+impl Tracker {
+    fn observe(&mut self, result: &CheckResult, policy: &AlertPolicy) -> Option<AlertEvent> {
+        if result.is_up() {
+            self.failures = 0;
+            self.first_failure = None;
+            self.successes += 1;
+            if self.state == AlertState::Down && self.successes >= policy.recover_after {
+                self.state = AlertState::Up;
+                let down_since = self.down_since.take().unwrap_or(result.timestamp);
+                return Some(AlertEvent {
+                    state: AlertState::Up,
+                    down_since,
+                    downtime: (result.timestamp - down_since).to_std().ok(),
+                    consecutive: self.successes,
+                    result: result.clone(),
+                });
+            }
+        } else {
+            self.successes = 0;
+            self.failures += 1;
+            let first_failure = *self.first_failure.get_or_insert(result.timestamp);
+            if self.state == AlertState::Up && self.failures >= policy.fail_after {
+                self.state = AlertState::Down;
+                self.down_since = Some(first_failure);
+                return Some(AlertEvent {
+                    state: AlertState::Down,
+                    down_since: first_failure,
+                    downtime: None,
+                    consecutive: self.failures,
+                    result: result.clone(),
+                });
+            }
+        }
+        None
+    }
+}
+
+
+/// Tracks every target's state from its results and notifies on each transition.
+///
+/// Notifications are sent in order from a thread of their own, so a slow mail server
+/// doesn't hold up the checks; [`Reporter::finish`] waits for the ones still queued.
+#[derive(Debug)]
+pub struct Alerter {
+    policy: AlertPolicy,
+    trackers: HashMap<String, Tracker>,
+    queue: Option<Sender<AlertEvent>>,
+    sender: Option<JoinHandle<()>>,
+}
+
+
+impl Alerter {
+    pub fn new(alerts: Alerts) -> Self {
+        let (queue, events) = mpsc::channel::<AlertEvent>();
+        let notifiers = alerts.notifiers;
+        let sender = std::thread::Builder::new()
+            .name("wsc-alerts".to_string())
+            .spawn(move || {
+                for event in events {
+                    for notifier in &notifiers {
+                        if let Err(err) = notifier.notify(&event) {
+                            log::error!("alert for {} via {} failed: {}", event.label(), notifier.describe(), err);
+                        }
+                    }
+                }
+            })
+            .map_err(|err| log::error!("alerts can't be sent: {}", err))
+            .ok();
+
+        Alerter { policy: alerts.policy, trackers: HashMap::new(), queue: Some(queue), sender }
+    }
+}
+
+
+impl Reporter for Alerter {
+    fn on_result(&mut self, result: &CheckResult) -> std::io::Result<()> {
+        if result.error_kind() == Some(ErrorKind::Aborted) {
+            return Ok(());
+        }
+        let tracker = self.trackers.entry(result.key()).or_default();
+        if let Some(event) = tracker.observe(result, &self.policy) {
+            log::warn!("{}", event.message());
+            if let Some(queue) = &self.queue {
+                let _ = queue.send(event);
+            }
+        }
+        Ok(())
+    }
+
+    /// Forgets targets that were removed by a reload.
+    fn flush(&mut self, latest: &[CheckResult]) -> std::io::Result<()> {
+        self.trackers.retain(|key, _| latest.iter().any(|result| result.key() == *key));
+        Ok(())
+    }
+
+    fn finish(&mut self, _results: &[CheckResult]) -> std::io::Result<()> {
+        self.queue = None;
+        if let Some(sender) = self.sender.take()
+            && sender.join().is_err()
+        {
+            log::error!("the alert sender panicked");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,231 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD;
+use http::header::HeaderValue;
+use openssl::pkey::PKey;
+use openssl::ssl::SslConnector;
+use openssl::x509::X509;
+
+use crate::tls;
+
+
+/// A password or token. It is read from the environment or a file when the targets are
+/// loaded, and it never shows up in output, logs or results.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(String);
+
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Secret(value.into())
+    }
+
+    /// Reads the secret from the environment variable `name`.
+    pub fn from_env(name: &str) -> Result<Self, String> {
+        match std::env::var(name) {
+            Ok(value) if !value.is_empty() => Ok(Secret(value)),
+            _ => Err(format!("environment variable {} is not set", name)),
+        }
+    }
+
+    /// Reads the secret from the file at `path`, without a trailing newline.
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        let value = contents.trim_end_matches(['\r', '\n']);
+        if value.is_empty() {
+            return Err(format!("{} is empty", path.display()));
+        }
+        Ok(Secret(value.to_string()))
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(***)")
+    }
+}
+
+
+/// Credentials sent in the `Authorization` header.
+///
+/// Like any `Authorization` header, they are dropped when a redirect leaves the target's
+/// origin: its scheme, host and port.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Auth {
+    Basic { username: String, password: Secret },
+    Bearer { token: Secret },
+}
+
+
+impl Auth {
+    /// The `Authorization` header value, marked sensitive.
+    pub(crate) fn header_value(&self) -> Result<HeaderValue, String> {
+        let value = match self {
+            Auth::Basic { username, password } => {
+                format!("Basic {}", STANDARD.encode(format!("{}:{}", username, password.expose())))
+            }
+            Auth::Bearer { token } => format!("Bearer {}", token.expose()),
+        };
+        let mut value = HeaderValue::from_str(&value)
+            .map_err(|_| "the credentials contain characters that aren't allowed in a header".to_string())?;
+        value.set_sensitive(true);
+        Ok(value)
+    }
+}
+
+
+/// A client certificate offered in the TLS handshake, for servers that require mutual TLS.
+///
+/// It is only offered to the target's own origin, not to wherever it redirects to.
+#[derive(Clone)]
+pub struct ClientCert {
+    cert: PathBuf,
+    connector: SslConnector,
+}
+
+
+impl ClientCert {
+    /// Loads a PEM certificate (optionally followed by its chain) and its unencrypted
+    /// PKCS#8 PEM private key.
+    pub fn from_pem_files(cert: &Path, key: &Path) -> Result<Self, String> {
+        let read = |path: &Path| std::fs::read(path).map_err(|err| format!("{}: {}", path.display(), err));
+        let certs = X509::stack_from_pem(&read(cert)?).map_err(|err| format!("{}: {}", cert.display(), err))?;
+        let private_key = PKey::private_key_from_pem(&read(key)?).map_err(|err| format!("{}: {}", key.display(), err))?;
+
+        let mut connector = tls::connector().map_err(|err| err.to_string())?;
+        let mut certs = certs.into_iter();
+        let leaf = certs.next().ok_or_else(|| format!("{}: no certificate found", cert.display()))?;
+        connector
+            .set_certificate(&leaf)
+            .and_then(|()| connector.set_private_key(&private_key))
+            .and_then(|()| connector.check_private_key())
+            .and_then(|()| certs.try_for_each(|intermediate| connector.add_extra_chain_cert(intermediate)))
+            .map_err(|err| format!("{} with {}: {}", cert.display(), key.display(), err))?;
+        Ok(ClientCert { cert: cert.to_path_buf(), connector: connector.build() })
+    }
+
+    pub(crate) fn connector(&self) -> &SslConnector {
+        &self.connector
+    }
+}
+
+
+impl fmt::Debug for ClientCert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientCert").field("cert", &self.cert).finish_non_exhaustive()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::asn1::Asn1Time;
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::hash::MessageDigest;
+    use openssl::nid::Nid;
+    use openssl::pkey::Private;
+    use openssl::x509::{X509Builder, X509NameBuilder};
+
+    fn key() -> PKey<Private> {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap()
+    }
+
+    fn self_signed(key: &PKey<Private>) -> X509 {
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_text("CN", "wsc client").unwrap();
+        let name = name.build();
+
+        let mut builder = X509Builder::new().unwrap();
+        builder.set_version(2).unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder.set_issuer_name(&name).unwrap();
+        builder.set_pubkey(key).unwrap();
+        builder.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        builder.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+        builder.sign(key, MessageDigest::sha256()).unwrap();
+        builder.build()
+    }
+
+    fn write(name: &str, contents: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("wsc-auth-{}-{}", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn basic_credentials_are_base64_encoded() {
+        let auth = Auth::Basic { username: "Aladdin".to_string(), password: Secret::new("open sesame") };
+        let value = auth.header_value().unwrap();
+        assert_eq!(value, "Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ==");
+        assert!(value.is_sensitive());
+    }
+
+    #[test]
+    fn bearer_tokens_are_sent_as_they_are() {
+        let auth = Auth::Bearer { token: Secret::new("abc.def-123") };
+        let value = auth.header_value().unwrap();
+        assert_eq!(value, "Bearer abc.def-123");
+        assert!(value.is_sensitive());
+
+        let auth = Auth::Bearer { token: Secret::new("line\nbreak") };
+        assert_eq!(
+            auth.header_value().unwrap_err(),
+            "the credentials contain characters that aren't allowed in a header"
+        );
+    }
+
+    #[test]
+    fn secrets_are_redacted_from_debug_output() {
+        assert_eq!(format!("{:?}", Secret::new("hunter2")), "Secret(***)");
+
+        let auth = Auth::Basic { username: "admin".to_string(), password: Secret::new("hunter2") };
+        let debug = format!("{:?}", auth);
+        assert!(debug.contains("admin") && !debug.contains("hunter2"), "{}", debug);
+    }
+
+    #[test]
+    fn secrets_read_from_files_lose_the_trailing_newline() {
+        let path = write("secret", b"s3cret\r\n");
+        let secret = Secret::from_file(&path);
+        std::fs::write(&path, "\n").unwrap();
+        let empty = Secret::from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(secret.unwrap().expose(), "s3cret");
+        assert_eq!(empty.unwrap_err(), format!("{} is empty", path.display()));
+    }
+
+    #[test]
+    fn client_certificates_load_from_pem() {
+        let key = key();
+        let cert = self_signed(&key);
+        let cert_path = write("client.crt", &cert.to_pem().unwrap());
+        let key_path = write("client.key", &key.private_key_to_pem_pkcs8().unwrap());
+        let other_key_path = write("other.key", &self::key().private_key_to_pem_pkcs8().unwrap());
+        let empty_path = write("empty.crt", b"");
+
+        let loaded = ClientCert::from_pem_files(&cert_path, &key_path);
+        let mismatched = ClientCert::from_pem_files(&cert_path, &other_key_path);
+        let empty = ClientCert::from_pem_files(&empty_path, &key_path);
+        for path in [&cert_path, &key_path, &other_key_path, &empty_path] {
+            std::fs::remove_file(path).unwrap();
+        }
+        let missing = ClientCert::from_pem_files(&cert_path, &key_path);
+
+        let loaded = loaded.unwrap();
+        assert!(format!("{:?}", loaded).contains(&cert_path.display().to_string()));
+        let prefix = format!("{} with {}: ", cert_path.display(), other_key_path.display());
+        assert!(mismatched.unwrap_err().starts_with(&prefix));
+        assert_eq!(empty.unwrap_err(), format!("{}: no certificate found", empty_path.display()));
+        assert!(missing.unwrap_err().starts_with(&format!("{}: ", cert_path.display())));
+    }
+}
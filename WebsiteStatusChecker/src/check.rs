@@ -0,0 +1,313 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::client::{HttpClient, Timings};
+use crate::error::{CheckError, ErrorKind};
+use crate::expect;
+use crate::redirect::Hop;
+use crate::retry::{self, RetryOn};
+use crate::schema::Record;
+use crate::target::Target;
+use crate::tls::Certificate;
+
+
+/// Outcome of checking one [`Target`].
+///
+/// Serializes to the result object described in [`crate::schema`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(into = "Record", try_from = "Record")]
+pub struct CheckResult {
+    pub url: String,
+    pub name: Option<String>,
+    pub tags: Vec<String>,
+    /// The final response's status code, if a response was received.
+    pub status: Option<u16>,
+    /// Why the target is down; `None` when the check passed.
+    pub error: Option<CheckError>,
+    /// The redirects followed by the last attempt, in order.
+    pub redirects: Vec<Hop>,
+    /// The target's `capture_headers` that the final response sent, by lowercase name.
+    pub headers: BTreeMap<String, String>,
+    /// The server's certificate, for `https` targets that got as far as the handshake.
+    pub certificate: Option<Certificate>,
+    /// Problems that don't make the target down yet, such as a certificate about to expire.
+    pub warnings: Vec<String>,
+    /// Time from sending the last attempt until its response's headers arrived.
+    pub response_time: Duration,
+    /// Where the last attempt spent its time.
+    pub timings: Timings,
+    /// When the check finished.
+    pub timestamp: DateTime<Utc>,
+    /// Every request made for this check, in order; empty if none finished.
+    pub attempts: Vec<Attempt>,
+}
+
+
+/// One request made while checking a target.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attempt {
+    pub status: Option<u16>,
+    pub error: Option<CheckError>,
+    /// The whole attempt, including reading the body.
+    pub duration: Duration,
+    pub timings: Timings,
+    /// The pause before the next attempt, or `None` if this was the last one.
+    pub backoff: Option<Duration>,
+}
+
+
+/// The name used for results in the project requirements.
+pub type WebsiteStatus = CheckResult;
+
+
+impl CheckResult {
+    pub fn is_up(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// Whether the target is up but something needs attention soon.
+    pub fn has_warnings(&self) -> bool {
+        self.is_up() && !self.warnings.is_empty()
+    }
+
+    /// The target's name if it has one, otherwise the URL.
+    pub fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.url)
+    }
+
+    /// The result recorded for a check that was given up on during shutdown.
+    pub fn aborted(target: &Target, elapsed: Duration) -> Self {
+        CheckResult {
+            url: target.url.clone(),
+            name: target.name.clone(),
+            tags: target.tags.clone(),
+            status: None,
+            error: Some(CheckError::new(ErrorKind::Aborted, "shut down before the check finished")),
+            redirects: Vec::new(),
+            headers: BTreeMap::new(),
+            certificate: None,
+            warnings: Vec::new(),
+            response_time: elapsed,
+            timings: Timings::default(),
+            timestamp: Utc::now(),
+            attempts: Vec::new(),
+        }
+    }
+
+    /// What went wrong, if the check failed.
+    pub fn error_kind(&self) -> Option<ErrorKind> {
+        self.error.as_ref().map(CheckError::kind)
+    }
+
+    /// Matches the result to the [`Target`] it came from.
+    pub(crate) fn key(&self) -> String {
+        format!("{}\n{}", self.label(), self.url)
+    }
+}
+
+
+impl fmt::Display for CheckResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.error, self.status) {
+            (Some(err), _) => write!(f, "[{}] ERROR {}: {} ({:?})", self.label(), err.kind(), err, self.response_time)?,
+            (None, Some(code)) => write!(f, "[{}] {} ({:?})", self.label(), code, self.response_time)?,
+            (None, None) => write!(f, "[{}] OK ({:?})", self.label(), self.response_time)?,
+        }
+        if self.timings != Timings::default() {
+            write!(f, " [{}]", self.timings)?;
+        }
+        if let Some(last) = self.redirects.last() {
+            let plural = if self.redirects.len() == 1 { "" } else { "s" };
+            write!(f, " via {} redirect{} to {}", self.redirects.len(), plural, last.location)?;
+        }
+        if self.attempts.len() > 1 {
+            write!(f, " after {} attempts", self.attempts.len())?;
+        }
+        if !self.tags.is_empty() {
+            write!(f, " tags={}", self.tags.join(","))?;
+        }
+        if self.has_warnings() {
+            write!(f, " WARNING {}", self.warnings.join("; "))?;
+        }
+        Ok(())
+    }
+}
+
+
+/// Probes `target`, retrying according to `target.retry`.
+pub async fn check_website(client: &HttpClient, target: &Target) -> CheckResult {
+    let first_started = Instant::now();
+    let mut attempts: Vec<Attempt> = Vec::new();
+
+    loop {
+        log::debug!("attempt {} for {}", attempts.len() + 1, target.url);
+        let started = Instant::now();
+        let outcome = attempt(client, target).await;
+        let duration = started.elapsed();
+
+        let retries_done = attempts.len() as u32;
+        let backoff = target.retry.next_delay(retries_done, outcome.retry, outcome.retry_after, first_started.elapsed());
+        attempts.push(Attempt {
+            status: outcome.status,
+            error: outcome.error.clone(),
+            duration,
+            timings: outcome.timings,
+            backoff,
+        });
+
+        match backoff {
+            Some(delay) => {
+                log::debug!("retrying {} in {:?}", target.url, delay);
+                tokio::time::sleep(delay).await;
+            }
+            None => {
+                return CheckResult {
+                    url: target.url.clone(),
+                    name: target.name.clone(),
+                    tags: target.tags.clone(),
+                    status: outcome.status,
+                    error: outcome.error,
+                    redirects: outcome.redirects,
+                    headers: outcome.headers,
+                    certificate: outcome.certificate,
+                    warnings: outcome.warnings,
+                    response_time: outcome.response_time.unwrap_or(duration),
+                    timings: outcome.timings,
+                    timestamp: Utc::now(),
+                    attempts,
+                };
+            }
+        }
+    }
+}
+
+
+/// What one attempt found out.
+struct Outcome {
+    status: Option<u16>,
+    error: Option<CheckError>,
+    redirects: Vec<Hop>,
+    /// Captured response headers.
+    headers: BTreeMap<String, String>,
+    certificate: Option<Certificate>,
+    warnings: Vec<String>,
+    timings: Timings,
+    /// Until the response headers arrived, if they did.
+    response_time: Option<Duration>,
+    /// How the attempt classifies for retrying.
+    retry: Option<RetryOn>,
+    /// How long the server asked us to wait before trying again.
+    retry_after: Option<Duration>,
+}
+
+
+async fn attempt(client: &HttpClient, target: &Target) -> Outcome {
+    let (response, trace) = client.execute(target).await;
+    let warnings = certificate_warnings(trace.certificate.as_ref(), target);
+    let response = match response {
+        Ok(response) => response,
+        Err(err) => {
+            let retry = match err.kind() {
+                ErrorKind::Timeout => Some(RetryOn::Timeout),
+                ErrorKind::Dns | ErrorKind::ConnectionRefused | ErrorKind::Connect | ErrorKind::Tls => {
+                    Some(RetryOn::Connect)
+                }
+                _ => None,
+            };
+            return Outcome {
+                status: None,
+                error: Some(err),
+                redirects: trace.redirects,
+                headers: BTreeMap::new(),
+                certificate: trace.certificate,
+                warnings,
+                timings: trace.timings,
+                response_time: None,
+                retry,
+                retry_after: None,
+            };
+        }
+    };
+
+    let code = response.status;
+    let retry_after = response
+        .headers
+        .get(http::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(retry::parse_retry_after);
+
+    let error = if !target.expected_status.contains(code) {
+        Some(CheckError::new(
+            ErrorKind::UnexpectedStatus,
+            format!("status {} is not one of {}", code, target.expected_status),
+        ))
+    } else if let Err(reason) = target.redirect_assertions.check(&response.url) {
+        Some(CheckError::new(ErrorKind::Redirect, reason))
+    } else if let Err(reason) = target.header_assertions.check(&response.headers) {
+        Some(CheckError::new(ErrorKind::Assertion, reason))
+    } else {
+        let body = String::from_utf8_lossy(&response.body);
+        let checked = target.body_assertions.check(&body)
+            .and_then(|()| expect::check_json(&target.json_assertions, &body));
+        checked.err().map(|reason| {
+            let reason = if response.truncated {
+                format!("{} (in the first {} bytes)", reason, response.body.len())
+            } else {
+                reason
+            };
+            CheckError::new(ErrorKind::Assertion, reason)
+        })
+    };
+    // A status the target accepts is a pass, not a reason to try again
+    let retry = match code {
+        429 => Some(RetryOn::TooManyRequests),
+        500..=599 => Some(RetryOn::ServerError),
+        _ => None,
+    }
+    .filter(|_| error.is_some());
+    let headers = target
+        .capture_headers
+        .iter()
+        .filter_map(|name| {
+            let value = expect::header_value(&response.headers, name)?;
+            Some((name.to_ascii_lowercase(), value))
+        })
+        .collect();
+
+    Outcome {
+        status: Some(code),
+        error,
+        redirects: trace.redirects,
+        headers,
+        certificate: trace.certificate,
+        warnings,
+        timings: trace.timings,
+        response_time: Some(response.headers_after),
+        retry,
+        retry_after,
+    }
+}
+
+
+/// A warning if `certificate`, or the first certificate in its chain to expire, expires
+/// within the target's `cert_expiry_warning`.
+fn certificate_warnings(certificate: Option<&Certificate>, target: &Target) -> Vec<String> {
+    let threshold = TimeDelta::from_std(target.cert_expiry_warning).unwrap_or(TimeDelta::MAX);
+    let Some(certificate) = certificate else { return Vec::new() };
+    let first = certificate.expires_first();
+    let in_chain = !std::ptr::eq(first, certificate);
+    let which = if in_chain { format!("certificate {} in the chain", first.subject) } else { "certificate".to_string() };
+    // An expired server certificate fails the check instead
+    if first.is_expired() && in_chain {
+        vec![format!("{} expired on {}", which, first.not_after.format("%Y-%m-%d"))]
+    } else if !first.is_expired() && first.expires_in() < threshold {
+        let days = first.expires_in().num_days();
+        vec![format!("{} expires in {} days, on {}", which, days, first.not_after.format("%Y-%m-%d"))]
+    } else {
+        Vec::new()
+    }
+}
@@ -0,0 +1,359 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use hyper_util::client::proxy::matcher::Matcher;
+use serde::Deserialize;
+use tokio::runtime::Runtime;
+
+use crate::check::{check_website, CheckResult};
+use crate::client::HttpClient;
+use crate::pool::WorkerPool;
+use crate::shutdown::{InFlight, Shutdown};
+use crate::tasks::TaskPool;
+use crate::target::Target;
+
+
+/// How a [`Checker`] runs checks concurrently.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Engine {
+    /// A fixed pool of OS threads fed through channels; one request in flight per thread.
+    #[default]
+    Threads,
+    /// Tasks on a tokio runtime; in-flight requests are limited by `concurrency`.
+    Async,
+}
+
+
+impl FromStr for Engine {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "threads" => Ok(Engine::Threads),
+            "async" => Ok(Engine::Async),
+            _ => Err(format!("unknown engine `{}` (use threads or async)", value)),
+        }
+    }
+}
+
+
+impl fmt::Display for Engine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Engine::Threads => "threads",
+            Engine::Async => "async",
+        })
+    }
+}
+
+
+/// Runs checks for many targets concurrently.
+///
+/// Create one with [`Checker::builder`]. A checker owns its HTTP client and the tokio
+/// runtime that drives it, so reuse one checker rather than building a new one per run.
+/// Its methods block and must not be called from inside an async context.
+#[derive(Clone)]
+pub struct Checker {
+    client: HttpClient,
+    runtime: Arc<Runtime>,
+    engine: Engine,
+    workers: usize,
+    concurrency: usize,
+}
+
+
+/// Configures a [`Checker`].
+#[derive(Debug)]
+pub struct CheckerBuilder {
+    user_agent: Option<String>,
+    proxies: Option<Matcher>,
+    engine: Engine,
+    workers: usize,
+    concurrency: usize,
+}
+
+
+impl Default for CheckerBuilder {
+    fn default() -> Self {
+        CheckerBuilder {
+            user_agent: None,
+            proxies: None,
+            engine: Engine::Threads,
+            workers: 4,
+            concurrency: 1000,
+        }
+    }
+}
+
+
+impl CheckerBuilder {
+    pub fn engine(mut self, engine: Engine) -> Self {
+        self.engine = engine;
+        self
+    }
+
+    /// Number of worker threads for [`Engine::Threads`]; values below one are treated as one.
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// Maximum requests in flight for [`Engine::Async`]; values below one are treated as one.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// `User-Agent` sent with every request unless a target sets its own.
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Proxies for every request, instead of the ones set in the environment; build them
+    /// with [`ProxyMatcher::builder`](crate::ProxyMatcher::builder).
+    pub fn proxies(mut self, proxies: Matcher) -> Self {
+        self.proxies = Some(proxies);
+        self
+    }
+
+    pub fn build(self) -> Result<Checker, Box<dyn std::error::Error + Send + Sync>> {
+        // With threads the workers run the checks themselves and one runtime thread is
+        // enough to drive the sockets
+        let mut runtime = tokio::runtime::Builder::new_multi_thread();
+        if self.engine == Engine::Threads {
+            runtime.worker_threads(1);
+        }
+        let runtime = runtime.enable_all().thread_name("wsc-runtime").build()?;
+
+        let mut client = HttpClient::new(self.user_agent.as_deref())?;
+        if let Some(proxies) = self.proxies {
+            client = client.with_proxies(proxies);
+        }
+
+        Ok(Checker {
+            client,
+            runtime: Arc::new(runtime),
+            engine: self.engine,
+            workers: self.workers,
+            concurrency: self.concurrency,
+        })
+    }
+}
+
+
+impl fmt::Debug for Checker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Checker")
+            .field("engine", &self.engine)
+            .field("workers", &self.workers)
+            .field("concurrency", &self.concurrency)
+            .finish_non_exhaustive()
+    }
+}
+
+
+impl Checker {
+    pub fn builder() -> CheckerBuilder {
+        CheckerBuilder::default()
+    }
+
+    pub fn engine(&self) -> Engine {
+        self.engine
+    }
+
+    pub fn workers(&self) -> usize {
+        self.workers
+    }
+
+    pub fn concurrency(&self) -> usize {
+        self.concurrency
+    }
+
+    /// Checks a single target, blocking the calling thread until it finishes.
+    pub fn check(&self, target: &Target) -> CheckResult {
+        self.runtime.block_on(check_website(&self.client, target))
+    }
+
+    /// Checks every target and returns the results in completion order.
+    ///
+    /// [`Engine::Threads`] starts at most `workers` threads however many targets there
+    /// are; [`Engine::Async`] keeps at most `concurrency` requests in flight.
+    pub fn run(&self, targets: Vec<Target>) -> Vec<CheckResult> {
+        self.stream(targets).collect()
+    }
+
+    /// Starts checking every target and yields each result as soon as it is ready.
+    ///
+    /// Dropping the stream early lets checks already running finish in the background but
+    /// discards their results.
+    pub fn stream(&self, targets: Vec<Target>) -> ResultStream {
+        let (tx, rx) = mpsc::channel();
+        let executor = self.executor(targets.len(), tx);
+        let mut in_flight = InFlight::default();
+        for target in targets {
+            in_flight.started(&target);
+            executor.submit(target);
+        }
+        executor.close();
+
+        ResultStream {
+            results: rx,
+            executor: Some(executor),
+            in_flight,
+            shutdown: None,
+            deadline: None,
+            aborted: Vec::new(),
+        }
+    }
+
+    /// Starts the engine's workers, sized for at most `jobs` targets at once.
+    pub(crate) fn executor(&self, jobs: usize, results: Sender<CheckResult>) -> Executor {
+        match self.engine {
+            Engine::Threads => {
+                let workers = self.workers.min(jobs);
+                Executor::Threads(WorkerPool::new(workers, &self.runtime, &self.client, results))
+            }
+            Engine::Async => Executor::Async(TaskPool::new(
+                Arc::clone(&self.runtime),
+                self.client.clone(),
+                self.concurrency,
+                results,
+            )),
+        }
+    }
+}
+
+
+/// The running side of an [`Engine`].
+pub(crate) enum Executor {
+    Threads(WorkerPool),
+    Async(TaskPool),
+}
+
+
+impl Executor {
+    pub(crate) fn submit(&self, target: Target) {
+        match self {
+            Executor::Threads(pool) => pool.submit(target),
+            Executor::Async(pool) => pool.submit(target),
+        }
+    }
+
+    pub(crate) fn close(&self) {
+        match self {
+            Executor::Threads(pool) => pool.close(),
+            Executor::Async(pool) => pool.close(),
+        }
+    }
+
+    /// Stops starting queued checks; checks already running carry on.
+    pub(crate) fn cancel(&self) {
+        match self {
+            Executor::Threads(pool) => pool.cancel(),
+            Executor::Async(pool) => pool.cancel(),
+        }
+    }
+
+    /// Gives up on every check without waiting for the ones still running.
+    pub(crate) fn abort(self) {
+        match self {
+            Executor::Threads(pool) => pool.abort(),
+            Executor::Async(pool) => pool.abort(),
+        }
+    }
+
+    pub(crate) fn join(self) {
+        match self {
+            Executor::Threads(pool) => pool.join(),
+            Executor::Async(pool) => pool.join(),
+        }
+    }
+}
+
+
+/// How often a stream watching a [`Shutdown`] checks whether it has been triggered.
+const SHUTDOWN_POLL: Duration = Duration::from_millis(100);
+
+
+/// Results of [`Checker::stream`], in completion order.
+pub struct ResultStream {
+    results: Receiver<CheckResult>,
+    executor: Option<Executor>,
+    in_flight: InFlight,
+    shutdown: Option<(Shutdown, Duration)>,
+    deadline: Option<Instant>,
+    aborted: Vec<CheckResult>,
+}
+
+
+impl ResultStream {
+    /// Ends the run early once `shutdown` is triggered: queued checks are dropped, running
+    /// checks get up to `grace` to finish, and everything unfinished is then yielded as an
+    /// [aborted](CheckResult::aborted) result.
+    pub fn with_shutdown(mut self, shutdown: Shutdown, grace: Duration) -> Self {
+        self.shutdown = Some((shutdown, grace));
+        self
+    }
+
+    /// Gives up on whatever is still pending and queues aborted results for it.
+    fn stop(&mut self, wait: bool) {
+        self.aborted = self.in_flight.abort_all();
+        match self.executor.take() {
+            Some(executor) if wait => executor.join(),
+            Some(executor) => executor.abort(),
+            None => {}
+        }
+    }
+}
+
+
+impl Iterator for ResultStream {
+    type Item = CheckResult;
+
+    fn next(&mut self) -> Option<CheckResult> {
+        loop {
+            if let Some(result) = self.aborted.pop() {
+                return Some(result);
+            }
+            let executor = self.executor.as_ref()?;
+
+            if self.deadline.is_none()
+                && let Some((shutdown, grace)) = &self.shutdown
+                && shutdown.is_triggered()
+            {
+                log::warn!(
+                    "shutting down: waiting up to {:?} for {} unfinished checks",
+                    grace,
+                    self.in_flight.len()
+                );
+                executor.cancel();
+                self.deadline = Some(Instant::now() + *grace);
+            }
+
+            let received = match (self.deadline, &self.shutdown) {
+                (Some(deadline), _) => self.results.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+                (None, Some(_)) => self.results.recv_timeout(SHUTDOWN_POLL),
+                (None, None) => self.results.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+
+            match received {
+                Ok(result) => {
+                    self.in_flight.finished(&result);
+                    return Some(result);
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    if self.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                        self.stop(false);
+                    }
+                }
+                // The executor holds the only senders, so this means every worker is done
+                Err(RecvTimeoutError::Disconnected) => self.stop(true),
+            }
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use website_status_checker::config::{parse_duration, Overrides};
+use website_status_checker::{Engine, MAX_RETRIES};
+
+
+/// Concurrent website status checker.
+#[derive(Debug, Parser)]
+#[command(name = "WebsiteStatusChecker", version, about, propagate_version = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Check every URL once, print the results and save them.
+    Check(CheckArgs),
+    /// Check each target on its own interval until interrupted; reloads the targets on
+    /// SIGHUP or when the input file changes.
+    Watch(WatchArgs),
+    /// Watch the targets like `watch` and serve their status as an HTML page, a JSON API
+    /// (`/api/status`, `/api/history`, `/api/statistics`) and Prometheus `/metrics`.
+    Serve(ServeArgs),
+    /// Print a previously saved results file or history without probing anything.
+    Report(ReportArgs),
+}
+
+
+#[derive(Debug, Args)]
+pub struct CheckArgs {
+    #[command(flatten)]
+    pub probe: ProbeArgs,
+
+    #[command(flatten)]
+    pub output: OutputArgs,
+}
+
+
+#[derive(Debug, Args)]
+pub struct WatchArgs {
+    #[command(flatten)]
+    pub probe: ProbeArgs,
+
+    #[command(flatten)]
+    pub output: OutputArgs,
+
+    /// Default pause between checks of a target (e.g. `30s`, `5m`) [default: 60s].
+    #[arg(long, value_parser = parse_duration)]
+    pub interval: Option<Duration>,
+
+    /// Randomly move each check by up to this fraction of its interval (0 to 1).
+    #[arg(long, default_value_t = 0.1, value_parser = parse_fraction)]
+    pub jitter: f64,
+}
+
+
+#[derive(Debug, Args)]
+pub struct ServeArgs {
+    #[command(flatten)]
+    pub watch: WatchArgs,
+
+    /// Address to serve on; use `0.0.0.0:8080` to make the page reachable from other
+    /// machines.
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    pub listen: SocketAddr,
+}
+
+
+#[derive(Debug, Args)]
+pub struct ReportArgs {
+    /// Results file written by `check` or `watch` in `json` or `jsonl` format, or a
+    /// history directory.
+    #[arg(default_value = "status.json", value_parser = existing_path)]
+    pub input: PathBuf,
+
+    /// Also summarise the results in this trailing window (e.g. `1h`, `7d`); repeatable.
+    #[arg(long = "window", value_parser = parse_duration)]
+    pub windows: Vec<Duration>,
+}
+
+
+#[derive(Debug, Args)]
+pub struct ProbeArgs {
+    /// Targets to check: a `.toml`/`.yaml` config file, or any other file with one URL per
+    /// line (blank lines and `#` comments are ignored).
+    #[arg(short, long, default_value = "urls.txt", value_parser = existing_file)]
+    pub input: PathBuf,
+
+    /// Concurrency engine: `threads` (worker pool) or `async` (tokio tasks) [default: threads].
+    #[arg(short, long)]
+    pub engine: Option<Engine>,
+
+    /// Number of worker threads for the `threads` engine [default: 4].
+    #[arg(short, long, value_parser = parse_positive)]
+    pub workers: Option<usize>,
+
+    /// Maximum requests in flight for the `async` engine [default: 1000].
+    #[arg(short, long, value_parser = parse_positive)]
+    pub concurrency: Option<usize>,
+
+    /// Default per-request timeout (e.g. `500ms`, `3s`, `1m`; a bare number is seconds)
+    /// [default: 5s].
+    #[arg(short, long, value_parser = parse_duration)]
+    pub timeout: Option<Duration>,
+
+    /// Default number of extra attempts after a retryable failure [default: 0].
+    #[arg(short, long, value_parser = clap::value_parser!(u32).range(0..=i64::from(MAX_RETRIES)))]
+    pub retries: Option<u32>,
+
+    /// On SIGINT/SIGTERM, how long running checks get to finish before they are reported
+    /// as aborted.
+    #[arg(long, default_value = "10s", value_parser = parse_grace)]
+    pub grace: Duration,
+
+    /// Also append every result to the history in this directory, overriding the config
+    /// file's `history.path`.
+    #[arg(long, value_name = "DIR")]
+    pub history: Option<PathBuf>,
+}
+
+
+impl ProbeArgs {
+    /// Command-line values layered over the config file and environment.
+    pub fn overrides(&self, interval: Option<Duration>) -> Overrides {
+        Overrides {
+            engine: self.engine,
+            workers: self.workers,
+            concurrency: self.concurrency,
+            timeout: self.timeout,
+            retries: self.retries,
+            interval,
+            history: self.history.clone(),
+        }
+    }
+}
+
+
+#[derive(Debug, Args)]
+pub struct OutputArgs {
+    /// Where to save the results.
+    #[arg(short, long, default_value = "status.json")]
+    pub output: PathBuf,
+
+    /// Format of the saved results.
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Json)]
+    pub format: OutputFormat,
+
+    /// Besides the whole run, summarise statistics over this trailing window (e.g. `1h`,
+    /// `24h`, `7d`), measured back from the newest result; repeatable.
+    #[arg(long = "window", value_parser = parse_duration)]
+    pub windows: Vec<Duration>,
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// One JSON array, written when the run is over.
+    Json,
+    /// One JSON object per line, written as each check finishes.
+    Jsonl,
+    /// One human-readable line per result, written as each check finishes.
+    Text,
+}
+
+
+fn existing_file(value: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(value);
+    if path.is_file() {
+        Ok(path)
+    } else {
+        Err(format!("`{}` does not exist or is not a file", value))
+    }
+}
+
+
+fn existing_path(value: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(value);
+    if path.exists() {
+        Ok(path)
+    } else {
+        Err(format!("`{}` does not exist", value))
+    }
+}
+
+
+fn parse_positive(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(0) => Err("must be at least one".to_string()),
+        Ok(n) => Ok(n),
+        Err(_) => Err(format!("`{}` is not a positive whole number", value)),
+    }
+}
+
+
+fn parse_fraction(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(fraction) if (0.0..=1.0).contains(&fraction) => Ok(fraction),
+        _ => Err(format!("`{}` is not a number between 0 and 1", value)),
+    }
+}
+
+
+fn parse_grace(value: &str) -> Result<Duration, String> {
+    if value.trim() == "0" {
+        Ok(Duration::ZERO)
+    } else {
+        parse_duration(value)
+    }
+}
@@ -0,0 +1,617 @@
+//! The HTTP client behind every check.
+//!
+//! It drives hyper's connection API itself instead of using a pooled client such as
+//! reqwest, whose connector hides DNS, connect and the TLS handshake in one step: every
+//! check opens its own connection, so each phase can be timed, the certificate chain the
+//! server sent read, and a certificate the handshake rejected fetched again to explain
+//! why. HTTP/2 is used when the server offers it during the TLS handshake. Requests are
+//! sent without `Accept-Encoding`, so bodies arrive uncompressed.
+//!
+//! Proxies come from `http_proxy`, `HTTPS_PROXY`, `ALL_PROXY` and `NO_PROXY` as curl reads
+//! them, through hyper-util's [`Matcher`]. Only `http://` proxies are supported; `https`
+//! URLs are tunnelled through them with `CONNECT`, so the TLS handshake is still with the
+//! target itself.
+
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use http::{Method, Request, StatusCode, Uri};
+use http_body_util::{BodyExt, Full};
+use hyper::body::{Bytes, Incoming};
+use hyper::client::conn::{http1, http2};
+use hyper_util::client::legacy::connect::proxy::Tunnel;
+use hyper_util::client::proxy::matcher::{Intercept, Matcher};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use openssl::ssl::{SslConnector, SslVerifyMode};
+use tokio::net::TcpStream;
+use tower_service::Service;
+use url::{Host, Url};
+
+use crate::auth::ClientCert;
+use crate::error::{CheckError, ErrorKind};
+use crate::redirect::{Hop, RedirectPolicy};
+use crate::target::Target;
+use crate::tls::{self, Certificate};
+
+
+/// How long each phase of a request took; phases that were never reached are `None`.
+///
+/// When redirects are followed each phase is summed over every hop.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Timings {
+    /// Resolving the host name; zero for IP addresses.
+    pub dns: Option<Duration>,
+    /// Opening the TCP connection.
+    pub connect: Option<Duration>,
+    /// The TLS handshake; `None` for plain `http`.
+    pub tls: Option<Duration>,
+    /// From sending the request until the response headers arrived.
+    pub ttfb: Option<Duration>,
+    /// Reading the response body.
+    pub download: Option<Duration>,
+}
+
+
+impl fmt::Display for Timings {
+    /// Lists the phases that were reached, e.g. `dns 1.2ms, connect 0.4ms, ttfb 20.5ms`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let phases = [
+            ("dns", self.dns),
+            ("connect", self.connect),
+            ("tls", self.tls),
+            ("ttfb", self.ttfb),
+            ("download", self.download),
+        ];
+        let mut first = true;
+        for (name, duration) in phases {
+            if let Some(duration) = duration {
+                let separator = if first { "" } else { ", " };
+                write!(f, "{}{} {:.1}ms", separator, name, duration.as_secs_f64() * 1000.0)?;
+                first = false;
+            }
+        }
+        Ok(())
+    }
+}
+
+
+/// Sends the requests for checks over a fresh connection each time, timing every phase.
+///
+/// Connections are never reused, so each check measures DNS, connect and TLS as a new
+/// visitor would see them.
+#[derive(Clone)]
+pub struct HttpClient {
+    tls: SslConnector,
+    /// Accepts any certificate, to read one the handshake rejected.
+    inspect: SslConnector,
+    user_agent: Option<HeaderValue>,
+    proxies: Arc<Matcher>,
+}
+
+
+/// The final response of a request, after any redirects.
+pub(crate) struct Response {
+    /// Where the response came from.
+    pub(crate) url: Url,
+    pub(crate) status: u16,
+    pub(crate) headers: HeaderMap,
+    /// At most the target's `max_size` bytes of the body.
+    pub(crate) body: Vec<u8>,
+    /// Whether the body went on past what was read.
+    pub(crate) truncated: bool,
+    /// From the start of the request until the final response's headers arrived.
+    pub(crate) headers_after: Duration,
+}
+
+
+/// The phase a request is in, so a timeout can say where it happened.
+#[derive(Debug, Clone, Copy)]
+enum Phase {
+    Dns,
+    Connect,
+    Tls,
+    FirstByte,
+    Download,
+}
+
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Phase::Dns => "resolving the host name",
+            Phase::Connect => "connecting",
+            Phase::Tls => "in the TLS handshake",
+            Phase::FirstByte => "waiting for the response",
+            Phase::Download => "reading the body",
+        })
+    }
+}
+
+
+/// What is learned as a request moves through its phases: [`Timings`], the certificate
+/// of the latest TLS handshake and the redirects followed.
+pub(crate) struct Trace {
+    pub(crate) timings: Timings,
+    pub(crate) certificate: Option<Certificate>,
+    pub(crate) redirects: Vec<Hop>,
+    phase: Phase,
+    phase_started: Instant,
+}
+
+
+impl Trace {
+    fn new() -> Self {
+        Trace {
+            timings: Timings::default(),
+            certificate: None,
+            redirects: Vec::new(),
+            phase: Phase::Dns,
+            phase_started: Instant::now(),
+        }
+    }
+
+    fn enter(&mut self, phase: Phase) {
+        self.phase = phase;
+        self.phase_started = Instant::now();
+    }
+
+    /// Adds the time since the current phase was entered to it, whether or not it succeeded.
+    fn leave(&mut self) {
+        let slot = match self.phase {
+            Phase::Dns => &mut self.timings.dns,
+            Phase::Connect => &mut self.timings.connect,
+            Phase::Tls => &mut self.timings.tls,
+            Phase::FirstByte => &mut self.timings.ttfb,
+            Phase::Download => &mut self.timings.download,
+        };
+        *slot = Some(slot.unwrap_or_default() + self.phase_started.elapsed());
+    }
+}
+
+
+impl HttpClient {
+    /// A client that sends `user_agent`, if given, unless a target sets its own, through
+    /// the proxies set in the environment.
+    pub fn new(user_agent: Option<&str>) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let tls = tls::connector()?.build();
+        let mut inspect = tls::connector()?;
+        inspect.set_verify(SslVerifyMode::NONE);
+        let user_agent = user_agent.map(HeaderValue::from_str).transpose()?;
+        Ok(HttpClient { tls, inspect: inspect.build(), user_agent, proxies: Arc::new(Matcher::from_env()) })
+    }
+
+    /// Replaces the proxies read from the environment.
+    pub fn with_proxies(mut self, proxies: Matcher) -> Self {
+        self.proxies = Arc::new(proxies);
+        self
+    }
+
+    /// Sends `target`'s request, following redirects, within `target.timeout`.
+    ///
+    /// The trace covers whatever was reached, whether or not the request succeeded; its
+    /// certificate is the last one a server presented, even if it was rejected.
+    pub(crate) async fn execute(&self, target: &Target) -> (Result<Response, CheckError>, Trace) {
+        let mut trace = Trace::new();
+        let result = match tokio::time::timeout(target.timeout, self.follow(target, &mut trace)).await {
+            Ok(result) => result,
+            Err(_) => {
+                trace.leave();
+                Err(CheckError::new(
+                    ErrorKind::Timeout,
+                    format!("timed out after {:?} {}", target.timeout, trace.phase),
+                ))
+            }
+        };
+        (result, trace)
+    }
+
+    async fn follow(&self, target: &Target, trace: &mut Trace) -> Result<Response, CheckError> {
+        let started = Instant::now();
+        let mut url = Url::parse(&target.url)
+            .map_err(|err| CheckError::with_source(ErrorKind::Other, format!("invalid URL `{}`: {}", target.url, err), err))?;
+        if !target.query.is_empty() {
+            url.query_pairs_mut().extend_pairs(&target.query);
+        }
+        let mut method = target.method.clone();
+        let mut body = target.body.clone();
+        let mut headers = self.headers(target)?;
+        let mut client_cert = target.client_cert.as_ref();
+        let mut visited: Vec<(Method, Url)> = Vec::new();
+
+        loop {
+            visited.push((method.clone(), url.clone()));
+            let tls = client_cert.map_or(&self.tls, ClientCert::connector);
+            let response = self.send(tls, &method, &url, &headers, body.as_deref(), trace).await?;
+            let status = response.status();
+
+            let location = response.headers().get(header::LOCATION).and_then(|value| value.to_str().ok());
+            if status.is_redirection()
+                && let Some(location) = location
+                && let RedirectPolicy::Follow(max_redirects) = target.redirects
+            {
+                let next = url.join(location).map_err(|err| {
+                    CheckError::with_source(ErrorKind::Redirect, format!("invalid redirect to `{}`: {}", location, err), err)
+                })?;
+                if status == StatusCode::SEE_OTHER && method != Method::HEAD
+                    || matches!(status, StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND) && method == Method::POST
+                {
+                    method = Method::GET;
+                    body = None;
+                    headers.remove(header::CONTENT_TYPE);
+                }
+                // A downgrade to http or another port is a different service as much as another host is
+                if next.origin() != url.origin() {
+                    headers.remove(header::AUTHORIZATION);
+                    headers.remove(header::COOKIE);
+                    client_cert = None;
+                }
+                trace.redirects.push(Hop {
+                    url: url.to_string(),
+                    status: status.as_u16(),
+                    location: next.to_string(),
+                });
+                if visited.contains(&(method.clone(), next.clone())) {
+                    return Err(CheckError::new(ErrorKind::Redirect, format!("redirect loop at {}", next)));
+                }
+                if trace.redirects.len() > max_redirects {
+                    return Err(CheckError::new(
+                        ErrorKind::Redirect,
+                        format!("too many redirects (more than {}) from {}", max_redirects, target.url),
+                    ));
+                }
+                log::debug!("{} redirected to {}", url, next);
+                url = next;
+                continue;
+            }
+
+            let headers_after = started.elapsed();
+            let (parts, body) = response.into_parts();
+            trace.enter(Phase::Download);
+            let body = read_body(body, target.body_assertions.max_size).await;
+            trace.leave();
+            let (body, truncated) = body.map_err(|err| {
+                CheckError::with_source(ErrorKind::Other, format!("failed to read the body of {}: {}", url, err), err)
+            })?;
+
+            return Ok(Response {
+                url,
+                status: parts.status.as_u16(),
+                headers: parts.headers,
+                body,
+                truncated,
+                headers_after,
+            });
+        }
+    }
+
+    /// Makes one request over a new connection and returns as soon as the headers arrive.
+    async fn send(
+        &self,
+        tls: &SslConnector,
+        method: &Method,
+        url: &Url,
+        headers: &HeaderMap,
+        body: Option<&[u8]>,
+        trace: &mut Trace,
+    ) -> Result<hyper::Response<Incoming>, CheckError> {
+        let https = match url.scheme() {
+            "https" => true,
+            "http" => false,
+            other => return Err(CheckError::new(ErrorKind::Other, format!("unsupported URL scheme `{}`", other))),
+        };
+        let port = url.port_or_known_default().unwrap_or(if https { 443 } else { 80 });
+        let host = match url.host() {
+            Some(host) => host.to_owned(),
+            None => return Err(CheckError::new(ErrorKind::Other, format!("{} has no host", url))),
+        };
+
+        // Through a proxy the phases up to the TLS handshake are spent reaching the proxy
+        let proxy = self.proxy_for(url)?;
+        let (route_host, route_port) = match &proxy {
+            Some(proxy) => proxy_address(proxy)?,
+            None => (host.clone(), port),
+        };
+        let authority = format!("{}:{}", host, port);
+        let tunnel = proxy.as_ref().filter(|_| https).map(|proxy| (proxy, authority.as_str()));
+
+        trace.enter(Phase::Dns);
+        let addresses = resolve(&route_host, route_port).await;
+        trace.leave();
+        let addresses = addresses?;
+
+        trace.enter(Phase::Connect);
+        let stream = open(&addresses, tunnel).await;
+        trace.leave();
+        let stream = stream?;
+
+        if !https {
+            return exchange(stream, false, method, url, headers, body, proxy.as_ref(), trace).await;
+        }
+
+        let domain = match &host {
+            Host::Domain(domain) => domain.to_string(),
+            Host::Ipv4(ip) => ip.to_string(),
+            Host::Ipv6(ip) => ip.to_string(),
+        };
+        trace.enter(Phase::Tls);
+        let stream = tls::connect(tls, &domain, stream).await;
+        trace.leave();
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                // Connect again without verification to find out what was wrong with the certificate
+                trace.certificate = self.inspect(&addresses, tunnel, &domain).await;
+                let message = match &trace.certificate {
+                    Some(certificate) => format!("{}: {}", tls::rejection(certificate, &domain), err),
+                    None => format!("TLS handshake with {} failed: {}", domain, err),
+                };
+                return Err(CheckError::with_source(ErrorKind::Tls, message, err));
+            }
+        };
+
+        trace.certificate = tls::peer_certificate(stream.ssl());
+        if let Some(certificate) = trace.certificate.as_ref().filter(|certificate| certificate.is_expired()) {
+            return Err(CheckError::new(ErrorKind::Tls, tls::rejection(certificate, &domain)));
+        }
+
+        let http2 = stream.ssl().selected_alpn_protocol() == Some(b"h2");
+        exchange(stream, http2, method, url, headers, body, None, trace).await
+    }
+
+    /// The certificate `domain` presents, read over a connection that accepts any certificate.
+    async fn inspect(
+        &self,
+        addresses: &[SocketAddr],
+        tunnel: Option<(&Intercept, &str)>,
+        domain: &str,
+    ) -> Option<Certificate> {
+        let stream = open(addresses, tunnel).await.ok()?;
+        let stream = tls::connect(&self.inspect, domain, stream).await.ok()?;
+        tls::peer_certificate(stream.ssl())
+    }
+
+    /// The proxy to send a request for `url` through, if any.
+    fn proxy_for(&self, url: &Url) -> Result<Option<Intercept>, CheckError> {
+        // Only the scheme and host decide, and a fragment would keep the URL from parsing
+        let Ok(uri) = url[..url::Position::BeforePath].parse::<Uri>() else { return Ok(None) };
+        let Some(proxy) = self.proxies.intercept(&uri) else { return Ok(None) };
+        match proxy.uri().scheme_str() {
+            Some("http") => Ok(Some(proxy)),
+            other => Err(CheckError::new(
+                ErrorKind::Other,
+                format!("only http:// proxies are supported, not {}://", other.unwrap_or_default()),
+            )),
+        }
+    }
+
+    /// The headers sent on every hop: the client's defaults overlaid with the target's,
+    /// then its credentials.
+    fn headers(&self, target: &Target) -> Result<HeaderMap, CheckError> {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_static("*/*"));
+        if let Some(user_agent) = &self.user_agent {
+            headers.insert(header::USER_AGENT, user_agent.clone());
+        }
+        for (name, value) in &target.headers {
+            let invalid = |err: String| CheckError::new(ErrorKind::Other, format!("invalid header `{}`: {}", name, err));
+            let name = HeaderName::from_bytes(name.as_bytes()).map_err(|err| invalid(err.to_string()))?;
+            let value = HeaderValue::from_str(value).map_err(|err| invalid(err.to_string()))?;
+            headers.insert(name, value);
+        }
+        if let Some(auth) = &target.auth {
+            let value = auth.header_value().map_err(|err| {
+                CheckError::new(ErrorKind::Other, format!("invalid credentials for {}: {}", target.url, err))
+            })?;
+            headers.insert(header::AUTHORIZATION, value);
+        }
+        Ok(headers)
+    }
+}
+
+
+impl fmt::Debug for HttpClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpClient")
+            .field("user_agent", &self.user_agent)
+            .field("proxies", &self.proxies)
+            .finish_non_exhaustive()
+    }
+}
+
+
+async fn resolve(host: &Host<String>, port: u16) -> Result<Vec<SocketAddr>, CheckError> {
+    let domain = match host {
+        Host::Domain(domain) => domain.as_str(),
+        Host::Ipv4(ip) => return Ok(vec![SocketAddr::new(IpAddr::V4(*ip), port)]),
+        Host::Ipv6(ip) => return Ok(vec![SocketAddr::new(IpAddr::V6(*ip), port)]),
+    };
+
+    let addresses: Vec<SocketAddr> = tokio::net::lookup_host((domain, port))
+        .await
+        .map_err(|err| CheckError::with_source(ErrorKind::Dns, format!("could not resolve {}: {}", domain, err), err))?
+        .collect();
+    if addresses.is_empty() {
+        return Err(CheckError::new(ErrorKind::Dns, format!("{} has no addresses", domain)));
+    }
+    Ok(addresses)
+}
+
+
+/// The host and port of `proxy`, to resolve and connect to.
+fn proxy_address(proxy: &Intercept) -> Result<(Host<String>, u16), CheckError> {
+    let uri = proxy.uri();
+    let host = uri.host().and_then(|host| Host::parse(host).ok()).ok_or_else(|| {
+        CheckError::new(ErrorKind::Other, format!("the proxy {} has no valid host", proxy_name(proxy)))
+    })?;
+    Ok((host, uri.port_u16().unwrap_or(80)))
+}
+
+
+/// The proxy's address, without credentials.
+fn proxy_name(proxy: &Intercept) -> String {
+    proxy.uri().authority().map_or_else(String::new, |authority| authority.to_string())
+}
+
+
+/// Connects to the first address that accepts, reporting the last failure if none do.
+async fn connect(addresses: &[SocketAddr]) -> Result<TcpStream, CheckError> {
+    let mut last_error = None;
+    for address in addresses {
+        match TcpStream::connect(address).await {
+            Ok(stream) => return Ok(stream),
+            Err(err) => last_error = Some((address, err)),
+        }
+    }
+
+    let (address, err) = last_error.expect("resolve returns at least one address");
+    let kind = match err.kind() {
+        io::ErrorKind::ConnectionRefused => ErrorKind::ConnectionRefused,
+        io::ErrorKind::TimedOut => ErrorKind::Timeout,
+        _ => ErrorKind::Connect,
+    };
+    Err(CheckError::with_source(kind, format!("could not connect to {}: {}", address, err), err))
+}
+
+
+/// Connects to addresses that were already resolved, so that a [`Tunnel`] to a proxy
+/// doesn't look its name up again outside the DNS phase.
+#[derive(Clone)]
+struct Dial(Arc<[SocketAddr]>);
+
+
+impl Service<Uri> for Dial {
+    type Response = TokioIo<TcpStream>;
+    type Error = CheckError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, CheckError>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), CheckError>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _proxy: Uri) -> Self::Future {
+        let addresses = Arc::clone(&self.0);
+        Box::pin(async move { connect(&addresses).await.map(TokioIo::new) })
+    }
+}
+
+
+/// Connects to the first address that accepts and, for a `tunnel` through a proxy, asks
+/// it to pass the connection on to the authority (`host:port`).
+async fn open(addresses: &[SocketAddr], tunnel: Option<(&Intercept, &str)>) -> Result<TcpStream, CheckError> {
+    let Some((proxy, authority)) = tunnel else { return connect(addresses).await };
+
+    let invalid = |err: http::Error| {
+        CheckError::with_source(ErrorKind::Other, format!("invalid tunnel to {}: {}", authority, err), err)
+    };
+    let target = Uri::builder().scheme("https").authority(authority).path_and_query("/").build().map_err(invalid)?;
+    let mut tunnel = Tunnel::new(proxy.uri().clone(), Dial(addresses.into()));
+    if let Some(authorization) = proxy.basic_auth() {
+        tunnel = tunnel.with_auth(authorization.clone());
+    }
+
+    match tunnel.call(target).await {
+        Ok(stream) => Ok(stream.into_inner()),
+        // Not reaching the proxy at all keeps its own kind and message
+        Err(err) => match std::error::Error::source(&err).and_then(|source| source.downcast_ref::<CheckError>()) {
+            Some(err) => Err(err.clone()),
+            None => Err(CheckError::with_source(
+                ErrorKind::Connect,
+                format!("proxy {} could not open a tunnel to {}: {}", proxy_name(proxy), authority, err),
+                err,
+            )),
+        },
+    }
+}
+
+
+/// Reads up to `limit` bytes of `body`, returning them and whether more were left.
+async fn read_body(mut body: Incoming, limit: usize) -> Result<(Vec<u8>, bool), hyper::Error> {
+    let mut bytes = Vec::new();
+    while let Some(frame) = body.frame().await {
+        let Ok(chunk) = frame?.into_data() else { continue };
+        let room = limit - bytes.len();
+        if chunk.len() > room {
+            bytes.extend_from_slice(&chunk[..room]);
+            return Ok((bytes, true));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok((bytes, false))
+}
+
+
+/// Sends the request over an open connection, as HTTP/2 if `http2` was agreed in the TLS
+/// handshake, and waits for the response headers.
+///
+/// A request to a `proxy` names the whole URL, as proxies expect; so does every HTTP/2
+/// request, whose scheme and authority travel in the request line.
+#[allow(clippy::too_many_arguments)]
+async fn exchange<S>(
+    io: S,
+    http2: bool,
+    method: &Method,
+    url: &Url,
+    headers: &HeaderMap,
+    body: Option<&[u8]>,
+    proxy: Option<&Intercept>,
+    trace: &mut Trace,
+) -> Result<hyper::Response<Incoming>, CheckError>
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+{
+    let failed = |err: hyper::Error| {
+        CheckError::with_source(ErrorKind::Other, format!("request to {} failed: {}", url, err), err)
+    };
+
+    let mut host = url.host_str().unwrap_or_default().to_string();
+    if let Some(port) = url.port() {
+        host = format!("{}:{}", host, port);
+    }
+    let path = match (proxy.is_some() || http2, url.query()) {
+        (true, _) => url[..url::Position::AfterQuery].to_string(),
+        (false, Some(query)) => format!("{}?{}", url.path(), query),
+        (false, None) => url.path().to_string(),
+    };
+
+    let mut request = Request::builder()
+        .method(method.clone())
+        .uri(path)
+        .body(Full::new(body.map_or_else(Bytes::new, Bytes::copy_from_slice)))
+        .map_err(|err| CheckError::with_source(ErrorKind::Other, format!("invalid request for {}: {}", url, err), err))?;
+    *request.headers_mut() = headers.clone();
+    if !http2 && let Ok(host) = HeaderValue::from_str(&host) {
+        request.headers_mut().insert(header::HOST, host);
+    }
+    if let Some(authorization) = proxy.and_then(Intercept::basic_auth) {
+        request.headers_mut().insert(header::PROXY_AUTHORIZATION, authorization.clone());
+    }
+
+    trace.enter(Phase::FirstByte);
+    let io = TokioIo::new(io);
+    let response = if http2 {
+        let (mut sender, connection) = http2::handshake(TokioExecutor::new(), io).await.map_err(failed)?;
+        tokio::spawn(async move {
+            if let Err(err) = connection.await {
+                log::debug!("connection closed with an error: {}", err);
+            }
+        });
+        sender.send_request(request).await
+    } else {
+        let (mut sender, connection) = http1::handshake(io).await.map_err(failed)?;
+        tokio::spawn(async move {
+            if let Err(err) = connection.await {
+                log::debug!("connection closed with an error: {}", err);
+            }
+        });
+        sender.send_request(request).await
+    };
+    trace.leave();
+    response.map_err(failed)
+}
@@ -0,0 +1,1291 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use http::{HeaderName, Method};
+use url::Url;
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+
+use crate::alert::{AlertPolicy, Alerts, Notifier};
+use crate::auth::{Auth, ClientCert, Secret};
+use crate::checker::Engine;
+use crate::expect::{BodyAssertions, HeaderAssertions, JsonAssertion, RedirectAssertions, StatusSet};
+use crate::history::HistoryOptions;
+use crate::notify::{CommandNotifier, SlackNotifier, SmtpNotifier, SmtpSecurity, WebhookNotifier, DEFAULT_NOTIFY_TIMEOUT};
+use crate::redirect::RedirectPolicy;
+use crate::retry::{RetryOn, RetryPolicy, MAX_RETRIES};
+use crate::target::{Target, DEFAULT_CERT_EXPIRY_WARNING, DEFAULT_INTERVAL, DEFAULT_TIMEOUT};
+
+
+/// Prefix of the environment variables that override the file's `[defaults]`.
+pub const ENV_PREFIX: &str = "WSC_";
+
+/// The longest duration accepted anywhere, so deadlines computed from it can't overflow.
+const MAX_DURATION: Duration = Duration::from_secs(100 * 365 * 86400);
+
+
+/// Settings shared by every target unless the target overrides them.
+#[derive(Debug, Clone)]
+struct Defaults {
+    method: Method,
+    timeout: Duration,
+    retry: RetryPolicy,
+    expected_status: StatusSet,
+    redirects: RedirectPolicy,
+    redirect_assertions: RedirectAssertions,
+    header_assertions: HeaderAssertions,
+    capture_headers: Vec<String>,
+    body_assertions: BodyAssertions,
+    json_assertions: Vec<JsonAssertion>,
+    cert_expiry_warning: Duration,
+    headers: BTreeMap<String, String>,
+    interval: Duration,
+}
+
+
+impl Default for Defaults {
+    fn default() -> Self {
+        Defaults {
+            method: Method::GET,
+            timeout: DEFAULT_TIMEOUT,
+            retry: RetryPolicy::default(),
+            expected_status: StatusSet::default(),
+            redirects: RedirectPolicy::default(),
+            redirect_assertions: RedirectAssertions::default(),
+            header_assertions: HeaderAssertions::default(),
+            capture_headers: Vec::new(),
+            body_assertions: BodyAssertions::default(),
+            json_assertions: Vec::new(),
+            cert_expiry_warning: DEFAULT_CERT_EXPIRY_WARNING,
+            headers: BTreeMap::new(),
+            interval: DEFAULT_INTERVAL,
+        }
+    }
+}
+
+
+/// Values given explicitly on the command line; they win over the file and environment.
+#[derive(Debug, Default)]
+pub struct Overrides {
+    pub engine: Option<Engine>,
+    pub workers: Option<usize>,
+    pub concurrency: Option<usize>,
+    pub timeout: Option<Duration>,
+    pub retries: Option<u32>,
+    pub interval: Option<Duration>,
+    pub history: Option<PathBuf>,
+}
+
+
+/// Everything needed for a run, as loaded by [`load`].
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub engine: Engine,
+    pub workers: usize,
+    pub concurrency: usize,
+    pub targets: Vec<Target>,
+    /// Directory to keep every result in, if any; see [`History`](crate::history::History).
+    pub history: Option<PathBuf>,
+    pub history_options: HistoryOptions,
+    /// What to alert on in watch mode, and how; `None` without an `[alerts]` table.
+    pub alerts: Option<Alerts>,
+}
+
+
+/// Why a target file could not be loaded; the message names the file and the offending
+/// line, key or environment variable.
+#[derive(Debug, Clone)]
+pub struct ConfigError(String);
+
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+
+impl std::error::Error for ConfigError {}
+
+
+/// Settings that apply to the whole run rather than to individual targets.
+struct RunSettings {
+    engine: Engine,
+    workers: usize,
+    concurrency: usize,
+    history: Option<PathBuf>,
+    history_options: HistoryOptions,
+}
+
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileConfig {
+    engine: Option<Engine>,
+    workers: Option<usize>,
+    concurrency: Option<usize>,
+    history: Option<FileHistory>,
+    alerts: Option<FileAlerts>,
+    #[serde(default)]
+    defaults: FileDefaults,
+    #[serde(default)]
+    targets: Vec<FileTarget>,
+}
+
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileDefaults {
+    #[serde(default, deserialize_with = "de_method")]
+    method: Option<Method>,
+    #[serde(default, deserialize_with = "de_duration")]
+    timeout: Option<Duration>,
+    #[serde(default, deserialize_with = "de_retries")]
+    retries: Option<u32>,
+    retry: Option<FileRetry>,
+    #[serde(default, deserialize_with = "de_status_set")]
+    expected_status: Option<StatusSet>,
+    #[serde(default, deserialize_with = "de_redirect_policy")]
+    redirects: Option<RedirectPolicy>,
+    #[serde(default, deserialize_with = "de_final_url")]
+    final_url: Option<Url>,
+    #[serde(default, deserialize_with = "de_regex")]
+    final_url_matches: Option<Regex>,
+    require_https: Option<bool>,
+    #[serde(default, deserialize_with = "de_header_names")]
+    header_present: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "de_header_names")]
+    header_absent: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "de_header_equals")]
+    header_equals: Option<BTreeMap<String, String>>,
+    #[serde(default, deserialize_with = "de_header_patterns")]
+    header_matches: Option<BTreeMap<String, Regex>>,
+    #[serde(default, deserialize_with = "de_header_names")]
+    capture_headers: Option<Vec<String>>,
+    body_contains: Option<Vec<String>>,
+    body_not_contains: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "de_regexes")]
+    body_matches: Option<Vec<Regex>>,
+    #[serde(default, deserialize_with = "de_size")]
+    max_body_size: Option<usize>,
+    #[serde(default, deserialize_with = "de_json_assertions")]
+    json_assertions: Option<Vec<JsonAssertion>>,
+    #[serde(default, deserialize_with = "de_duration")]
+    cert_expiry_warning: Option<Duration>,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(default, deserialize_with = "de_duration")]
+    interval: Option<Duration>,
+}
+
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileTarget {
+    #[serde(deserialize_with = "de_url")]
+    url: String,
+    name: Option<String>,
+    #[serde(default, deserialize_with = "de_method")]
+    method: Option<Method>,
+    #[serde(default, deserialize_with = "de_duration")]
+    timeout: Option<Duration>,
+    #[serde(default, deserialize_with = "de_retries")]
+    retries: Option<u32>,
+    retry: Option<FileRetry>,
+    #[serde(default, deserialize_with = "de_status_set")]
+    expected_status: Option<StatusSet>,
+    #[serde(default, deserialize_with = "de_redirect_policy")]
+    redirects: Option<RedirectPolicy>,
+    #[serde(default, deserialize_with = "de_final_url")]
+    final_url: Option<Url>,
+    #[serde(default, deserialize_with = "de_regex")]
+    final_url_matches: Option<Regex>,
+    require_https: Option<bool>,
+    #[serde(default, deserialize_with = "de_header_names")]
+    header_present: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "de_header_names")]
+    header_absent: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "de_header_equals")]
+    header_equals: Option<BTreeMap<String, String>>,
+    #[serde(default, deserialize_with = "de_header_patterns")]
+    header_matches: Option<BTreeMap<String, Regex>>,
+    #[serde(default, deserialize_with = "de_header_names")]
+    capture_headers: Option<Vec<String>>,
+    body_contains: Option<Vec<String>>,
+    body_not_contains: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "de_regexes")]
+    body_matches: Option<Vec<Regex>>,
+    #[serde(default, deserialize_with = "de_size")]
+    max_body_size: Option<usize>,
+    #[serde(default, deserialize_with = "de_json_assertions")]
+    json_assertions: Option<Vec<JsonAssertion>>,
+    #[serde(default, deserialize_with = "de_duration")]
+    cert_expiry_warning: Option<Duration>,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(default)]
+    query: BTreeMap<String, String>,
+    auth: Option<FileAuth>,
+    client_cert: Option<FileClientCert>,
+    body: Option<String>,
+    body_file: Option<PathBuf>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default, deserialize_with = "de_duration")]
+    interval: Option<Duration>,
+}
+
+
+/// The `final_url*` and `require_https` keys of a `[defaults]` or target table.
+struct FileRedirectAssertions {
+    final_url: Option<Url>,
+    final_url_matches: Option<Regex>,
+    require_https: Option<bool>,
+}
+
+
+/// The `header_*` keys of a `[defaults]` or target table.
+struct FileHeaderAssertions {
+    present: Option<Vec<String>>,
+    absent: Option<Vec<String>>,
+    equals: Option<BTreeMap<String, String>>,
+    matches: Option<BTreeMap<String, Regex>>,
+}
+
+
+/// The `body_*` and `max_body_size` keys of a `[defaults]` or target table.
+struct FileBodyAssertions {
+    contains: Option<Vec<String>>,
+    not_contains: Option<Vec<String>>,
+    matches: Option<Vec<Regex>>,
+    max_size: Option<usize>,
+}
+
+
+/// An `auth` table. Secrets are named by environment variable or file, never written inline.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+enum FileAuth {
+    Basic {
+        username: String,
+        password_env: Option<String>,
+        password_file: Option<PathBuf>,
+    },
+    Bearer {
+        token_env: Option<String>,
+        token_file: Option<PathBuf>,
+    },
+}
+
+
+/// A `client_cert` table: PEM certificate and PKCS#8 key files.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileClientCert {
+    cert: PathBuf,
+    key: PathBuf,
+}
+
+
+/// A `retry` table; unset keys keep the value from the layer below.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileRetry {
+    #[serde(default, deserialize_with = "de_duration")]
+    initial_backoff: Option<Duration>,
+    #[serde(default, deserialize_with = "de_duration")]
+    max_backoff: Option<Duration>,
+    #[serde(default, deserialize_with = "de_multiplier")]
+    multiplier: Option<f64>,
+    #[serde(default, deserialize_with = "de_fraction")]
+    jitter: Option<f64>,
+    #[serde(default, deserialize_with = "de_duration")]
+    max_elapsed: Option<Duration>,
+    on: Option<Vec<RetryOn>>,
+}
+
+
+/// A `history` table; `path` is relative to the config file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileHistory {
+    path: PathBuf,
+    #[serde(default, deserialize_with = "de_duration")]
+    retention: Option<Duration>,
+    #[serde(default, deserialize_with = "de_duration")]
+    downsample_after: Option<Duration>,
+    #[serde(default, deserialize_with = "de_duration")]
+    resolution: Option<Duration>,
+}
+
+
+/// An `alerts` table.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileAlerts {
+    fail_after: Option<u32>,
+    recover_after: Option<u32>,
+    #[serde(default)]
+    notifiers: Vec<FileNotifier>,
+}
+
+
+/// One of the `alerts.notifiers`. Webhook URLs may be named by environment variable,
+/// since they usually embed a token.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+enum FileNotifier {
+    Webhook {
+        url: Option<String>,
+        url_env: Option<String>,
+        #[serde(default)]
+        headers: BTreeMap<String, String>,
+    },
+    Slack {
+        url: Option<String>,
+        url_env: Option<String>,
+    },
+    Smtp {
+        host: String,
+        port: Option<u16>,
+        #[serde(default)]
+        security: SmtpSecurity,
+        username: Option<String>,
+        password_env: Option<String>,
+        password_file: Option<PathBuf>,
+        from: String,
+        to: Vec<String>,
+        #[serde(default, deserialize_with = "de_duration")]
+        timeout: Option<Duration>,
+    },
+    Command {
+        command: Vec<String>,
+        #[serde(default, deserialize_with = "de_duration")]
+        timeout: Option<Duration>,
+    },
+}
+
+
+/// Loads targets from `path`.
+///
+/// `.toml`, `.yaml` and `.yml` files are parsed as configuration files; anything else is
+/// read as a plain list with one URL per line. Settings are layered as built-in defaults,
+/// then the file's `[defaults]`, then `WSC_*` environment variables, then `overrides`,
+/// and finally each target's own values. No two targets may have both the same name and
+/// the same URL.
+pub fn load(path: &Path, overrides: &Overrides) -> Result<Config, ConfigError> {
+    load_layers(path, overrides).map_err(ConfigError)
+}
+
+
+fn load_layers(path: &Path, overrides: &Overrides) -> Result<Config, String> {
+    let (file, url_list) = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => (parse_toml(path)?, false),
+        Some("yaml") | Some("yml") => (parse_yaml(path)?, false),
+        _ => (read_url_list(path)?, true),
+    };
+
+    // `history.path` and `body_file` paths are relative to the file they're written in
+    let base = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut defaults = Defaults::default();
+    let mut run = RunSettings {
+        engine: file.engine.unwrap_or_default(),
+        workers: file.workers.unwrap_or(4),
+        concurrency: file.concurrency.unwrap_or(1000),
+        history: None,
+        history_options: HistoryOptions::default(),
+    };
+    if let Some(history) = file.history {
+        apply_history(&mut run, history, base);
+    }
+    let alerts = file
+        .alerts
+        .map(|alerts| resolve_alerts(alerts, base))
+        .transpose()
+        .map_err(|(key, err)| format!("{}: key `alerts{}`: {}", path.display(), key, err))?;
+    apply_file_defaults(&mut defaults, file.defaults);
+    apply_env(&mut defaults, &mut run)?;
+
+    if let Some(value) = overrides.engine { run.engine = value; }
+    if let Some(value) = overrides.workers { run.workers = value; }
+    if let Some(value) = overrides.concurrency { run.concurrency = value; }
+    if let Some(value) = overrides.timeout { defaults.timeout = value; }
+    if let Some(value) = overrides.retries { defaults.retry.max_retries = value; }
+    if let Some(value) = overrides.interval { defaults.interval = value; }
+    if let Some(value) = &overrides.history { run.history = Some(value.clone()); }
+
+    if run.workers == 0 {
+        return Err(format!("{}: workers: at least one worker is required", path.display()));
+    }
+    if run.concurrency == 0 {
+        return Err(format!("{}: concurrency: must be at least one", path.display()));
+    }
+    if run.history_options.downsample_after > run.history_options.retention {
+        return Err(format!("{}: history.downsample_after: must not be longer than the retention", path.display()));
+    }
+    let targets: Vec<Target> = file
+        .targets
+        .into_iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            resolve(entry, &defaults, base)
+                .map_err(|(key, err)| format!("{}: key `targets[{}].{}`: {}", path.display(), index, key, err))
+        })
+        .collect::<Result<_, _>>()?;
+
+    // Watch mode tells targets apart by name and URL, so two alike would share one schedule
+    let mut seen = HashMap::new();
+    for (index, target) in targets.iter().enumerate() {
+        if let Some(first) = seen.insert(target.key(), index) {
+            return Err(if url_list {
+                format!("{}: {} is listed twice", path.display(), target.url)
+            } else {
+                format!(
+                    "{}: key `targets[{}]`: same name and URL as `targets[{}]`; give one of them a different `name`",
+                    path.display(),
+                    index,
+                    first
+                )
+            });
+        }
+    }
+    Ok(Config {
+        engine: run.engine,
+        workers: run.workers,
+        concurrency: run.concurrency,
+        targets,
+        history: run.history,
+        history_options: run.history_options,
+        alerts,
+    })
+}
+
+
+fn parse_toml(path: &Path) -> Result<FileConfig, String> {
+    let contents = read(path)?;
+    let deserializer = toml::Deserializer::parse(&contents)
+        .map_err(|err| format!("{}: {}", path.display(), err))?;
+    serde_path_to_error::deserialize(deserializer).map_err(|err| {
+        format!("{}: key `{}`: {}", path.display(), err.path(), err.inner())
+    })
+}
+
+
+fn parse_yaml(path: &Path) -> Result<FileConfig, String> {
+    let contents = read(path)?;
+    let deserializer = serde_yaml::Deserializer::from_str(&contents);
+    serde_path_to_error::deserialize(deserializer).map_err(|err| {
+        format!("{}: key `{}`: {}", path.display(), err.path(), err.inner())
+    })
+}
+
+
+fn read(path: &Path) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+
+fn read_url_list(path: &Path) -> Result<FileConfig, String> {
+    let file = File::open(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let reader = BufReader::new(file);
+    let mut config = FileConfig::default();
+
+    for (number, line) in reader.lines().enumerate() {
+        let line = line.map_err(|err| format!("{}: {}", path.display(), err))?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let url = parse_url(line)
+            .map_err(|err| format!("{}:{}: {}", path.display(), number + 1, err))?;
+        config.targets.push(FileTarget { url, ..Default::default() });
+    }
+
+    Ok(config)
+}
+
+
+fn apply_file_defaults(defaults: &mut Defaults, file: FileDefaults) {
+    if let Some(value) = file.method { defaults.method = value; }
+    if let Some(value) = file.timeout { defaults.timeout = value; }
+    if let Some(value) = file.retry { apply_retry(&mut defaults.retry, value); }
+    if let Some(value) = file.retries { defaults.retry.max_retries = value; }
+    if let Some(value) = file.expected_status { defaults.expected_status = value; }
+    if let Some(value) = file.redirects { defaults.redirects = value; }
+    apply_redirect_assertions(&mut defaults.redirect_assertions, FileRedirectAssertions {
+        final_url: file.final_url,
+        final_url_matches: file.final_url_matches,
+        require_https: file.require_https,
+    });
+    apply_header_assertions(&mut defaults.header_assertions, FileHeaderAssertions {
+        present: file.header_present,
+        absent: file.header_absent,
+        equals: file.header_equals,
+        matches: file.header_matches,
+    });
+    if let Some(value) = file.capture_headers { defaults.capture_headers = value; }
+    apply_body_assertions(&mut defaults.body_assertions, FileBodyAssertions {
+        contains: file.body_contains,
+        not_contains: file.body_not_contains,
+        matches: file.body_matches,
+        max_size: file.max_body_size,
+    });
+    if let Some(value) = file.json_assertions { defaults.json_assertions = value; }
+    if let Some(value) = file.cert_expiry_warning { defaults.cert_expiry_warning = value; }
+    if let Some(value) = file.interval { defaults.interval = value; }
+    defaults.headers.extend(file.headers);
+}
+
+
+fn apply_history(run: &mut RunSettings, file: FileHistory, base: &Path) {
+    run.history = Some(base.join(file.path));
+    if let Some(value) = file.retention { run.history_options.retention = value; }
+    if let Some(value) = file.downsample_after { run.history_options.downsample_after = value; }
+    if let Some(value) = file.resolution { run.history_options.resolution = value; }
+}
+
+
+fn apply_env(defaults: &mut Defaults, run: &mut RunSettings) -> Result<(), String> {
+    let var = |key: &str| std::env::var(format!("{}{}", ENV_PREFIX, key)).ok();
+    let invalid = |key: &str, err: String| format!("{}{}: {}", ENV_PREFIX, key, err);
+
+    if let Some(value) = var("ENGINE") {
+        run.engine = value.parse().map_err(|err| invalid("ENGINE", err))?;
+    }
+    if let Some(value) = var("WORKERS") {
+        run.workers = value.parse().map_err(|_| invalid("WORKERS", format!("`{}` is not a number", value)))?;
+    }
+    if let Some(value) = var("CONCURRENCY") {
+        run.concurrency = value.parse().map_err(|_| invalid("CONCURRENCY", format!("`{}` is not a number", value)))?;
+    }
+    if let Some(value) = var("HISTORY") {
+        run.history = Some(PathBuf::from(value));
+    }
+    if let Some(value) = var("METHOD") {
+        defaults.method = parse_method(&value).map_err(|err| invalid("METHOD", err))?;
+    }
+    if let Some(value) = var("TIMEOUT") {
+        defaults.timeout = parse_duration(&value).map_err(|err| invalid("TIMEOUT", err))?;
+    }
+    if let Some(value) = var("RETRIES") {
+        defaults.retry.max_retries = parse_retries(&value).map_err(|err| invalid("RETRIES", err))?;
+    }
+    if let Some(value) = var("RETRY_ON") {
+        defaults.retry.retry_on = value
+            .split(',')
+            .filter(|class| !class.trim().is_empty())
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map_err(|err| invalid("RETRY_ON", err))?;
+    }
+    if let Some(value) = var("EXPECTED_STATUS") {
+        defaults.expected_status = value.parse().map_err(|err| invalid("EXPECTED_STATUS", err))?;
+    }
+    if let Some(value) = var("REDIRECTS") {
+        defaults.redirects = value.parse().map_err(|err| invalid("REDIRECTS", err))?;
+    }
+    if let Some(value) = var("CERT_EXPIRY_WARNING") {
+        defaults.cert_expiry_warning = parse_duration(&value).map_err(|err| invalid("CERT_EXPIRY_WARNING", err))?;
+    }
+    if let Some(value) = var("INTERVAL") {
+        defaults.interval = parse_duration(&value).map_err(|err| invalid("INTERVAL", err))?;
+    }
+    Ok(())
+}
+
+
+fn apply_retry(policy: &mut RetryPolicy, file: FileRetry) {
+    if let Some(value) = file.initial_backoff { policy.initial_backoff = value; }
+    if let Some(value) = file.max_backoff { policy.max_backoff = value; }
+    if let Some(value) = file.multiplier { policy.multiplier = value; }
+    if let Some(value) = file.jitter { policy.jitter = value; }
+    if let Some(value) = file.max_elapsed { policy.max_elapsed = Some(value); }
+    if let Some(value) = file.on { policy.retry_on = value; }
+}
+
+
+fn apply_redirect_assertions(assertions: &mut RedirectAssertions, file: FileRedirectAssertions) {
+    if let Some(value) = file.final_url { assertions.final_url = Some(value); }
+    if let Some(value) = file.final_url_matches { assertions.final_url_matches = Some(value); }
+    if let Some(value) = file.require_https { assertions.require_https = value; }
+}
+
+
+fn apply_header_assertions(assertions: &mut HeaderAssertions, file: FileHeaderAssertions) {
+    if let Some(value) = file.present { assertions.present = value; }
+    if let Some(value) = file.absent { assertions.absent = value; }
+    if let Some(value) = file.equals { assertions.equals = value; }
+    if let Some(value) = file.matches { assertions.matches = value; }
+}
+
+
+fn apply_body_assertions(assertions: &mut BodyAssertions, file: FileBodyAssertions) {
+    if let Some(value) = file.contains { assertions.contains = value; }
+    if let Some(value) = file.not_contains { assertions.not_contains = value; }
+    if let Some(value) = file.matches { assertions.matches = value; }
+    if let Some(value) = file.max_size { assertions.max_size = value; }
+}
+
+
+/// Builds a target from its entry; an error names the offending key.
+fn resolve(entry: FileTarget, defaults: &Defaults, base: &Path) -> Result<Target, (&'static str, String)> {
+    let mut headers = defaults.headers.clone();
+    headers.extend(entry.headers);
+
+    let mut retry = defaults.retry.clone();
+    if let Some(value) = entry.retry { apply_retry(&mut retry, value); }
+    if let Some(value) = entry.retries { retry.max_retries = value; }
+
+    let mut redirect_assertions = defaults.redirect_assertions.clone();
+    apply_redirect_assertions(&mut redirect_assertions, FileRedirectAssertions {
+        final_url: entry.final_url,
+        final_url_matches: entry.final_url_matches,
+        require_https: entry.require_https,
+    });
+
+    let mut header_assertions = defaults.header_assertions.clone();
+    apply_header_assertions(&mut header_assertions, FileHeaderAssertions {
+        present: entry.header_present,
+        absent: entry.header_absent,
+        equals: entry.header_equals,
+        matches: entry.header_matches,
+    });
+
+    let mut body_assertions = defaults.body_assertions.clone();
+    apply_body_assertions(&mut body_assertions, FileBodyAssertions {
+        contains: entry.body_contains,
+        not_contains: entry.body_not_contains,
+        matches: entry.body_matches,
+        max_size: entry.max_body_size,
+    });
+
+    let body = match (entry.body, entry.body_file) {
+        (Some(_), Some(_)) => return Err(("body_file", "set either `body` or `body_file`, not both".to_string())),
+        (Some(body), None) => Some(body.into_bytes()),
+        (None, Some(file)) => {
+            let file = base.join(file);
+            let body = std::fs::read(&file).map_err(|err| ("body_file", format!("{}: {}", file.display(), err)))?;
+            Some(body)
+        }
+        (None, None) => None,
+    };
+
+    let auth = entry.auth.map(|auth| resolve_auth(auth, base)).transpose().map_err(|err| ("auth", err))?;
+    let client_cert = entry
+        .client_cert
+        .map(|cert| ClientCert::from_pem_files(&base.join(cert.cert), &base.join(cert.key)))
+        .transpose()
+        .map_err(|err| ("client_cert", err))?;
+
+    Ok(Target {
+        url: entry.url,
+        name: entry.name,
+        method: entry.method.unwrap_or_else(|| defaults.method.clone()),
+        timeout: entry.timeout.unwrap_or(defaults.timeout),
+        retry,
+        expected_status: entry.expected_status.unwrap_or_else(|| defaults.expected_status.clone()),
+        redirects: entry.redirects.unwrap_or(defaults.redirects),
+        redirect_assertions,
+        header_assertions,
+        capture_headers: entry.capture_headers.unwrap_or_else(|| defaults.capture_headers.clone()),
+        body_assertions,
+        json_assertions: entry.json_assertions.unwrap_or_else(|| defaults.json_assertions.clone()),
+        cert_expiry_warning: entry.cert_expiry_warning.unwrap_or(defaults.cert_expiry_warning),
+        headers,
+        query: entry.query,
+        auth,
+        client_cert,
+        body,
+        tags: entry.tags,
+        interval: entry.interval.unwrap_or(defaults.interval),
+    })
+}
+
+
+/// Errors name the offending key below `alerts`, e.g. `.notifiers[1].url`.
+fn resolve_alerts(file: FileAlerts, base: &Path) -> Result<Alerts, (String, String)> {
+    let mut policy = AlertPolicy::default();
+    if let Some(value) = file.fail_after { policy.fail_after = value; }
+    if let Some(value) = file.recover_after { policy.recover_after = value; }
+    if policy.fail_after == 0 {
+        return Err((".fail_after".to_string(), "must be at least one".to_string()));
+    }
+    if policy.recover_after == 0 {
+        return Err((".recover_after".to_string(), "must be at least one".to_string()));
+    }
+
+    let notifiers = file
+        .notifiers
+        .into_iter()
+        .enumerate()
+        .map(|(index, notifier)| {
+            resolve_notifier(notifier, base).map_err(|(key, err)| (format!(".notifiers[{}]{}", index, key), err))
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(Alerts { policy, notifiers })
+}
+
+
+fn resolve_notifier(notifier: FileNotifier, base: &Path) -> Result<Arc<dyn Notifier>, (&'static str, String)> {
+    Ok(match notifier {
+        FileNotifier::Webhook { url, url_env, headers } => {
+            let mut webhook = WebhookNotifier::new(webhook_url(url, url_env).map_err(|err| (".url", err))?);
+            for (name, value) in headers {
+                webhook = webhook.with_header(name, value);
+            }
+            Arc::new(webhook)
+        }
+        FileNotifier::Slack { url, url_env } => {
+            Arc::new(SlackNotifier::new(webhook_url(url, url_env).map_err(|err| (".url", err))?))
+        }
+        FileNotifier::Smtp { host, port, security, username, password_env, password_file, from, to, timeout } => {
+            if to.is_empty() {
+                return Err((".to", "at least one recipient is required".to_string()));
+            }
+            mail_address(&from).map_err(|err| (".from", err))?;
+            for address in &to {
+                mail_address(address).map_err(|err| (".to", err))?;
+            }
+            let credentials = match username {
+                Some(username) => Some((
+                    username,
+                    read_secret("password", password_env, password_file, base).map_err(|err| (".password_env", err))?,
+                )),
+                None if password_env.is_some() || password_file.is_some() => {
+                    return Err((".username", "a password was given without a username".to_string()));
+                }
+                None => None,
+            };
+            Arc::new(SmtpNotifier {
+                host,
+                port: port.unwrap_or_else(|| security.default_port()),
+                security,
+                credentials,
+                from,
+                to,
+                timeout: timeout.unwrap_or(DEFAULT_NOTIFY_TIMEOUT),
+            })
+        }
+        FileNotifier::Command { command, timeout } => {
+            let mut command = command.into_iter();
+            let program = command.next().ok_or((".command", "the program to run is missing".to_string()))?;
+            Arc::new(CommandNotifier {
+                program: PathBuf::from(program),
+                args: command.collect(),
+                timeout: timeout.unwrap_or(DEFAULT_NOTIFY_TIMEOUT),
+            })
+        }
+    })
+}
+
+
+/// Rejects an address that would break out of the SMTP command or header it is sent in.
+fn mail_address(address: &str) -> Result<(), String> {
+    if address.trim().is_empty() {
+        return Err("an address can't be empty".to_string());
+    }
+    if address.chars().any(char::is_control) {
+        return Err(format!("{:?} contains a control character", address));
+    }
+    Ok(())
+}
+
+
+/// Reads a webhook URL from whichever of `url` and `url_env` is set.
+fn webhook_url(url: Option<String>, env: Option<String>) -> Result<Secret, String> {
+    let url = match (url, env) {
+        (Some(url), None) => Secret::new(url),
+        (None, Some(var)) => Secret::from_env(&var)?,
+        _ => return Err("set exactly one of `url` and `url_env`".to_string()),
+    };
+    // The URL itself isn't repeated, since it may hold a token
+    parse_url(url.expose()).map_err(|_| "not a valid http or https URL".to_string())?;
+    Ok(url)
+}
+
+
+fn resolve_auth(auth: FileAuth, base: &Path) -> Result<Auth, String> {
+    Ok(match auth {
+        FileAuth::Basic { username, password_env, password_file } => Auth::Basic {
+            username,
+            password: read_secret("password", password_env, password_file, base)?,
+        },
+        FileAuth::Bearer { token_env, token_file } => Auth::Bearer {
+            token: read_secret("token", token_env, token_file, base)?,
+        },
+    })
+}
+
+
+/// Reads the secret `name` from whichever of `<name>_env` and `<name>_file` is set.
+fn read_secret(name: &str, env: Option<String>, file: Option<PathBuf>, base: &Path) -> Result<Secret, String> {
+    match (env, file) {
+        (Some(var), None) => Secret::from_env(&var),
+        (None, Some(file)) => Secret::from_file(&base.join(file)),
+        _ => Err(format!("set exactly one of `{0}_env` and `{0}_file`", name)),
+    }
+}
+
+
+/// Parses `250ms`, `3s`, `2m`, `1h`, `14d`, or a bare number of seconds.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("`{}` is not a duration like `500ms`, `3s` or `2m`", value))?;
+    let seconds = match unit.trim() {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        "d" => number * 86400.0,
+        other => return Err(format!("unknown duration unit `{}` (use ms, s, m, h or d)", other)),
+    };
+
+    if seconds <= 0.0 {
+        return Err("duration must be greater than zero".to_string());
+    }
+    Duration::try_from_secs_f64(seconds)
+        .ok()
+        .filter(|duration| *duration <= MAX_DURATION)
+        .ok_or_else(|| format!("`{}` is longer than 100 years", value))
+}
+
+
+fn parse_method(value: &str) -> Result<Method, String> {
+    Method::from_bytes(value.trim().to_ascii_uppercase().as_bytes())
+        .map_err(|_| format!("`{}` is not an HTTP method", value))
+}
+
+
+fn parse_url(value: &str) -> Result<String, String> {
+    let url = Url::parse(value).map_err(|err| format!("invalid URL `{}`: {}", value, err))?;
+    match url.scheme() {
+        "http" | "https" => Ok(value.to_string()),
+        other => Err(format!("unsupported URL scheme `{}` in `{}`", other, value)),
+    }
+}
+
+
+fn de_duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Seconds(u64),
+        Text(String),
+    }
+
+    match Raw::deserialize(deserializer)? {
+        Raw::Seconds(0) => Err(serde::de::Error::custom("duration must be greater than zero")),
+        Raw::Seconds(seconds) if Duration::from_secs(seconds) > MAX_DURATION => {
+            Err(serde::de::Error::custom(format!("`{}` is longer than 100 years", seconds)))
+        }
+        Raw::Seconds(seconds) => Ok(Some(Duration::from_secs(seconds))),
+        Raw::Text(text) => parse_duration(&text).map(Some).map_err(serde::de::Error::custom),
+    }
+}
+
+
+fn parse_retries(value: &str) -> Result<u32, String> {
+    match value.trim().parse() {
+        Ok(retries) if retries <= MAX_RETRIES => Ok(retries),
+        Ok(_) => Err(format!("at most {} retries are allowed", MAX_RETRIES)),
+        Err(_) => Err(format!("`{}` is not a number", value)),
+    }
+}
+
+
+fn de_retries<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+    let value = u32::deserialize(deserializer)?;
+    if value <= MAX_RETRIES {
+        Ok(Some(value))
+    } else {
+        Err(serde::de::Error::custom(format!("at most {} retries are allowed", MAX_RETRIES)))
+    }
+}
+
+
+fn de_multiplier<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f64>, D::Error> {
+    let value = f64::deserialize(deserializer)?;
+    if value >= 1.0 {
+        Ok(Some(value))
+    } else {
+        Err(serde::de::Error::custom("multiplier must be at least 1.0"))
+    }
+}
+
+
+fn de_fraction<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f64>, D::Error> {
+    let value = f64::deserialize(deserializer)?;
+    if (0.0..=1.0).contains(&value) {
+        Ok(Some(value))
+    } else {
+        Err(serde::de::Error::custom("must be between 0.0 and 1.0"))
+    }
+}
+
+
+/// Accepts a code, a string like `"2xx, 304"`, or a list of either.
+fn de_status_set<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<StatusSet>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Part {
+        Code(u16),
+        Text(String),
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        One(Part),
+        Many(Vec<Part>),
+    }
+
+    let parts = match Raw::deserialize(deserializer)? {
+        Raw::One(part) => vec![part],
+        Raw::Many(parts) => parts,
+    };
+    let text: Vec<String> = parts
+        .into_iter()
+        .map(|part| match part {
+            Part::Code(code) => code.to_string(),
+            Part::Text(text) => text,
+        })
+        .collect();
+    text.join(",").parse().map(Some).map_err(serde::de::Error::custom)
+}
+
+
+/// Accepts `"none"`, `"follow"`, or the maximum number of redirects.
+fn de_redirect_policy<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<RedirectPolicy>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Hops(usize),
+        Text(String),
+    }
+
+    match Raw::deserialize(deserializer)? {
+        Raw::Hops(0) => Ok(Some(RedirectPolicy::None)),
+        Raw::Hops(hops) => Ok(Some(RedirectPolicy::Follow(hops))),
+        Raw::Text(text) => text.parse().map(Some).map_err(serde::de::Error::custom),
+    }
+}
+
+
+fn de_final_url<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Url>, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    let url = parse_url(&value).map_err(serde::de::Error::custom)?;
+    Url::parse(&url).map(Some).map_err(serde::de::Error::custom)
+}
+
+
+fn de_regex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Regex>, D::Error> {
+    let pattern = String::deserialize(deserializer)?;
+    Regex::new(&pattern).map(Some).map_err(serde::de::Error::custom)
+}
+
+
+/// Header names, lowercased.
+fn de_header_names<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<String>>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|name| parse_header_name(name).map_err(serde::de::Error::custom))
+        .collect::<Result<_, _>>()
+        .map(Some)
+}
+
+
+fn de_header_equals<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<BTreeMap<String, String>>, D::Error> {
+    BTreeMap::<String, String>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(name, value)| Ok((parse_header_name(&name).map_err(serde::de::Error::custom)?, value)))
+        .collect::<Result<_, _>>()
+        .map(Some)
+}
+
+
+fn de_header_patterns<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<BTreeMap<String, Regex>>, D::Error> {
+    BTreeMap::<String, String>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(name, pattern)| {
+            let name = parse_header_name(&name).map_err(serde::de::Error::custom)?;
+            let pattern = Regex::new(&pattern).map_err(serde::de::Error::custom)?;
+            Ok((name, pattern))
+        })
+        .collect::<Result<_, _>>()
+        .map(Some)
+}
+
+
+fn parse_header_name(name: &str) -> Result<String, String> {
+    HeaderName::from_bytes(name.trim().as_bytes())
+        .map(|name| name.as_str().to_string())
+        .map_err(|_| format!("`{}` is not a valid header name", name))
+}
+
+
+fn de_regexes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<Regex>>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|pattern| Regex::new(pattern).map_err(serde::de::Error::custom))
+        .collect::<Result<_, _>>()
+        .map(Some)
+}
+
+
+fn de_json_assertions<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<JsonAssertion>>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|assertion| assertion.parse().map_err(serde::de::Error::custom))
+        .collect::<Result<_, _>>()
+        .map(Some)
+}
+
+
+/// Accepts a number of bytes, or a string like `512KB` or `2MiB`.
+fn de_size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<usize>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Bytes(usize),
+        Text(String),
+    }
+
+    match Raw::deserialize(deserializer)? {
+        Raw::Bytes(0) => Err(serde::de::Error::custom("size must be greater than zero")),
+        Raw::Bytes(bytes) => Ok(Some(bytes)),
+        Raw::Text(text) => parse_size(&text).map(Some).map_err(serde::de::Error::custom),
+    }
+}
+
+
+/// Parses `4096`, `4096B`, `64KB`/`64KiB` or `2MB`/`2MiB`; both prefixes mean powers of 1024.
+fn parse_size(value: &str) -> Result<usize, String> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let number: usize = number
+        .parse()
+        .map_err(|_| format!("`{}` is not a size like `4096`, `64KB` or `2MB`", value))?;
+    let multiplier = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1024,
+        "M" | "MB" | "MIB" => 1024 * 1024,
+        other => return Err(format!("unknown size unit `{}` (use B, KB or MB)", other)),
+    };
+
+    match number.checked_mul(multiplier) {
+        Some(0) => Err("size must be greater than zero".to_string()),
+        Some(bytes) => Ok(bytes),
+        None => Err(format!("size `{}` is too large", value)),
+    }
+}
+
+
+fn de_method<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Method>, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    parse_method(&value).map(Some).map_err(serde::de::Error::custom)
+}
+
+
+fn de_url<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    parse_url(&value).map_err(serde::de::Error::custom)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::sync::{Mutex, MutexGuard};
+
+    /// Held by every test that loads a file, as `WSC_*` variables reach every load.
+    static ENV: Mutex<()> = Mutex::new(());
+
+    fn lock_env() -> MutexGuard<'static, ()> {
+        ENV.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn write(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("wsc-config-{}-{}", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn load_error(name: &str, contents: &str) -> String {
+        let path = write(name, contents);
+        let err = load(&path, &Overrides::default()).unwrap_err().to_string();
+        std::fs::remove_file(&path).unwrap();
+        err.replace(&path.display().to_string(), "FILE")
+    }
+
+    #[test]
+    fn layers_apply_from_built_ins_to_the_target() {
+        let _env = lock_env();
+        let path = write("layers.toml", r#"
+            [defaults]
+            interval = "1m"
+            retries = 2
+            timeout = "5s"
+
+            [[targets]]
+            url = "https://example.com/"
+
+            [[targets]]
+            url = "https://example.org/"
+            timeout = "11s"
+        "#);
+        // SAFETY: every test that reads the environment holds the lock
+        unsafe {
+            std::env::set_var("WSC_RETRIES", "3");
+            std::env::set_var("WSC_TIMEOUT", "7s");
+        }
+        let overrides = Overrides { timeout: Some(Duration::from_secs(9)), ..Overrides::default() };
+        let config = load(&path, &overrides);
+        unsafe {
+            std::env::remove_var("WSC_RETRIES");
+            std::env::remove_var("WSC_TIMEOUT");
+        }
+        std::fs::remove_file(&path).unwrap();
+        let config = config.unwrap();
+
+        let [inherited, own] = &config.targets[..] else { panic!("{:?}", config.targets) };
+        assert_eq!(inherited.method, Method::GET);
+        assert_eq!(inherited.interval, Duration::from_secs(60));
+        assert_eq!(inherited.retry.max_retries, 3);
+        assert_eq!(inherited.timeout, Duration::from_secs(9));
+        assert_eq!(own.timeout, Duration::from_secs(11));
+    }
+
+    #[test]
+    fn environment_variables_override_the_file() {
+        let _env = lock_env();
+        let path = write("env.toml", r#"
+            workers = 8
+
+            [defaults]
+            method = "GET"
+
+            [[targets]]
+            url = "https://example.com/"
+        "#);
+        // SAFETY: every test that reads the environment holds the lock
+        unsafe {
+            std::env::set_var("WSC_WORKERS", "2");
+            std::env::set_var("WSC_METHOD", "head");
+            std::env::set_var("WSC_INTERVAL", "90s");
+        }
+        let config = load(&path, &Overrides::default());
+        unsafe { std::env::set_var("WSC_TIMEOUT", "soon") };
+        let invalid = load(&path, &Overrides::default());
+        for key in ["WORKERS", "METHOD", "INTERVAL", "TIMEOUT"] {
+            unsafe { std::env::remove_var(format!("{}{}", ENV_PREFIX, key)) };
+        }
+        std::fs::remove_file(&path).unwrap();
+        let config = config.unwrap();
+
+        assert_eq!(config.workers, 2);
+        assert_eq!(config.targets[0].method, Method::HEAD);
+        assert_eq!(config.targets[0].interval, Duration::from_secs(90));
+        assert_eq!(
+            invalid.unwrap_err().to_string(),
+            "WSC_TIMEOUT: `soon` is not a duration like `500ms`, `3s` or `2m`"
+        );
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        let _env = lock_env();
+        let err = load_error("unknown-default.toml", "[defaults]\ntimout = \"5s\"\n");
+        assert!(err.starts_with("FILE: key `defaults.timout`: "), "{}", err);
+        assert!(err.contains("unknown field `timout`"), "{}", err);
+
+        let err = load_error("unknown-target.toml", "[[targets]]\nurl = \"https://example.com/\"\nretires = 3\n");
+        assert!(err.starts_with("FILE: key `targets[0].retires`: "), "{}", err);
+        assert!(err.contains("unknown field `retires`"), "{}", err);
+
+        let err = load_error("unknown-top.yaml", "target:\n  - url: https://example.com/\n");
+        assert!(err.starts_with("FILE: key `target`: unknown field `target`, expected one of "), "{}", err);
+    }
+
+    #[test]
+    fn errors_name_the_file_and_key() {
+        let _env = lock_env();
+        let err = load_error("bad-timeout.toml", "[[targets]]\nurl = \"https://example.com/\"\ntimeout = \"soon\"\n");
+        assert!(err.starts_with("FILE: key `targets[0].timeout`: "), "{}", err);
+        assert!(err.contains("`soon` is not a duration"), "{}", err);
+
+        let err = load_error("bad-method.yaml", "defaults:\n  method: \"GE T\"\n");
+        assert!(err.starts_with("FILE: key `defaults.method`: "), "{}", err);
+        assert!(err.contains("`GE T` is not an HTTP method"), "{}", err);
+
+        let err = load_error("urls.txt", "# probes\nhttps://example.com/\nftp://example.com/\n");
+        assert_eq!(err, "FILE:3: unsupported URL scheme `ftp` in `ftp://example.com/`");
+    }
+
+    #[test]
+    fn targets_need_a_distinct_name_or_url() {
+        let _env = lock_env();
+        let path = write("names.toml", r#"
+            [[targets]]
+            url = "https://example.com/"
+
+            [[targets]]
+            url = "https://example.com/"
+            name = "example"
+        "#);
+        let config = load(&path, &Overrides::default());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(config.unwrap().targets.len(), 2);
+
+        let yaml = concat!(
+            "targets:\n",
+            "- {url: 'https://example.com/', name: a}\n",
+            "- url: https://example.org/\n",
+            "- {url: 'https://example.com/', name: a}\n",
+        );
+        let err = load_error("twice.yaml", yaml);
+        assert_eq!(
+            err,
+            "FILE: key `targets[2]`: same name and URL as `targets[0]`; give one of them a different `name`"
+        );
+
+        let err = load_error("twice.txt", "https://example.com/\nhttps://example.com/\n");
+        assert_eq!(err, "FILE: https://example.com/ is listed twice");
+    }
+
+    #[test]
+    fn sizes_take_bytes_kilobytes_and_megabytes() {
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert_eq!(parse_size(" 512B "), Ok(512));
+        assert_eq!(parse_size("64KB"), Ok(64 * 1024));
+        assert_eq!(parse_size("64 kib"), Ok(64 * 1024));
+        assert_eq!(parse_size("2MB"), Ok(2 * 1024 * 1024));
+        assert_eq!(parse_size("2M"), Ok(2 * 1024 * 1024));
+    }
+
+    #[test]
+    fn sizes_reject_zero_overflow_and_other_units() {
+        assert_eq!(parse_size("0KB"), Err("size must be greater than zero".to_string()));
+        assert_eq!(parse_size("1GB"), Err("unknown size unit `GB` (use B, KB or MB)".to_string()));
+        assert!(parse_size("1.5MB").is_err());
+        assert!(parse_size("MB").is_err());
+        assert_eq!(parse_size(&format!("{}MB", usize::MAX)), Err(format!("size `{}MB` is too large", usize::MAX)));
+    }
+}
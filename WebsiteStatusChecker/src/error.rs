@@ -0,0 +1,126 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+
+/// What broke during a check.
+///
+/// The names (as printed and in JSON) are stable so alerts can be routed on them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// The host name could not be resolved.
+    Dns,
+    /// The host actively refused the connection.
+    ConnectionRefused,
+    /// Any other failure to open the connection (unreachable, reset, ...).
+    Connect,
+    /// The TLS handshake failed, including untrusted or mismatched certificates.
+    Tls,
+    /// The request or connection timed out.
+    Timeout,
+    /// A redirect loop, more redirects than allowed, or redirects that didn't end where
+    /// the target's redirect assertions require.
+    Redirect,
+    /// A response arrived but its status wasn't one of the expected codes.
+    UnexpectedStatus,
+    /// The response failed one of the target's assertions on its content.
+    Assertion,
+    /// The run shut down before the check finished.
+    Aborted,
+    /// Anything else, including errors read from files that predate error kinds.
+    Other,
+}
+
+
+impl ErrorKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorKind::Dns => "dns",
+            ErrorKind::ConnectionRefused => "connection_refused",
+            ErrorKind::Connect => "connect",
+            ErrorKind::Tls => "tls",
+            ErrorKind::Timeout => "timeout",
+            ErrorKind::Redirect => "redirect",
+            ErrorKind::UnexpectedStatus => "unexpected_status",
+            ErrorKind::Assertion => "assertion",
+            ErrorKind::Aborted => "aborted",
+            ErrorKind::Other => "other",
+        }
+    }
+}
+
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+
+impl FromStr for ErrorKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        serde_json::from_value(serde_json::Value::String(value.trim().to_string()))
+            .map_err(|_| format!("unknown error kind `{}`", value))
+    }
+}
+
+
+/// Why a check failed: its [`ErrorKind`], a readable message, and the underlying
+/// I/O, TLS or HTTP error when the failure happened in this process.
+#[derive(Debug, Clone)]
+pub struct CheckError {
+    kind: ErrorKind,
+    message: String,
+    source: Option<Arc<dyn Error + Send + Sync>>,
+}
+
+
+impl CheckError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        CheckError { kind, message: message.into(), source: None }
+    }
+
+    /// An error caused by `source`; `message` should already describe it.
+    pub fn with_source(
+        kind: ErrorKind,
+        message: impl Into<String>,
+        source: impl Error + Send + Sync + 'static,
+    ) -> Self {
+        CheckError { kind, message: message.into(), source: Some(Arc::new(source)) }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+
+impl PartialEq for CheckError {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind && self.message == other.message
+    }
+}
+
+
+impl fmt::Display for CheckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+
+impl Error for CheckError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_deref().map(|err| err as &(dyn Error + 'static))
+    }
+}
@@ -0,0 +1,655 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use http::HeaderMap;
+use regex::Regex;
+use serde_json::Value;
+use url::Url;
+
+use crate::jsonpath::JsonPath;
+
+
+/// Body bytes read for assertions when nothing else is configured.
+pub const DEFAULT_MAX_BODY_SIZE: usize = 1024 * 1024;
+
+
+/// The status codes a target may answer with and still count as up.
+///
+/// Parsed from a comma-separated list of codes (`200`), classes (`2xx`) and ranges
+/// (`200-204`). The default accepts `2xx` and `3xx`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusSet(Vec<RangeInclusive<u16>>);
+
+
+impl StatusSet {
+    /// A set accepting any code in any of `ranges`.
+    pub fn new(ranges: impl IntoIterator<Item = RangeInclusive<u16>>) -> Self {
+        StatusSet(ranges.into_iter().collect())
+    }
+
+    /// A set accepting exactly `codes`.
+    pub fn codes(codes: impl IntoIterator<Item = u16>) -> Self {
+        StatusSet::new(codes.into_iter().map(|code| code..=code))
+    }
+
+    pub fn contains(&self, code: u16) -> bool {
+        self.0.iter().any(|range| range.contains(&code))
+    }
+}
+
+
+impl Default for StatusSet {
+    fn default() -> Self {
+        StatusSet::new([200..=299, 300..=399])
+    }
+}
+
+
+impl FromStr for StatusSet {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut ranges = Vec::new();
+        for part in value.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+            ranges.push(parse_range(part)?);
+        }
+        if ranges.is_empty() {
+            return Err("expected at least one status code".to_string());
+        }
+        Ok(StatusSet(ranges))
+    }
+}
+
+
+impl fmt::Display for StatusSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, range) in self.0.iter().enumerate() {
+            if index > 0 {
+                f.write_str(", ")?;
+            }
+            let (start, end) = (*range.start(), *range.end());
+            if start == end {
+                write!(f, "{}", start)?;
+            } else if start % 100 == 0 && end == start + 99 {
+                write!(f, "{}xx", start / 100)?;
+            } else {
+                write!(f, "{}-{}", start, end)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+
+/// Checks on the text of a response body.
+///
+/// Only the first `max_size` bytes are read, and the assertions see just those. The body
+/// is decoded as UTF-8, with invalid sequences replaced.
+#[derive(Debug, Clone)]
+pub struct BodyAssertions {
+    /// Text that must appear in the body.
+    pub contains: Vec<String>,
+    /// Text that must not appear, such as `Maintenance`.
+    pub not_contains: Vec<String>,
+    /// Patterns that must match somewhere in the body.
+    pub matches: Vec<Regex>,
+    pub max_size: usize,
+}
+
+
+impl Default for BodyAssertions {
+    fn default() -> Self {
+        BodyAssertions {
+            contains: Vec::new(),
+            not_contains: Vec::new(),
+            matches: Vec::new(),
+            max_size: DEFAULT_MAX_BODY_SIZE,
+        }
+    }
+}
+
+
+impl BodyAssertions {
+    /// Describes the first assertion `body` fails, if any.
+    pub fn check(&self, body: &str) -> Result<(), String> {
+        if let Some(text) = self.contains.iter().find(|text| !body.contains(text.as_str())) {
+            return Err(format!("body does not contain `{}`", text));
+        }
+        if let Some(text) = self.not_contains.iter().find(|text| body.contains(text.as_str())) {
+            return Err(format!("body contains `{}`", text));
+        }
+        if let Some(pattern) = self.matches.iter().find(|pattern| !pattern.is_match(body)) {
+            return Err(format!("body does not match /{}/", pattern));
+        }
+        Ok(())
+    }
+}
+
+
+/// Checks on the response headers. Header names are case-insensitive; a header sent
+/// several times is checked as its values joined with `, `.
+#[derive(Debug, Clone, Default)]
+pub struct HeaderAssertions {
+    /// Headers that must be sent.
+    pub present: Vec<String>,
+    /// Headers that must not be sent, such as `x-powered-by`.
+    pub absent: Vec<String>,
+    /// Headers that must have exactly this value.
+    pub equals: BTreeMap<String, String>,
+    /// Headers whose value must match the pattern.
+    pub matches: BTreeMap<String, Regex>,
+}
+
+
+impl HeaderAssertions {
+    /// Describes the first assertion `headers` fail, if any.
+    pub fn check(&self, headers: &HeaderMap) -> Result<(), String> {
+        for name in &self.present {
+            if header_value(headers, name).is_none() {
+                return Err(format!("header `{}` is missing", name));
+            }
+        }
+        for name in &self.absent {
+            if let Some(value) = header_value(headers, name) {
+                return Err(format!("header `{}` should be absent but is `{}`", name, value));
+            }
+        }
+        for (name, expected) in &self.equals {
+            match header_value(headers, name) {
+                Some(value) if value == *expected => {}
+                Some(value) => return Err(format!("header `{}` is `{}`, expected `{}`", name, value, expected)),
+                None => return Err(format!("header `{}` is missing, expected `{}`", name, expected)),
+            }
+        }
+        for (name, pattern) in &self.matches {
+            match header_value(headers, name) {
+                Some(value) if pattern.is_match(&value) => {}
+                Some(value) => return Err(format!("header `{}` is `{}`, which does not match /{}/", name, value, pattern)),
+                None => return Err(format!("header `{}` is missing, expected a match for /{}/", name, pattern)),
+            }
+        }
+        Ok(())
+    }
+}
+
+
+/// All values of header `name` joined with `, `, or `None` if it wasn't sent.
+pub(crate) fn header_value(headers: &HeaderMap, name: &str) -> Option<String> {
+    let values: Vec<String> = headers
+        .get_all(name.trim().to_ascii_lowercase().as_str())
+        .iter()
+        .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+        .collect();
+    if values.is_empty() {
+        None
+    } else {
+        Some(values.join(", "))
+    }
+}
+
+
+/// Checks on the URL a check ends up at after following redirects.
+#[derive(Debug, Clone, Default)]
+pub struct RedirectAssertions {
+    /// The exact URL the redirects must lead to.
+    pub final_url: Option<Url>,
+    /// A pattern the final URL must match.
+    pub final_url_matches: Option<Regex>,
+    /// Whether the final URL must be `https`, to catch a broken HTTP to HTTPS redirect.
+    pub require_https: bool,
+}
+
+
+impl RedirectAssertions {
+    /// Describes the first assertion `final_url` fails, if any.
+    pub fn check(&self, final_url: &Url) -> Result<(), String> {
+        if self.require_https && final_url.scheme() != "https" {
+            return Err(format!("ended at {}, which is not https", final_url));
+        }
+        if let Some(expected) = self.final_url.as_ref().filter(|expected| *expected != final_url) {
+            return Err(format!("ended at {}, expected {}", final_url, expected));
+        }
+        if let Some(pattern) = self.final_url_matches.as_ref().filter(|pattern| !pattern.is_match(final_url.as_str())) {
+            return Err(format!("ended at {}, which does not match /{}/", final_url, pattern));
+        }
+        Ok(())
+    }
+}
+
+
+/// At most this many failing values are listed in one failure reason.
+const MAX_REPORTED_MISMATCHES: usize = 5;
+
+
+/// A check on a JSON response body, written as `<path> <op> <value>` or `<path> exists`.
+///
+/// The path is a [`JsonPath`] subset and the value is JSON, so strings need quotes:
+/// `$.status == "ok"`, `$.dependencies[*].healthy == true`, `$.queue.depth < 100`.
+/// Operators are `==`, `!=`, `<`, `<=`, `>` and `>=`; the ordering ones compare numbers.
+/// When the path selects several values, every one of them must pass, and a path that
+/// selects nothing fails.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonAssertion {
+    path: JsonPath,
+    check: JsonCheck,
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+enum JsonCheck {
+    Exists,
+    Compare(Comparison, Value),
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+
+impl Comparison {
+    const ALL: [(&'static str, Comparison); 6] = [
+        ("==", Comparison::Equal),
+        ("!=", Comparison::NotEqual),
+        ("<=", Comparison::LessOrEqual),
+        (">=", Comparison::GreaterOrEqual),
+        ("<", Comparison::Less),
+        (">", Comparison::Greater),
+    ];
+
+    fn symbol(self) -> &'static str {
+        Comparison::ALL.iter().find(|(_, op)| *op == self).map_or("", |(symbol, _)| symbol)
+    }
+
+    fn holds(self, actual: &Value, expected: &Value) -> bool {
+        match self {
+            Comparison::Equal => actual == expected,
+            Comparison::NotEqual => actual != expected,
+            ordering => match (actual.as_f64(), expected.as_f64()) {
+                (Some(actual), Some(expected)) => match ordering {
+                    Comparison::Less => actual < expected,
+                    Comparison::LessOrEqual => actual <= expected,
+                    Comparison::Greater => actual > expected,
+                    _ => actual >= expected,
+                },
+                _ => false,
+            },
+        }
+    }
+}
+
+
+impl FromStr for JsonAssertion {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (path, rest) = JsonPath::parse_prefix(value.trim())?;
+        let rest = rest.trim();
+        if rest == "exists" {
+            return Ok(JsonAssertion { path, check: JsonCheck::Exists });
+        }
+
+        let (symbol, comparison) = Comparison::ALL
+            .iter()
+            .find(|(symbol, _)| rest.starts_with(symbol))
+            .ok_or_else(|| format!("expected `==`, `!=`, `<`, `<=`, `>`, `>=` or `exists` after `{}` in `{}`", path, value))?;
+        let operand = rest[symbol.len()..].trim();
+        let expected: Value = serde_json::from_str(operand)
+            .map_err(|_| format!("`{}` in `{}` is not a JSON value; quote strings, as in \"ok\"", operand, value))?;
+        if !matches!(comparison, Comparison::Equal | Comparison::NotEqual) && !expected.is_number() {
+            return Err(format!("`{}` compares with `{}`, which needs a number", value, symbol));
+        }
+        Ok(JsonAssertion { path, check: JsonCheck::Compare(*comparison, expected) })
+    }
+}
+
+
+impl fmt::Display for JsonAssertion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.check {
+            JsonCheck::Exists => write!(f, "{} exists", self.path),
+            JsonCheck::Compare(comparison, expected) => write!(f, "{} {} {}", self.path, comparison.symbol(), expected),
+        }
+    }
+}
+
+
+impl JsonAssertion {
+    /// Describes every value in `root` that fails this assertion.
+    fn mismatches(&self, root: &Value) -> Vec<String> {
+        let selected = self.path.select(root);
+        if selected.is_empty() {
+            return vec![format!("{}: expected {}, found nothing", self.path, self.expectation())];
+        }
+
+        let JsonCheck::Compare(comparison, expected) = &self.check else { return Vec::new() };
+        selected
+            .into_iter()
+            .filter(|(_, actual)| !comparison.holds(actual, expected))
+            .map(|(path, actual)| format!("{}: expected {}, got {}", path, self.expectation(), preview(actual)))
+            .collect()
+    }
+
+    fn expectation(&self) -> String {
+        match &self.check {
+            JsonCheck::Exists => "a value".to_string(),
+            JsonCheck::Compare(Comparison::Equal, expected) => preview(expected),
+            JsonCheck::Compare(Comparison::NotEqual, expected) => format!("anything but {}", preview(expected)),
+            JsonCheck::Compare(comparison, expected) => format!("{} {}", comparison.symbol(), preview(expected)),
+        }
+    }
+}
+
+
+/// Parses `body` as JSON and describes every value that fails one of `assertions`.
+pub fn check_json(assertions: &[JsonAssertion], body: &str) -> Result<(), String> {
+    if assertions.is_empty() {
+        return Ok(());
+    }
+    let root: Value = serde_json::from_str(body).map_err(|err| format!("body is not JSON: {}", err))?;
+
+    let mismatches: Vec<String> = assertions.iter().flat_map(|assertion| assertion.mismatches(&root)).collect();
+    if mismatches.is_empty() {
+        return Ok(());
+    }
+    let mut reason = mismatches[..mismatches.len().min(MAX_REPORTED_MISMATCHES)].join("; ");
+    if mismatches.len() > MAX_REPORTED_MISMATCHES {
+        reason.push_str(&format!("; and {} more", mismatches.len() - MAX_REPORTED_MISMATCHES));
+    }
+    Err(reason)
+}
+
+
+/// Compact JSON for a failure reason, cut short if it is long.
+fn preview(value: &Value) -> String {
+    const LIMIT: usize = 80;
+    let text = value.to_string();
+    match text.char_indices().nth(LIMIT) {
+        Some((cut, _)) => format!("{}...", &text[..cut]),
+        None => text,
+    }
+}
+
+
+fn parse_range(part: &str) -> Result<RangeInclusive<u16>, String> {
+    let invalid = || format!("`{}` is not a status code, class like `2xx` or range like `200-204`", part);
+
+    if let Some(class) = part.strip_suffix("xx").or_else(|| part.strip_suffix("XX")) {
+        let class: u16 = class.parse().map_err(|_| invalid())?;
+        if !(1..=5).contains(&class) {
+            return Err(invalid());
+        }
+        return Ok(class * 100..=class * 100 + 99);
+    }
+
+    let (start, end) = match part.split_once('-') {
+        Some((start, end)) => (start.trim(), end.trim()),
+        None => (part, part),
+    };
+    let start = parse_code(start).ok_or_else(invalid)?;
+    let end = parse_code(end).ok_or_else(invalid)?;
+    if start > end {
+        return Err(format!("status range `{}` ends before it starts", part));
+    }
+    Ok(start..=end)
+}
+
+
+fn parse_code(value: &str) -> Option<u16> {
+    value.parse().ok().filter(|code| (100..=599).contains(code))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_sets_take_codes_classes_and_ranges() {
+        let set: StatusSet = " 200, 3XX,401-403 ,".parse().unwrap();
+        for code in [200, 300, 301, 399, 401, 402, 403] {
+            assert!(set.contains(code), "{}", code);
+        }
+        for code in [199, 201, 299, 400, 404, 500] {
+            assert!(!set.contains(code), "{}", code);
+        }
+        assert_eq!(set.to_string(), "200, 3xx, 401-403");
+    }
+
+    #[test]
+    fn status_sets_reject_anything_else() {
+        for value in ["", " , ", "ok", "0xx", "6xx", "2x", "99", "600", "200-", "-204", "2xx-3xx", "200-700"] {
+            assert!(value.parse::<StatusSet>().is_err(), "{}", value);
+        }
+        assert_eq!("".parse::<StatusSet>().unwrap_err(), "expected at least one status code");
+        assert_eq!("204-200".parse::<StatusSet>().unwrap_err(), "status range `204-200` ends before it starts");
+        assert_eq!(
+            "2xx, 6xx".parse::<StatusSet>().unwrap_err(),
+            "`6xx` is not a status code, class like `2xx` or range like `200-204`"
+        );
+    }
+
+    #[test]
+    fn default_status_set_is_2xx_and_3xx() {
+        let set = StatusSet::default();
+        assert_eq!(set.to_string(), "2xx, 3xx");
+        assert!(set.contains(200) && set.contains(304) && set.contains(399));
+        assert!(!set.contains(199) && !set.contains(400));
+        assert_eq!(StatusSet::codes([200, 204]).to_string(), "200, 204");
+        assert_eq!(StatusSet::new([100..=199, 200..=250]).to_string(), "1xx, 200-250");
+    }
+
+    fn body(contains: &[&str], not_contains: &[&str], matches: &[&str]) -> BodyAssertions {
+        BodyAssertions {
+            contains: contains.iter().map(|text| text.to_string()).collect(),
+            not_contains: not_contains.iter().map(|text| text.to_string()).collect(),
+            matches: matches.iter().map(|pattern| Regex::new(pattern).unwrap()).collect(),
+            ..BodyAssertions::default()
+        }
+    }
+
+    #[test]
+    fn body_assertions_report_the_first_failure() {
+        let assertions = body(&["Welcome", "Cart"], &["Maintenance"], &["<title>[^<]*Shop</title>"]);
+
+        assert_eq!(assertions.check("<title>The Shop</title> Welcome! Cart (0)"), Ok(()));
+        assert_eq!(
+            assertions.check("<title>The Shop</title> Welcome!"),
+            Err("body does not contain `Cart`".to_string())
+        );
+        assert_eq!(
+            assertions.check("<title>The Shop</title> Welcome! Cart. Down for Maintenance"),
+            Err("body contains `Maintenance`".to_string())
+        );
+        assert_eq!(
+            assertions.check("<title>Error</title> Welcome! Cart"),
+            Err("body does not match /<title>[^<]*Shop</title>/".to_string())
+        );
+    }
+
+    #[test]
+    fn body_text_is_matched_exactly() {
+        let assertions = body(&["Welcome"], &["maintenance"], &[]);
+        assert!(assertions.check("welcome").is_err());
+        assert!(assertions.check("Welcome, Maintenance is over").is_ok());
+        assert_eq!(body(&[], &[], &[]).check(""), Ok(()));
+        assert_eq!(BodyAssertions::default().max_size, DEFAULT_MAX_BODY_SIZE);
+    }
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    #[test]
+    fn redirect_assertions_report_the_first_failure() {
+        let assertions = RedirectAssertions {
+            final_url: Some(url("https://example.com/")),
+            final_url_matches: Some(Regex::new(r"^https://(www\.)?example\.com/").unwrap()),
+            require_https: true,
+        };
+
+        assert_eq!(assertions.check(&url("https://example.com/")), Ok(()));
+        assert_eq!(
+            assertions.check(&url("http://example.com/")),
+            Err("ended at http://example.com/, which is not https".to_string())
+        );
+        assert_eq!(
+            assertions.check(&url("https://www.example.com/")),
+            Err("ended at https://www.example.com/, expected https://example.com/".to_string())
+        );
+    }
+
+    #[test]
+    fn redirect_patterns_match_the_whole_url() {
+        let assertions = RedirectAssertions {
+            final_url_matches: Some(Regex::new(r"/login\?next=").unwrap()),
+            ..RedirectAssertions::default()
+        };
+        assert_eq!(assertions.check(&url("http://example.com/login?next=%2F")), Ok(()));
+        assert_eq!(
+            assertions.check(&url("http://example.com/home")),
+            Err(r"ended at http://example.com/home, which does not match //login\?next=/".to_string())
+        );
+
+        // URLs are compared after normalization, and nothing is required by default
+        let exact = RedirectAssertions { final_url: Some(url("HTTPS://Example.com")), ..RedirectAssertions::default() };
+        assert_eq!(exact.check(&url("https://example.com/")), Ok(()));
+        assert_eq!(RedirectAssertions::default().check(&url("ftp://example.com/")), Ok(()));
+    }
+
+    const HEALTH: &str = r#"{
+        "status": "degraded",
+        "version": "1.2",
+        "queue": {"depth": 812},
+        "dependencies": [{"name": "db", "healthy": true}, {"name": "cache", "healthy": false}]
+    }"#;
+
+    fn json(assertions: &[&str]) -> Result<(), String> {
+        let assertions: Vec<JsonAssertion> = assertions.iter().map(|assertion| assertion.parse().unwrap()).collect();
+        check_json(&assertions, HEALTH)
+    }
+
+    #[test]
+    fn json_assertions_parse_and_print() {
+        for (written, printed) in [
+            ("$.status == \"ok\"", "$.status == \"ok\""),
+            ("$.queue.depth<100", "$.queue.depth < 100"),
+            ("  $.queue.depth >= 1.5 ", "$.queue.depth >= 1.5"),
+            ("$.dependencies[*].healthy != false", "$.dependencies[*].healthy != false"),
+            ("$.version exists", "$.version exists"),
+            ("$['a b'] == null", "$['a b'] == null"),
+        ] {
+            let assertion: JsonAssertion = written.parse().unwrap();
+            assert_eq!(assertion.to_string(), printed);
+        }
+    }
+
+    #[test]
+    fn json_assertions_reject_what_they_cant_check() {
+        assert_eq!(
+            "$.status == ok".parse::<JsonAssertion>().unwrap_err(),
+            "`ok` in `$.status == ok` is not a JSON value; quote strings, as in \"ok\""
+        );
+        assert_eq!(
+            "$.version < \"2\"".parse::<JsonAssertion>().unwrap_err(),
+            "`$.version < \"2\"` compares with `<`, which needs a number"
+        );
+        assert!("$.status ~ 1".parse::<JsonAssertion>().unwrap_err().starts_with("expected `==`"));
+        assert!("$.status".parse::<JsonAssertion>().is_err());
+        assert!("status == 1".parse::<JsonAssertion>().is_err());
+    }
+
+    #[test]
+    fn json_assertions_check_every_selected_value() {
+        assert_eq!(json(&["$.status == \"degraded\"", "$.queue.depth > 800", "$.version exists"]), Ok(()));
+        assert_eq!(json(&["$.dependencies[*].name != \"\"", "$.queue.depth <= 812"]), Ok(()));
+        assert_eq!(
+            json(&["$.dependencies[*].healthy == true"]),
+            Err("$.dependencies[1].healthy: expected true, got false".to_string())
+        );
+        assert_eq!(json(&["$.uptime exists"]), Err("$.uptime: expected a value, found nothing".to_string()));
+        assert_eq!(
+            json(&["$.status != \"degraded\"", "$.queue.depth < 100"]),
+            Err("$.status: expected anything but \"degraded\", got \"degraded\"; $.queue.depth: expected < 100, got 812".to_string())
+        );
+        // Ordering only holds between numbers
+        assert!(json(&["$.version > 1"]).is_err());
+    }
+
+    #[test]
+    fn json_failures_are_capped_and_need_json() {
+        let body = format!("[{}]", ["0"; 8].join(","));
+        let assertion: JsonAssertion = "$[*] == 1".parse().unwrap();
+        let reason = check_json(std::slice::from_ref(&assertion), &body).unwrap_err();
+        assert_eq!(reason.matches("expected 1, got 0").count(), MAX_REPORTED_MISMATCHES);
+        assert!(reason.ends_with("; and 3 more"), "{}", reason);
+
+        assert!(check_json(&[assertion], "<html>").unwrap_err().starts_with("body is not JSON: "));
+        assert_eq!(check_json(&[], "<html>"), Ok(()));
+    }
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(*name, http::HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    #[test]
+    fn header_values_are_joined_and_names_case_insensitive() {
+        let sent = headers(&[("cache-control", "no-store"), ("cache-control", "max-age=0"), ("server", "nginx")]);
+        assert_eq!(header_value(&sent, "Cache-Control"), Some("no-store, max-age=0".to_string()));
+        assert_eq!(header_value(&sent, " SERVER "), Some("nginx".to_string()));
+        assert_eq!(header_value(&sent, "x-missing"), None);
+    }
+
+    #[test]
+    fn header_assertions_report_the_first_failure() {
+        let sent = headers(&[("content-type", "application/json"), ("cache-control", "max-age=60"), ("x-powered-by", "PHP")]);
+        let assertions = |change: fn(&mut HeaderAssertions)| {
+            let mut assertions = HeaderAssertions {
+                present: vec!["Content-Type".to_string()],
+                equals: BTreeMap::from([("content-type".to_string(), "application/json".to_string())]),
+                matches: BTreeMap::from([("Cache-Control".to_string(), Regex::new(r"max-age=\d+").unwrap())]),
+                ..HeaderAssertions::default()
+            };
+            change(&mut assertions);
+            assertions.check(&sent)
+        };
+
+        assert_eq!(assertions(|_| {}), Ok(()));
+        assert_eq!(
+            assertions(|rules| rules.present.push("strict-transport-security".to_string())),
+            Err("header `strict-transport-security` is missing".to_string())
+        );
+        assert_eq!(
+            assertions(|rules| rules.absent.push("X-Powered-By".to_string())),
+            Err("header `X-Powered-By` should be absent but is `PHP`".to_string())
+        );
+        assert_eq!(
+            assertions(|rules| { rules.equals.insert("content-type".to_string(), "text/html".to_string()); }),
+            Err("header `content-type` is `application/json`, expected `text/html`".to_string())
+        );
+        assert_eq!(
+            assertions(|rules| { rules.equals.insert("x-version".to_string(), "2".to_string()); }),
+            Err("header `x-version` is missing, expected `2`".to_string())
+        );
+        assert_eq!(
+            assertions(|rules| { rules.matches.insert("cache-control".to_string(), Regex::new("no-store").unwrap()); }),
+            Err("header `cache-control` is `max-age=60`, which does not match /no-store/".to_string())
+        );
+        assert_eq!(
+            assertions(|rules| { rules.matches.insert("etag".to_string(), Regex::new(".").unwrap()); }),
+            Err("header `etag` is missing, expected a match for /./".to_string())
+        );
+    }
+}
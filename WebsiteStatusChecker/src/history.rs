@@ -0,0 +1,500 @@
+//! A persistent, append-only store of check results.
+//!
+//! A history is a directory holding two JSON-lines files:
+//!
+//! - `results.jsonl`: every result at full resolution, one schema
+//!   [`Line`](crate::schema::Line) per check, appended as checks finish.
+//! - `rollups.jsonl`: older results downsampled into one [`Rollup`] per target and
+//!   period (an hour by default), with the counts needed for uptime and error rates.
+//!
+//! [`History::compact`] moves results older than `downsample_after` into rollups and drops
+//! anything older than `retention`, rewriting each file atomically. Clones of a
+//! `History` share a lock, so reads never see a compaction half done, but only one
+//! process should write to a history at a time.
+//!
+//! A last line left partly written, by a crash or by an append still under way, is
+//! skipped when reading and cut off before the next append.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::check::CheckResult;
+use crate::error::ErrorKind;
+use crate::schema::{self, Line, RollupRecord};
+
+
+const RESULTS_FILE: &str = "results.jsonl";
+const ROLLUPS_FILE: &str = "rollups.jsonl";
+
+
+/// How long a history keeps results, and at what resolution.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryOptions {
+    /// Anything older than this is deleted.
+    pub retention: Duration,
+    /// Results older than this are replaced by rollups.
+    pub downsample_after: Duration,
+    /// The period each rollup covers.
+    pub resolution: Duration,
+}
+
+
+impl Default for HistoryOptions {
+    fn default() -> Self {
+        HistoryOptions {
+            retention: Duration::from_secs(90 * 24 * 3600),
+            downsample_after: Duration::from_secs(7 * 24 * 3600),
+            resolution: Duration::from_secs(3600),
+        }
+    }
+}
+
+
+/// The checks of one target during one period, summed up.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "RollupRecord", try_from = "RollupRecord")]
+pub struct Rollup {
+    pub url: String,
+    pub name: Option<String>,
+    /// The start of the period; it lasts the history's `resolution`.
+    pub start: DateTime<Utc>,
+    pub period: Duration,
+    pub checks: usize,
+    pub up: usize,
+    /// Failed checks by what broke.
+    pub errors: BTreeMap<ErrorKind, usize>,
+    /// Checks that got a response, and their response times.
+    pub responses: usize,
+    pub mean_response_time: Option<Duration>,
+    pub min_response_time: Option<Duration>,
+    pub max_response_time: Option<Duration>,
+}
+
+
+impl Rollup {
+    /// The target's name if it has one, otherwise the URL.
+    pub fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.url)
+    }
+
+    pub fn end(&self) -> DateTime<Utc> {
+        let period = TimeDelta::from_std(self.period).unwrap_or(TimeDelta::MAX);
+        self.start.checked_add_signed(period).unwrap_or(DateTime::<Utc>::MAX_UTC)
+    }
+
+    fn key(&self) -> (String, DateTime<Utc>) {
+        (format!("{}\n{}", self.label(), self.url), self.start)
+    }
+
+    fn empty(result: &CheckResult, start: DateTime<Utc>, period: Duration) -> Self {
+        Rollup {
+            url: result.url.clone(),
+            name: result.name.clone(),
+            start,
+            period,
+            checks: 0,
+            up: 0,
+            errors: BTreeMap::new(),
+            responses: 0,
+            mean_response_time: None,
+            min_response_time: None,
+            max_response_time: None,
+        }
+    }
+
+    fn add(&mut self, result: &CheckResult) {
+        self.checks += 1;
+        match result.error_kind() {
+            Some(kind) => *self.errors.entry(kind).or_insert(0) += 1,
+            None => self.up += 1,
+        }
+        if result.status.is_some() {
+            let time = result.response_time;
+            let total = self.mean_response_time.unwrap_or_default() * self.responses as u32 + time;
+            self.responses += 1;
+            self.mean_response_time = Some(total / self.responses as u32);
+            self.min_response_time = Some(self.min_response_time.map_or(time, |min| min.min(time)));
+            self.max_response_time = Some(self.max_response_time.map_or(time, |max| max.max(time)));
+        }
+    }
+
+    fn merge(&mut self, other: Rollup) {
+        self.checks += other.checks;
+        self.up += other.up;
+        for (kind, count) in other.errors {
+            *self.errors.entry(kind).or_insert(0) += count;
+        }
+        if let Some(mean) = other.mean_response_time {
+            let total = self.mean_response_time.unwrap_or_default() * self.responses as u32 + mean * other.responses as u32;
+            self.responses += other.responses;
+            self.mean_response_time = (self.responses > 0).then(|| total / self.responses as u32);
+        }
+        self.min_response_time = self.min_response_time.into_iter().chain(other.min_response_time).min();
+        self.max_response_time = self.max_response_time.into_iter().chain(other.max_response_time).max();
+    }
+}
+
+
+/// Everything a history holds from some point on.
+#[derive(Debug, Clone, Default)]
+pub struct Contents {
+    /// Full-resolution results, oldest first.
+    pub results: Vec<CheckResult>,
+    /// Downsampled periods, oldest first.
+    pub rollups: Vec<Rollup>,
+}
+
+
+/// A history directory; see the [module docs](self).
+#[derive(Debug, Clone)]
+pub struct History {
+    dir: PathBuf,
+    options: HistoryOptions,
+    lock: Arc<RwLock<()>>,
+}
+
+
+impl History {
+    /// Opens the history in `dir`, creating the directory if needed.
+    pub fn open(dir: impl Into<PathBuf>, options: HistoryOptions) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(History { dir, options, lock: Arc::default() })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Appends `results` to the full-resolution file.
+    pub fn append(&self, results: &[CheckResult]) -> io::Result<()> {
+        let _guard = self.lock.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let path = self.dir.join(RESULTS_FILE);
+        let mut file = OpenOptions::new().create(true).read(true).append(true).open(&path)?;
+        cut_partial_line(&mut file, &path)?;
+        let mut file = BufWriter::new(file);
+        for result in results {
+            serde_json::to_writer(&mut file, &Line::new(result.clone()))?;
+            writeln!(file)?;
+        }
+        file.flush()
+    }
+
+    /// Reads everything newer than `since`, or everything if it's `None`.
+    pub fn read(&self, since: Option<DateTime<Utc>>) -> io::Result<Contents> {
+        let _guard = self.lock.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        self.read_unlocked(since)
+    }
+
+    fn read_unlocked(&self, since: Option<DateTime<Utc>>) -> io::Result<Contents> {
+        let newer = |timestamp: DateTime<Utc>| since.is_none_or(|since| timestamp >= since);
+
+        let mut results: Vec<CheckResult> = read_lines(&self.dir.join(RESULTS_FILE))?
+            .into_iter()
+            .map(|line: Line| line.result)
+            .filter(|result| newer(result.timestamp))
+            .collect();
+        results.sort_by_key(|result| result.timestamp);
+
+        let mut rollups: Vec<Rollup> = read_lines(&self.dir.join(ROLLUPS_FILE))?
+            .into_iter()
+            .filter(|rollup: &Rollup| newer(rollup.end()))
+            .collect();
+        rollups.sort_by_key(|rollup| rollup.start);
+
+        Ok(Contents { results, rollups })
+    }
+
+    /// Downsamples results older than `downsample_after` and deletes anything older than
+    /// `retention`, as of `now`. A duration reaching back past the earliest time that can
+    /// be represented keeps everything.
+    pub fn compact(&self, now: DateTime<Utc>) -> io::Result<()> {
+        let ago = |duration: Duration| {
+            TimeDelta::from_std(duration).ok().and_then(|duration| now.checked_sub_signed(duration))
+        };
+        let expired = ago(self.options.retention).unwrap_or(DateTime::<Utc>::MIN_UTC);
+        let old = ago(self.options.downsample_after).unwrap_or(DateTime::<Utc>::MIN_UTC);
+
+        let _guard = self.lock.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let contents = self.read_unlocked(None)?;
+        let (old_results, recent): (Vec<CheckResult>, Vec<CheckResult>) =
+            contents.results.into_iter().partition(|result| result.timestamp < old);
+        if old_results.is_empty() && contents.rollups.iter().all(|rollup| rollup.end() >= expired) {
+            return Ok(());
+        }
+
+        let mut rollups: BTreeMap<(String, DateTime<Utc>), Rollup> = BTreeMap::new();
+        for rollup in contents.rollups {
+            merge_rollup(&mut rollups, rollup);
+        }
+        for result in &old_results {
+            let start = period_start(result.timestamp, self.options.resolution);
+            let mut rollup = Rollup::empty(result, start, self.options.resolution);
+            rollup.add(result);
+            merge_rollup(&mut rollups, rollup);
+        }
+        rollups.retain(|_, rollup| rollup.end() >= expired);
+
+        log::info!(
+            "compacted history in {}: {} results downsampled into {} rollups",
+            self.dir.display(),
+            old_results.len(),
+            rollups.len()
+        );
+        let mut rollups: Vec<Rollup> = rollups.into_values().collect();
+        rollups.sort_by_key(|rollup| rollup.start);
+        write_lines(&self.dir.join(ROLLUPS_FILE), rollups.iter())?;
+
+        let recent: Vec<Line> = recent
+            .into_iter()
+            .filter(|result| result.timestamp >= expired)
+            .map(Line::new)
+            .collect();
+        write_lines(&self.dir.join(RESULTS_FILE), recent.iter())
+    }
+}
+
+
+fn merge_rollup(rollups: &mut BTreeMap<(String, DateTime<Utc>), Rollup>, rollup: Rollup) {
+    match rollups.get_mut(&rollup.key()) {
+        Some(existing) => existing.merge(rollup),
+        None => {
+            rollups.insert(rollup.key(), rollup);
+        }
+    }
+}
+
+
+/// The start of the `resolution`-long period containing `timestamp`, counted from the epoch.
+fn period_start(timestamp: DateTime<Utc>, resolution: Duration) -> DateTime<Utc> {
+    let step = resolution.as_secs().max(1) as i64;
+    let seconds = timestamp.timestamp();
+    DateTime::from_timestamp(seconds - seconds.rem_euclid(step), 0).unwrap_or(timestamp)
+}
+
+
+/// Reads a JSON-lines file, treating a missing file as empty and checking each line's
+/// schema version. A last line without a newline that can't be read is skipped.
+fn read_lines<T: for<'de> Deserialize<'de>>(path: &Path) -> io::Result<Vec<T>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+
+    let mut items = Vec::new();
+    let mut reader = BufReader::new(file);
+    let mut line = String::new();
+    for number in 1.. {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_line(&line) {
+            Ok(item) => items.push(item),
+            Err(err) if !line.ends_with('\n') => {
+                log::warn!("{}:{}: skipping a partly written last line: {}", path.display(), number, err);
+            }
+            Err(err) => {
+                let message = format!("{}:{}: {}", path.display(), number, err);
+                return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+            }
+        }
+    }
+    Ok(items)
+}
+
+
+fn parse_line<T: for<'de> Deserialize<'de>>(line: &str) -> Result<T, String> {
+    let value: serde_json::Value = serde_json::from_str(line).map_err(|err| err.to_string())?;
+    let version = value.get("schema_version").and_then(serde_json::Value::as_u64).unwrap_or(0);
+    schema::check_version(version as u32).map_err(|err| err.to_string())?;
+    serde_json::from_value(value).map_err(|err| err.to_string())
+}
+
+
+/// Cuts off a last line that doesn't end in a newline, left by a writer that crashed
+/// part way through, so the next line appended to `file` starts on a line of its own.
+fn cut_partial_line(file: &mut File, path: &Path) -> io::Result<()> {
+    let len = file.metadata()?.len();
+    let mut end = len;
+    let mut buffer = [0; 4096];
+    while end > 0 {
+        let start = end.saturating_sub(buffer.len() as u64);
+        let chunk = &mut buffer[..(end - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(chunk)?;
+        if let Some(newline) = chunk.iter().rposition(|byte| *byte == b'\n') {
+            end = start + newline as u64 + 1;
+            break;
+        }
+        end = start;
+    }
+    if end < len {
+        log::warn!("{}: cutting off a partly written last line of {} bytes", path.display(), len - end);
+        file.set_len(end)?;
+    }
+    Ok(())
+}
+
+
+/// Replaces the file at `path` with `items`, one per line, through a temporary file.
+fn write_lines<'a, T: Serialize + 'a>(path: &Path, items: impl Iterator<Item = &'a T>) -> io::Result<()> {
+    let temporary = path.with_extension("jsonl.tmp");
+    let mut file = BufWriter::new(File::create(&temporary)?);
+    for item in items {
+        serde_json::to_writer(&mut file, item)?;
+        writeln!(file)?;
+    }
+    file.into_inner().map_err(io::IntoInnerError::into_error)?.sync_all()?;
+    fs::rename(temporary, path)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::CheckError;
+    use crate::target::Target;
+
+    /// On the hour.
+    const NOON: i64 = 1_699_999_200;
+
+    fn at(minutes: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(NOON + minutes * 60, 0).unwrap()
+    }
+
+    /// A check finished `minutes` after noon: up with a response time in milliseconds, or down.
+    fn result(minutes: i64, millis: Option<u64>) -> CheckResult {
+        let mut result = CheckResult::aborted(&Target::new("https://example.com/"), Duration::ZERO);
+        result.timestamp = at(minutes);
+        result.error = None;
+        match millis {
+            Some(millis) => {
+                result.status = Some(200);
+                result.response_time = Duration::from_millis(millis);
+            }
+            None => result.error = Some(CheckError::new(ErrorKind::Timeout, "timed out")),
+        }
+        result
+    }
+
+    fn history(name: &str, options: HistoryOptions) -> History {
+        let dir = std::env::temp_dir().join(format!("wsc-history-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        History::open(dir, options).unwrap()
+    }
+
+    fn hours(hours: u64) -> Duration {
+        Duration::from_secs(hours * 3600)
+    }
+
+    #[test]
+    fn periods_start_on_multiples_of_the_resolution() {
+        assert_eq!(period_start(at(95), hours(1)), at(60));
+        assert_eq!(period_start(at(60), hours(1)), at(60));
+        assert_eq!(period_start(at(-1), hours(1)), at(-60));
+        assert_eq!(period_start(at(95), Duration::from_secs(15 * 60)), at(90));
+        // Resolutions under a second count as a second
+        assert_eq!(period_start(at(95), Duration::from_millis(10)), at(95));
+    }
+
+    #[test]
+    fn rollups_sum_checks_and_response_times() {
+        let results = [result(0, Some(10)), result(5, None), result(10, Some(30))];
+        let mut rollup = Rollup::empty(&results[0], at(0), hours(1));
+        for result in &results {
+            rollup.add(result);
+        }
+        assert_eq!((rollup.checks, rollup.up, rollup.responses), (3, 2, 2));
+        assert_eq!(rollup.errors, BTreeMap::from([(ErrorKind::Timeout, 1)]));
+        assert_eq!(rollup.mean_response_time, Some(Duration::from_millis(20)));
+        assert_eq!(rollup.end(), at(60));
+
+        let mut other = Rollup::empty(&results[0], at(0), hours(1));
+        other.add(&result(20, Some(60)));
+        other.add(&result(25, None));
+        rollup.merge(other);
+        assert_eq!((rollup.checks, rollup.up, rollup.responses), (5, 3, 3));
+        assert_eq!(rollup.errors, BTreeMap::from([(ErrorKind::Timeout, 2)]));
+        assert_eq!(rollup.mean_response_time, Some(Duration::from_millis(100) / 3));
+        assert_eq!(rollup.min_response_time, Some(Duration::from_millis(10)));
+        assert_eq!(rollup.max_response_time, Some(Duration::from_millis(60)));
+    }
+
+    #[test]
+    fn compaction_downsamples_then_expires() {
+        let options = HistoryOptions { retention: hours(3), downsample_after: hours(1), resolution: hours(1) };
+        let history = history("compact", options);
+        let results = [result(-70, Some(5)), result(20, Some(10)), result(50, None), result(150, Some(30))];
+        history.append(&results).unwrap();
+
+        // At three o'clock the hour before eleven has expired, and the hour after noon is summed up
+        history.compact(at(180)).unwrap();
+        let contents = history.read(None).unwrap();
+        assert_eq!(contents.results.len(), 1);
+        assert_eq!(contents.results[0].timestamp, at(150));
+        assert_eq!(contents.rollups.len(), 1);
+        assert_eq!((contents.rollups[0].start, contents.rollups[0].checks, contents.rollups[0].up), (at(0), 2, 1));
+
+        // Reads leave out what ended before `since`
+        let later = history.read(Some(at(61))).unwrap();
+        assert_eq!((later.results.len(), later.rollups.len()), (1, 0));
+
+        // Compacting again changes nothing, and later everything expires
+        history.compact(at(180)).unwrap();
+        assert_eq!(history.read(None).unwrap().rollups, contents.rollups);
+        history.compact(at(600)).unwrap();
+        let contents = history.read(None).unwrap();
+        assert!(contents.results.is_empty() && contents.rollups.is_empty());
+    }
+
+    #[test]
+    fn cut_offs_too_far_back_keep_everything() {
+        let options = HistoryOptions {
+            retention: Duration::MAX,
+            downsample_after: Duration::from_secs(1 << 50),
+            resolution: hours(1),
+        };
+        let history = history("overflow", options);
+        history.append(&[result(0, Some(10)), result(60, Some(10))]).unwrap();
+
+        history.compact(at(120)).unwrap();
+        let contents = history.read(None).unwrap();
+        assert_eq!((contents.results.len(), contents.rollups.len()), (2, 0));
+    }
+
+    #[test]
+    fn only_a_partial_last_line_is_tolerated() {
+        let history = history("partial", HistoryOptions::default());
+        let path = history.dir().join(RESULTS_FILE);
+        history.append(&[result(0, Some(10)), result(1, Some(10))]).unwrap();
+
+        // A crash part way through an append
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"schema_version": 2, "url": "https://exa"#).unwrap();
+        assert_eq!(history.read(None).unwrap().results.len(), 2);
+
+        history.append(&[result(2, Some(10))]).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        assert_eq!(contents.lines().count(), 3);
+        assert_eq!(history.read(None).unwrap().results.len(), 3);
+
+        // A bad line with more after it is an error
+        file.write_all(b"not json\n").unwrap();
+        history.append(&[result(3, Some(10))]).unwrap();
+        let err = history.read(None).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("results.jsonl:4: "), "{}", err);
+    }
+}
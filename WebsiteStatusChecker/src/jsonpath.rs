@@ -0,0 +1,205 @@
+use std::fmt;
+
+use serde_json::Value;
+
+
+/// A JSONPath expression limited to `$`, `.key`, `['key']`, `[index]`, `[*]` and `.*`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPath {
+    segments: Vec<Segment>,
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+    Wildcard,
+}
+
+
+impl JsonPath {
+    /// Parses a path from the start of `input`, returning it and the unparsed rest.
+    ///
+    /// The path ends at the first whitespace or other character that can't continue it.
+    pub fn parse_prefix(input: &str) -> Result<(JsonPath, &str), String> {
+        let mut rest = input
+            .strip_prefix('$')
+            .ok_or_else(|| format!("`{}` is not a JSONPath; it must start with `$`", input))?;
+        let mut segments = Vec::new();
+
+        loop {
+            if let Some(after) = rest.strip_prefix(".*") {
+                segments.push(Segment::Wildcard);
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix('.') {
+                let end = after.find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-')).unwrap_or(after.len());
+                if end == 0 {
+                    return Err(format!("expected a key after `.` in `{}`", input));
+                }
+                segments.push(Segment::Key(after[..end].to_string()));
+                rest = &after[end..];
+            } else if let Some(after) = rest.strip_prefix('[') {
+                // A quoted key may itself contain `]`, so find its closing quote first
+                let close = match after.chars().next() {
+                    Some(quote @ ('\'' | '"')) => after[1..].find(quote).map(|end| end + 2),
+                    _ => after.find(']'),
+                };
+                let close = close
+                    .filter(|&close| after[close..].starts_with(']'))
+                    .ok_or_else(|| format!("unclosed `[` in `{}`", input))?;
+                segments.push(parse_bracket(after[..close].trim())
+                    .ok_or_else(|| format!("`[{}]` in `{}` is not an index, `*` or quoted key", &after[..close], input))?);
+                rest = &after[close + 1..];
+            } else {
+                return Ok((JsonPath { segments }, rest));
+            }
+        }
+    }
+
+    /// Every value the path selects in `root`, with the concrete path to each.
+    pub fn select<'a>(&self, root: &'a Value) -> Vec<(String, &'a Value)> {
+        let mut current = vec![("$".to_string(), root)];
+        for segment in &self.segments {
+            let mut next = Vec::new();
+            for (path, value) in current {
+                match (segment, value) {
+                    (Segment::Key(key), Value::Object(map)) => {
+                        if let Some(child) = map.get(key) {
+                            next.push((format!("{}{}", path, key_segment(key)), child));
+                        }
+                    }
+                    (Segment::Index(index), Value::Array(items)) => {
+                        if let Some(child) = items.get(*index) {
+                            next.push((format!("{}[{}]", path, index), child));
+                        }
+                    }
+                    (Segment::Wildcard, Value::Array(items)) => {
+                        next.extend(items.iter().enumerate().map(|(index, child)| (format!("{}[{}]", path, index), child)));
+                    }
+                    (Segment::Wildcard, Value::Object(map)) => {
+                        next.extend(map.iter().map(|(key, child)| (format!("{}{}", path, key_segment(key)), child)));
+                    }
+                    _ => {}
+                }
+            }
+            current = next;
+        }
+        current
+    }
+}
+
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("$")?;
+        for segment in &self.segments {
+            match segment {
+                Segment::Key(key) => f.write_str(&key_segment(key))?,
+                Segment::Index(index) => write!(f, "[{}]", index)?,
+                Segment::Wildcard => f.write_str("[*]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+
+fn parse_bracket(inner: &str) -> Option<Segment> {
+    if inner == "*" {
+        return Some(Segment::Wildcard);
+    }
+    if let Ok(index) = inner.parse() {
+        return Some(Segment::Index(index));
+    }
+    let quoted = inner
+        .strip_prefix('\'')
+        .and_then(|key| key.strip_suffix('\''))
+        .or_else(|| inner.strip_prefix('"').and_then(|key| key.strip_suffix('"')))?;
+    Some(Segment::Key(quoted.to_string()))
+}
+
+
+/// `.key` when the key is a plain identifier, otherwise `['key']`.
+fn key_segment(key: &str) -> String {
+    let plain = !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+    if plain {
+        format!(".{}", key)
+    } else {
+        format!("['{}']", key)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn parse(input: &str) -> Result<JsonPath, String> {
+        match JsonPath::parse_prefix(input)? {
+            (path, "") => Ok(path),
+            (_, rest) => Err(format!("left over: `{}`", rest)),
+        }
+    }
+
+    #[test]
+    fn parses_each_kind_of_segment() {
+        let path = parse("$.data.items[0]['weird key]'][*].*[\"x-y\"]").unwrap();
+        assert_eq!(path.to_string(), "$.data.items[0]['weird key]'][*][*].x-y");
+        assert_eq!(parse("$").unwrap().to_string(), "$");
+        assert_eq!(parse("$[ 2 ]").unwrap().to_string(), "$[2]");
+    }
+
+    #[test]
+    fn parsing_stops_where_the_path_ends() {
+        let (path, rest) = JsonPath::parse_prefix("$.queue.depth < 100").unwrap();
+        assert_eq!(path.to_string(), "$.queue.depth");
+        assert_eq!(rest, " < 100");
+
+        let (path, rest) = JsonPath::parse_prefix("$.status==\"ok\"").unwrap();
+        assert_eq!(path.to_string(), "$.status");
+        assert_eq!(rest, "==\"ok\"");
+    }
+
+    #[test]
+    fn rejects_malformed_paths() {
+        assert_eq!(parse("status").unwrap_err(), "`status` is not a JSONPath; it must start with `$`");
+        assert_eq!(parse("$.").unwrap_err(), "expected a key after `.` in `$.`");
+        assert_eq!(parse("$.a[1").unwrap_err(), "unclosed `[` in `$.a[1`");
+        assert_eq!(parse("$['a]").unwrap_err(), "unclosed `[` in `$['a]`");
+        assert_eq!(parse("$[a]").unwrap_err(), "`[a]` in `$[a]` is not an index, `*` or quoted key");
+        assert!(parse("$[-1]").is_err());
+    }
+
+    #[test]
+    fn selects_values_with_their_concrete_paths() {
+        let root = json!({
+            "status": "ok",
+            "dependencies": [{"name": "db", "healthy": true}, {"name": "cache", "healthy": false}, {"name": "queue"}],
+            "weird key]": 1,
+        });
+        let select = |path: &str| -> Vec<(String, Value)> {
+            parse(path).unwrap().select(&root).into_iter().map(|(path, value)| (path, value.clone())).collect()
+        };
+
+        assert_eq!(select("$.status"), [("$.status".to_string(), json!("ok"))]);
+        assert_eq!(
+            select("$.dependencies[*].healthy"),
+            [("$.dependencies[0].healthy".to_string(), json!(true)), ("$.dependencies[1].healthy".to_string(), json!(false))]
+        );
+        assert_eq!(select("$.dependencies[2].name"), [("$.dependencies[2].name".to_string(), json!("queue"))]);
+        assert_eq!(select("$['weird key]']"), [("$['weird key]']".to_string(), json!(1))]);
+        assert_eq!(select("$.*").len(), 3);
+        assert_eq!(select("$").len(), 1);
+    }
+
+    #[test]
+    fn selects_nothing_where_the_document_differs() {
+        let root = json!({"items": [1, 2], "count": 2});
+        for path in ["$.missing", "$.items[2]", "$.items.first", "$.count[0]", "$.count.*", "$.count[*]"] {
+            assert!(parse(path).unwrap().select(&root).is_empty(), "{}", path);
+        }
+    }
+}
@@ -0,0 +1,71 @@
+//! Concurrent website status checking.
+//!
+//! Describe what to probe with [`Target`]s (built in code or loaded with
+//! [`config::load`]), run them through a [`Checker`], and hand each
+//! [`CheckResult`] to one or more [`Reporter`]s as it arrives.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use website_status_checker::{Checker, Reporter, Target, TerminalReporter};
+//!
+//! let checker = Checker::builder().workers(8).build().unwrap();
+//! let targets = vec![
+//!     Target::new("https://example.com/"),
+//!     Target::new("https://example.org/health").with_timeout(Duration::from_secs(1)),
+//! ];
+//!
+//! let mut reporter = TerminalReporter::default();
+//! let mut results = Vec::new();
+//! for result in checker.stream(targets) {
+//!     reporter.on_result(&result).unwrap();
+//!     results.push(result);
+//! }
+//! reporter.finish(&results).unwrap();
+//! ```
+
+pub mod alert;
+pub mod auth;
+pub mod check;
+pub mod checker;
+pub mod client;
+pub mod config;
+pub mod error;
+pub mod expect;
+pub mod history;
+pub mod jsonpath;
+pub mod metrics;
+pub mod notify;
+mod pool;
+pub mod redirect;
+pub mod report;
+pub mod retry;
+pub mod schema;
+pub mod serve;
+pub mod shutdown;
+pub mod stats;
+pub mod target;
+mod tasks;
+pub mod tls;
+pub mod watch;
+
+pub use alert::{AlertEvent, AlertPolicy, AlertState, Alerter, Alerts, Notifier};
+pub use auth::{Auth, ClientCert, Secret};
+pub use check::{Attempt, CheckResult, WebsiteStatus};
+pub use checker::{Checker, CheckerBuilder, Engine, ResultStream};
+pub use client::{HttpClient, Timings};
+pub use config::{Config, ConfigError};
+pub use error::{CheckError, ErrorKind};
+pub use expect::{BodyAssertions, HeaderAssertions, JsonAssertion, RedirectAssertions, StatusSet};
+pub use history::{History, HistoryOptions, Rollup};
+pub use hyper_util::client::proxy::matcher::Matcher as ProxyMatcher;
+pub use metrics::Metrics;
+pub use notify::{CommandNotifier, SlackNotifier, SmtpNotifier, SmtpSecurity, WebhookNotifier};
+pub use redirect::{Hop, RedirectPolicy};
+pub use report::{HistoryReporter, JsonFileReporter, JsonLinesReporter, Reporter, TerminalReporter, TextFileReporter};
+pub use retry::{RetryOn, RetryPolicy, MAX_RETRIES};
+pub use serve::{serve, ServeOptions, Server, StatusBoard};
+pub use shutdown::Shutdown;
+pub use stats::{Statistics, Stats, Summary};
+pub use target::Target;
+pub use tls::Certificate;
+pub use watch::{watch, WatchOptions};
@@ -1,139 +1,245 @@
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+mod cli;
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::{Duration, SystemTime};
-use reqwest::blocking::Client;
-use std::time::Instant;
-use std::sync::mpsc;
-use std::thread;
-
-
-#[derive(Debug)]
-struct WebsiteStatus {
-    url: String,
-    action_status: Result<u16, String>,
-    response_time: Duration,
-    timestamp: SystemTime,
+use clap::Parser;
+use cli::{CheckArgs, Cli, Command, OutputArgs, OutputFormat, ReportArgs, ServeArgs, WatchArgs};
+use signal_hook::consts::SIGHUP;
+use website_status_checker::config::{self, Config};
+use website_status_checker::schema;
+use website_status_checker::{
+    Alerter, Checker, CheckResult, History, HistoryOptions, HistoryReporter, JsonFileReporter, JsonLinesReporter,
+    Reporter, ServeOptions, Shutdown, Statistics, StatusBoard, TerminalReporter, TextFileReporter,
+    WatchOptions,
+};
+
+
+fn reporters(output: &OutputArgs, history: Option<HistoryReporter>) -> io::Result<Vec<Box<dyn Reporter>>> {
+    let windows = output.windows.clone();
+    let file: Box<dyn Reporter> = match output.format {
+        OutputFormat::Json => Box::new(JsonFileReporter::new(&output.output).with_windows(windows.clone())),
+        OutputFormat::Jsonl => Box::new(JsonLinesReporter::create(&output.output)?.with_windows(windows.clone())),
+        OutputFormat::Text => Box::new(TextFileReporter::create(&output.output)?.with_windows(windows.clone())),
+    };
+    let mut reporters: Vec<Box<dyn Reporter>> = vec![Box::new(TerminalReporter::default().with_windows(windows)), file];
+    if let Some(history) = history {
+        reporters.push(Box::new(history));
+    }
+    Ok(reporters)
 }
 
 
-fn read_urls_from_file(path: &str) -> Vec<String> {
-    let file = File::open(path).expect("Failed to open file");
-    let reader = BufReader::new(file);
-    reader.lines()
-        .filter_map(|line| {
-            let line = line.ok()?.trim().to_string();
-            if line.is_empty() || line.starts_with('#') { None } else { Some(line) }
-        })
-        .collect()
+fn open_history(config: &Config) -> io::Result<Option<HistoryReporter>> {
+    let Some(dir) = &config.history else {
+        return Ok(None);
+    };
+    HistoryReporter::open(dir, config.history_options.clone())
+        .map(Some)
+        .map_err(|err| io::Error::new(err.kind(), format!("history {}: {}", dir.display(), err)))
 }
 
 
-fn check_website(client: &Client, url: &str, timeout: Duration, retries: usize) -> WebsiteStatus {
-    let start_time = Instant::now();
-    let mut attempt = 0;
-
-    loop {
-        println!("Attempt {} for {}", attempt + 1, url);
-        let result = client.get(url)
-            .timeout(timeout)
-            .send()
-            .map(|res| res.status().as_u16())
-            .map_err(|err| err.to_string());
-
-        if result.is_ok() || attempt >= retries {
-            return WebsiteStatus {
-                url: url.to_string(),
-                action_status: result,
-                response_time: start_time.elapsed(),
-                timestamp: SystemTime::now(),
-            };
+/// Hands each result to every reporter as soon as it arrives.
+fn check_once(checker: &Checker, config: Config, args: &CheckArgs) -> io::Result<()> {
+    let mut reporters = reporters(&args.output, open_history(&config)?)?;
+    let shutdown = Shutdown::on_signals()?;
+
+    let mut results = Vec::new();
+    for result in checker.stream(config.targets).with_shutdown(shutdown, args.probe.grace) {
+        for reporter in reporters.iter_mut() {
+            reporter.on_result(&result)?;
         }
+        results.push(result);
+    }
 
-        attempt += 1;
-        std::thread::sleep(Duration::from_millis(100));
+    for reporter in reporters.iter_mut() {
+        reporter.finish(&results)?;
     }
+    Ok(())
 }
 
 
-fn run_checks(urls: Vec<String>, workers: usize, timeout: Duration, retries: usize) -> Vec<WebsiteStatus> {
-    let client = Client::new();
-    let (tx, rx) = mpsc::channel();
-    let mut handles = Vec::new();
-
-    for chunk in urls.chunks(workers) {
-        let tx = tx.clone();
-        let client = client.clone();
-        let chunk = chunk.to_vec();
+/// Runs watch mode, reloading the targets on SIGHUP or when the input file changes, and
+/// alerting on state changes if the config has an `[alerts]` table.
+fn watch(
+    checker: &Checker,
+    config: Config,
+    args: &WatchArgs,
+    mut reporters: Vec<Box<dyn Reporter>>,
+    shutdown: Shutdown,
+) -> io::Result<()> {
+    if let Some(alerts) = &config.alerts {
+        reporters.push(Box::new(Alerter::new(alerts.clone())));
+    }
+    let hangup = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(SIGHUP, Arc::clone(&hangup))?;
+
+    let input = &args.probe.input;
+    let mut modified = modified_time(input);
+    let reload = || {
+        let now_modified = modified_time(input);
+        if !hangup.swap(false, Ordering::Relaxed) && now_modified == modified {
+            return None;
+        }
+        modified = now_modified;
 
-        let handle = thread::spawn(move || {
-            for url in chunk {
-                let status = check_website(&client, &url, timeout, retries);
-                println!("Sending result for {}", url);
-                tx.send(status).expect("Failed to send");
+        match config::load(input, &args.probe.overrides(args.interval)) {
+            Ok(config) => {
+                log::info!("reloaded {} targets from {}", config.targets.len(), input.display());
+                Some(config.targets)
             }
-        });
+            Err(err) => {
+                eprintln!("error: keeping the previous targets: {}", err);
+                None
+            }
+        }
+    };
+
+    let options = WatchOptions {
+        jitter: args.jitter,
+        shutdown,
+        grace: args.probe.grace,
+    };
+    website_status_checker::watch(checker, config.targets, &mut reporters, &options, reload)
+}
 
-        handles.push(handle);
-    }
 
-    println!("Waiting for workers to complete...");
+/// Runs watch mode while serving the status page, until interrupted.
+fn serve(checker: &Checker, config: Config, args: &ServeArgs) -> io::Result<()> {
+    let history = open_history(&config)?;
+    let board = StatusBoard::new();
+    let shutdown = Shutdown::on_signals()?;
+    let options = ServeOptions {
+        listen: args.listen,
+        windows: if args.watch.output.windows.is_empty() {
+            vec![Duration::from_secs(24 * 3600)]
+        } else {
+            args.watch.output.windows.clone()
+        },
+        history: history.as_ref().map(|reporter| reporter.history().clone()),
+        shutdown: shutdown.clone(),
+    };
+    let server = website_status_checker::serve(board.clone(), options)
+        .map_err(|err| io::Error::new(err.kind(), format!("listening on {}: {}", args.listen, err)))?;
+    println!("status page at http://{}/", server.local_addr());
+
+    let mut reporters = reporters(&args.watch.output, history)?;
+    reporters.push(Box::new(board));
+    let watched = watch(checker, config, &args.watch, reporters, shutdown.clone());
+    shutdown.trigger();
+    server.join();
+    watched
+}
 
-    // Wait for all threads to finish
-    for handle in handles {
-        handle.join().unwrap();
-    }
 
-    drop(tx); // Closer the sender before collecting results
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
 
-    println!("All workers finished processing!");
+fn print_report(args: &ReportArgs) -> io::Result<()> {
+    let path = &args.input;
+    if path.is_dir() {
+        return print_history(args);
+    }
+    let results = schema::read_results(path)
+        .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path.display(), err)))?;
 
-    // Collect results
-    rx.iter().collect()
+    let mut reporter = TerminalReporter::default().with_windows(args.windows.clone());
+    for result in &results {
+        reporter.on_result(result)?;
+    }
+    reporter.finish(&results)
 }
 
 
-fn print_status(status: &WebsiteStatus) {
-    match &status.action_status {
-        Ok(code) => println!("[{}] {} ({:?})", status.url, code, status.response_time),
-        Err(err) => println!("[{}] ERROR: {} ({:?})", status.url, err, status.response_time),
+/// Prints the latest result per target in a history, then statistics over all of it.
+///
+/// Nothing is compacted, so the history can be read while `watch` writes to it.
+fn print_history(args: &ReportArgs) -> io::Result<()> {
+    let dir = &args.input;
+    let contents = History::open(dir, HistoryOptions::default())
+        .and_then(|history| history.read(None))
+        .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", dir.display(), err)))?;
+
+    let mut statistics = Statistics::new(args.windows.clone());
+    for rollup in &contents.rollups {
+        statistics.record_rollup(rollup);
+    }
+    let mut latest: BTreeMap<(&str, &str), &CheckResult> = BTreeMap::new();
+    for result in &contents.results {
+        statistics.record(result);
+        latest.insert((result.label(), &result.url), result);
+    }
+
+    for result in latest.values() {
+        println!("{}", result);
+    }
+    println!(
+        "\n{} results and {} downsampled periods in {}",
+        contents.results.len(),
+        contents.rollups.len(),
+        dir.display()
+    );
+    for summary in statistics.summaries() {
+        print!("\n{}", summary);
     }
+    Ok(())
 }
 
 
-fn save_results_to_json(statuses: &[WebsiteStatus]) -> String {
-    let mut json_string = String::from("[");
-    for status in statuses {
-        json_string.push_str(&format!(
-            r#"{{"url":"{}", "status":{}, "response_time":"{:?}", "timestamp":"{:?}"}},"#,
-            status.url,
-            match &status.action_status {
-                Ok(code) => code.to_string(),
-                Err(err) => format!(r#""{}""#, err),
-            },
-            status.response_time,
-            status.timestamp
-        ));
+fn build_checker(config: &Config) -> Result<Checker, String> {
+    Checker::builder()
+        .engine(config.engine)
+        .workers(config.workers)
+        .concurrency(config.concurrency)
+        .build()
+        .map_err(|err| format!("failed to create HTTP client: {}", err))
+}
+
+
+fn run(cli: Cli) -> Result<(), String> {
+    match cli.command {
+        Command::Check(args) => {
+            let config = config::load(&args.probe.input, &args.probe.overrides(None))
+                .map_err(|err| err.to_string())?;
+            let checker = build_checker(&config)?;
+            check_once(&checker, config, &args).map_err(|err| err.to_string())
+        }
+        Command::Watch(args) => {
+            let config = config::load(&args.probe.input, &args.probe.overrides(args.interval))
+                .map_err(|err| err.to_string())?;
+            let checker = build_checker(&config)?;
+            let reporters = open_history(&config)
+                .and_then(|history| reporters(&args.output, history))
+                .map_err(|err| err.to_string())?;
+            let shutdown = Shutdown::on_signals().map_err(|err| err.to_string())?;
+            watch(&checker, config, &args, reporters, shutdown).map_err(|err| err.to_string())
+        }
+        Command::Serve(args) => {
+            let config = config::load(&args.watch.probe.input, &args.watch.probe.overrides(args.watch.interval))
+                .map_err(|err| err.to_string())?;
+            let checker = build_checker(&config)?;
+            serve(&checker, config, &args).map_err(|err| err.to_string())
+        }
+        Command::Report(args) => print_report(&args).map_err(|err| err.to_string()),
     }
-    json_string.pop(); // Remove trailing comma
-    json_string.push(']');
-    json_string
 }
 
 
-fn main() {
-    let urls = read_urls_from_file("urls.txt");
-    let workers = 4;
-    let timeout = Duration::from_secs(3);
-    let retries = 0;
+fn main() -> ExitCode {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
 
-    let results = run_checks(urls, workers, timeout, retries);
-    
-    println!("\nWebsite Status Results:\n");
-    
-    for status in &results {
-        print_status(status);  // Ensure this function prints each site's result  
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
     }
-
-    let json_output = save_results_to_json(&results);  
-    std::fs::write("status.json", json_output).expect("Failed to write JSON file");
-}
\ No newline at end of file
+}
@@ -0,0 +1,323 @@
+//! Prometheus metrics derived from check results.
+//!
+//! [`Metrics::render`] writes the Prometheus text exposition format (version 0.0.4),
+//! which Prometheus and OpenMetrics scrapers both accept. Every series has a `target`
+//! label (the target's name, or its URL) and a `url` label:
+//!
+//! - `wsc_up`: 1 if the latest check passed, else 0.
+//! - `wsc_http_status`: the latest response's status code, when there was one.
+//! - `wsc_response_time_seconds`: a histogram of the response times of every check that
+//!   got a response.
+//! - `wsc_phase_seconds`: a histogram of the time checks spent in each `phase` (`dns`,
+//!   `connect`, `tls`, `ttfb`, `download`) they reached.
+//! - `wsc_certificate_expiry_seconds`: seconds until the first certificate in the latest
+//!   chain seen expires, negative once it has.
+//! - `wsc_checks_total`: checks by `result`, either `up` or the error kind.
+//! - `wsc_last_check_timestamp_seconds`: when the latest check finished.
+//!
+//! Series of targets removed by a reload disappear at the next flush.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write as _;
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::check::CheckResult;
+
+
+/// Upper bounds of the buckets of the response time and phase histograms, in seconds.
+pub const RESPONSE_TIME_BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+
+/// Counters and histograms accumulated over every result, by target.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    targets: BTreeMap<(String, String), Series>,
+}
+
+
+#[derive(Debug, Clone, Default)]
+struct Series {
+    /// Checks by `up` or error kind.
+    checks: BTreeMap<String, u64>,
+    response_time: Histogram,
+    /// Time spent in each phase, by phase name.
+    phases: BTreeMap<&'static str, Histogram>,
+}
+
+
+#[derive(Debug, Clone, Default)]
+struct Histogram {
+    /// Cumulative counts per bucket in [`RESPONSE_TIME_BUCKETS`].
+    buckets: [u64; RESPONSE_TIME_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+
+impl Histogram {
+    fn observe(&mut self, time: Duration) {
+        let seconds = time.as_secs_f64();
+        for (bucket, bound) in self.buckets.iter_mut().zip(RESPONSE_TIME_BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+
+    /// Writes the `_bucket`, `_sum` and `_count` samples of `name` with `labels`.
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let bucket = format!("{}_bucket", name);
+        for (count, bound) in self.buckets.iter().zip(RESPONSE_TIME_BUCKETS) {
+            sample(out, &bucket, &format!("{},le=\"{}\"", labels, bound), count);
+        }
+        sample(out, &bucket, &format!("{},le=\"+Inf\"", labels), self.count);
+        sample(out, &format!("{}_sum", name), labels, self.sum);
+        sample(out, &format!("{}_count", name), labels, self.count);
+    }
+}
+
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics::default()
+    }
+
+    pub fn record(&mut self, result: &CheckResult) {
+        let series = self.targets.entry(labels_of(result)).or_default();
+        let outcome = result.error_kind().map_or("up".to_string(), |kind| kind.to_string());
+        *series.checks.entry(outcome).or_insert(0) += 1;
+
+        if result.status.is_some() {
+            series.response_time.observe(result.response_time);
+        }
+        for (phase, time) in phases(result) {
+            if let Some(time) = time {
+                series.phases.entry(phase).or_default().observe(time);
+            }
+        }
+    }
+
+    /// Drops the counters of targets that aren't in `latest` any more.
+    pub fn retain(&mut self, latest: &[CheckResult]) {
+        let current: HashSet<(&str, &str)> =
+            latest.iter().map(|result| (result.label(), result.url.as_str())).collect();
+        self.targets.retain(|(target, url), _| current.contains(&(target.as_str(), url.as_str())));
+    }
+
+    /// The metrics in the text exposition format, with gauges taken from `latest`.
+    pub fn render(&self, latest: &[CheckResult]) -> String {
+        let mut out = String::new();
+
+        family(&mut out, "wsc_up", "gauge", "Whether the target's latest check passed.");
+        for result in latest {
+            sample(&mut out, "wsc_up", &labels(result.label(), &result.url), u8::from(result.is_up()));
+        }
+
+        family(&mut out, "wsc_http_status", "gauge", "Status code of the target's latest response.");
+        for result in latest {
+            if let Some(status) = result.status {
+                sample(&mut out, "wsc_http_status", &labels(result.label(), &result.url), status);
+            }
+        }
+
+        family(&mut out, "wsc_response_time_seconds", "histogram", "Time until the response headers arrived.");
+        for ((target, url), series) in &self.targets {
+            series.response_time.render(&mut out, "wsc_response_time_seconds", &labels(target, url));
+        }
+
+        family(&mut out, "wsc_phase_seconds", "histogram", "Time spent in each phase of a check.");
+        for ((target, url), series) in &self.targets {
+            for (phase, histogram) in &series.phases {
+                let phase_labels = format!("{},phase=\"{}\"", labels(target, url), phase);
+                histogram.render(&mut out, "wsc_phase_seconds", &phase_labels);
+            }
+        }
+
+        family(&mut out, "wsc_certificate_expiry_seconds", "gauge", "Seconds until the first certificate in the target's chain expires.");
+        for result in latest {
+            if let Some(certificate) = &result.certificate {
+                let left = (certificate.expires_first().not_after - Utc::now()).num_seconds();
+                sample(&mut out, "wsc_certificate_expiry_seconds", &labels(result.label(), &result.url), left);
+            }
+        }
+
+        family(&mut out, "wsc_checks_total", "counter", "Checks by outcome: up, or what broke.");
+        for ((target, url), series) in &self.targets {
+            for (outcome, count) in &series.checks {
+                let outcome_labels = format!("{},result=\"{}\"", labels(target, url), escape(outcome));
+                sample(&mut out, "wsc_checks_total", &outcome_labels, count);
+            }
+        }
+
+        family(&mut out, "wsc_last_check_timestamp_seconds", "gauge", "When the target's latest check finished.");
+        for result in latest {
+            let timestamp = result.timestamp.timestamp_millis() as f64 / 1000.0;
+            sample(&mut out, "wsc_last_check_timestamp_seconds", &labels(result.label(), &result.url), timestamp);
+        }
+
+        out
+    }
+}
+
+
+fn labels_of(result: &CheckResult) -> (String, String) {
+    (result.label().to_string(), result.url.clone())
+}
+
+
+/// The phases of a check and the time spent in each, `None` for those it didn't reach.
+fn phases(result: &CheckResult) -> [(&'static str, Option<Duration>); 5] {
+    let timings = &result.timings;
+    [
+        ("dns", timings.dns),
+        ("connect", timings.connect),
+        ("tls", timings.tls),
+        ("ttfb", timings.ttfb),
+        ("download", timings.download),
+    ]
+}
+
+
+/// The `target` and `url` labels every series has.
+fn labels(target: &str, url: &str) -> String {
+    format!("target=\"{}\",url=\"{}\"", escape(target), escape(url))
+}
+
+
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+
+fn sample(out: &mut String, name: &str, labels: &str, value: impl std::fmt::Display) {
+    let _ = writeln!(out, "{}{{{}}} {}", name, labels, value);
+}
+
+
+/// Escapes a label value: backslashes, double quotes and newlines.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Timings;
+    use crate::error::{CheckError, ErrorKind};
+    use crate::target::Target;
+
+    /// A check of `url` that got a 200 after `millis` milliseconds.
+    fn up(url: &str, millis: u64) -> CheckResult {
+        let mut result = CheckResult::aborted(&Target::new(url), Duration::from_millis(millis));
+        result.status = Some(200);
+        result.error = None;
+        result
+    }
+
+    fn down(url: &str, kind: ErrorKind) -> CheckResult {
+        let mut result = CheckResult::aborted(&Target::new(url), Duration::ZERO);
+        result.error = Some(CheckError::new(kind, "failed"));
+        result
+    }
+
+    /// The value of the one sample whose name and labels are `series`.
+    fn value<'a>(out: &'a str, series: &str) -> &'a str {
+        let mut values = out.lines().filter_map(|line| line.strip_prefix(series)?.strip_prefix(' '));
+        let value = values.next().unwrap_or_else(|| panic!("no {} in\n{}", series, out));
+        assert_eq!(values.next(), None, "{} appears twice", series);
+        value
+    }
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let url = "https://example.com/";
+        let mut metrics = Metrics::new();
+        for millis in [3, 30, 300, 20_000] {
+            metrics.record(&up(url, millis));
+        }
+        metrics.record(&down(url, ErrorKind::Timeout));
+        let out = metrics.render(&[]);
+
+        let series = labels(url, url);
+        let bucket = |le: &str| value(&out, &format!("wsc_response_time_seconds_bucket{{{},le=\"{}\"}}", series, le));
+        assert_eq!(bucket("0.005"), "1");
+        assert_eq!(bucket("0.01"), "1");
+        assert_eq!(bucket("0.05"), "2");
+        assert_eq!(bucket("0.5"), "3");
+        assert_eq!(bucket("10"), "3");
+        // Checks without a response aren't timed
+        assert_eq!(bucket("+Inf"), "4");
+
+        assert_eq!(value(&out, &format!("wsc_response_time_seconds_count{{{}}}", series)), "4");
+        assert_eq!(value(&out, &format!("wsc_response_time_seconds_sum{{{}}}", series)), "20.333");
+        assert_eq!(value(&out, &format!("wsc_checks_total{{{},result=\"up\"}}", series)), "4");
+        assert_eq!(value(&out, &format!("wsc_checks_total{{{},result=\"timeout\"}}", series)), "1");
+    }
+
+    #[test]
+    fn phases_are_timed_only_when_reached() {
+        let url = "http://example.com/";
+        let mut result = up(url, 40);
+        result.timings = Timings {
+            dns: Some(Duration::from_millis(2)),
+            ttfb: Some(Duration::from_millis(30)),
+            ..Timings::default()
+        };
+        let mut metrics = Metrics::new();
+        metrics.record(&result);
+        let out = metrics.render(&[]);
+
+        let count = |phase: &str| format!("wsc_phase_seconds_count{{{},phase=\"{}\"}}", labels(url, url), phase);
+        assert_eq!(value(&out, &count("dns")), "1");
+        assert_eq!(value(&out, &count("ttfb")), "1");
+        assert!(!out.contains("phase=\"tls\"") && !out.contains("phase=\"connect\""), "{}", out);
+    }
+
+    #[test]
+    fn gauges_come_from_the_latest_results() {
+        let url = "https://example.com/";
+        let mut latest = up(url, 10);
+        latest.status = Some(503);
+        latest.error = Some(CheckError::new(ErrorKind::UnexpectedStatus, "status 503"));
+        let out = Metrics::new().render(&[latest, down("https://example.org/", ErrorKind::Dns)]);
+
+        let series = labels(url, url);
+        assert_eq!(value(&out, &format!("wsc_up{{{}}}", series)), "0");
+        assert_eq!(value(&out, &format!("wsc_http_status{{{}}}", series)), "503");
+        assert!(!out.contains("wsc_http_status{target=\"https://example.org/\""), "{}", out);
+        assert!(out.contains("# TYPE wsc_response_time_seconds histogram\n"), "{}", out);
+    }
+
+    #[test]
+    fn label_values_are_escaped() {
+        let mut result = up("https://example.com/?q=\"x\"", 10);
+        result.name = Some("C:\\sites\\shop\nEU".to_string());
+        let mut metrics = Metrics::new();
+        metrics.record(&result);
+        let out = metrics.render(&[result]);
+
+        let labels = r#"{target="C:\\sites\\shop\nEU",url="https://example.com/?q=\"x\""}"#;
+        assert_eq!(value(&out, &format!("wsc_up{}", labels)), "1");
+        assert_eq!(value(&out, &format!("wsc_response_time_seconds_count{}", labels)), "1");
+    }
+
+    #[test]
+    fn retain_drops_removed_targets() {
+        let kept = up("https://example.com/", 10);
+        let removed = up("https://example.org/", 10);
+        let mut metrics = Metrics::new();
+        metrics.record(&kept);
+        metrics.record(&removed);
+
+        metrics.retain(std::slice::from_ref(&kept));
+        let out = metrics.render(std::slice::from_ref(&kept));
+        assert!(out.contains("url=\"https://example.com/\""), "{}", out);
+        assert!(!out.contains("example.org"), "{}", out);
+    }
+}
@@ -0,0 +1,415 @@
+//! The built-in [`Notifier`]s: generic and Slack-compatible webhooks, email over SMTP
+//! and local commands.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD;
+use http::Method;
+use openssl::ssl::SslStream;
+use serde::Deserialize;
+
+use crate::alert::{AlertEvent, AlertState, Notifier};
+use crate::auth::Secret;
+use crate::checker::Checker;
+use crate::expect::StatusSet;
+use crate::redirect::RedirectPolicy;
+use crate::target::Target;
+use crate::tls;
+
+
+/// How long a notifier may take to deliver one alert, by default.
+pub const DEFAULT_NOTIFY_TIMEOUT: Duration = Duration::from_secs(30);
+
+
+/// POSTs each event as JSON (the alert object in [`crate::schema`]) to a URL.
+#[derive(Debug, Clone)]
+pub struct WebhookNotifier {
+    url: Secret,
+    headers: BTreeMap<String, String>,
+}
+
+
+impl WebhookNotifier {
+    /// The URL is kept out of logs, since webhook URLs often embed a token.
+    pub fn new(url: Secret) -> Self {
+        WebhookNotifier { url, headers: BTreeMap::new() }
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+}
+
+
+impl Notifier for WebhookNotifier {
+    fn notify(&self, event: &AlertEvent) -> Result<(), String> {
+        let body = serde_json::to_vec(event).map_err(|err| err.to_string())?;
+        post_json(&self.url, &self.headers, body)
+    }
+
+    fn describe(&self) -> String {
+        "webhook".to_string()
+    }
+}
+
+
+/// Posts each event's message to a Slack incoming webhook, or anything that accepts
+/// the same `{"text": ...}` payload (Mattermost, Rocket.Chat, Discord's `/slack` URLs).
+#[derive(Debug, Clone)]
+pub struct SlackNotifier {
+    url: Secret,
+}
+
+
+impl SlackNotifier {
+    pub fn new(url: Secret) -> Self {
+        SlackNotifier { url }
+    }
+}
+
+
+impl Notifier for SlackNotifier {
+    fn notify(&self, event: &AlertEvent) -> Result<(), String> {
+        let icon = match event.state {
+            AlertState::Down => ":red_circle:",
+            AlertState::Up => ":large_green_circle:",
+        };
+        let text = format!("{} {} ({})", icon, event.message(), event.result.url);
+        let body = serde_json::to_vec(&serde_json::json!({ "text": text })).map_err(|err| err.to_string())?;
+        post_json(&self.url, &BTreeMap::new(), body)
+    }
+
+    fn describe(&self) -> String {
+        "slack".to_string()
+    }
+}
+
+
+/// Sends the request through the same client as the checks, with two retries.
+///
+/// Redirects aren't followed and the error is reduced to its kind and status, so that
+/// neither the request nor the error message repeats the URL and its token.
+fn post_json(url: &Secret, headers: &BTreeMap<String, String>, body: Vec<u8>) -> Result<(), String> {
+    let mut target = Target::new(url.expose())
+        .with_method(Method::POST)
+        .with_header("Content-Type", "application/json")
+        .with_body(body)
+        .with_timeout(Duration::from_secs(10))
+        .with_retries(2)
+        .with_redirects(RedirectPolicy::None)
+        .with_expected_status(StatusSet::new([200..=299]));
+    for (name, value) in headers {
+        target = target.with_header(name, value);
+    }
+
+    let result = notify_checker()?.check(&target);
+    match (result.error, result.status) {
+        (None, _) => Ok(()),
+        (Some(_), Some(status)) => Err(format!("the server answered {}", status)),
+        (Some(err), None) => Err(format!("the request failed: {}", err.kind())),
+    }
+}
+
+
+/// The checker webhooks are sent with, built on first use and kept for the rest of the run.
+fn notify_checker() -> Result<&'static Checker, String> {
+    static CHECKER: OnceLock<Checker> = OnceLock::new();
+    if let Some(checker) = CHECKER.get() {
+        return Ok(checker);
+    }
+    let checker = Checker::builder().workers(1).build().map_err(|err| err.to_string())?;
+    Ok(CHECKER.get_or_init(|| checker))
+}
+
+
+/// How the connection to an SMTP server is secured.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// Plain text throughout, for a relay on the local network.
+    None,
+    /// Upgrade with `STARTTLS` before authenticating; the usual setup on port 587.
+    #[default]
+    StartTls,
+    /// TLS from the start, usually on port 465.
+    Tls,
+}
+
+
+impl SmtpSecurity {
+    pub fn default_port(self) -> u16 {
+        match self {
+            SmtpSecurity::None => 25,
+            SmtpSecurity::StartTls => 587,
+            SmtpSecurity::Tls => 465,
+        }
+    }
+}
+
+
+/// Emails each event through an SMTP server.
+#[derive(Debug, Clone)]
+pub struct SmtpNotifier {
+    pub host: String,
+    pub port: u16,
+    pub security: SmtpSecurity,
+    /// Credentials for `AUTH PLAIN`, if the server needs them.
+    pub credentials: Option<(String, Secret)>,
+    pub from: String,
+    pub to: Vec<String>,
+    pub timeout: Duration,
+}
+
+
+impl Notifier for SmtpNotifier {
+    fn notify(&self, event: &AlertEvent) -> Result<(), String> {
+        self.send(&self.compose(event)).map_err(|err| format!("{}:{}: {}", self.host, self.port, err))
+    }
+
+    fn describe(&self) -> String {
+        format!("smtp {}:{}", self.host, self.port)
+    }
+}
+
+
+impl SmtpNotifier {
+    /// The message, headers and all, with CRLF line endings but not yet dot-stuffed.
+    fn compose(&self, event: &AlertEvent) -> String {
+        let result = &event.result;
+        let subject = format!("[{}] {}", event.state.to_string().to_uppercase(), event.label());
+
+        let mut body = format!("{}\r\n\r\nURL: {}\r\n", event.message(), result.url);
+        if let Some(status) = result.status {
+            let _ = write!(body, "Status: {}\r\n", status);
+        }
+        if let Some(err) = &result.error {
+            let _ = write!(body, "Error: {}: {}\r\n", err.kind(), err);
+        }
+        let _ = write!(body, "Down since: {}\r\n", event.down_since.format("%Y-%m-%d %H:%M:%S UTC"));
+        let _ = write!(body, "Checked: {}\r\n", result.timestamp.format("%Y-%m-%d %H:%M:%S UTC"));
+
+        format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nMIME-Version: 1.0\r\n\
+             Content-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\n{}",
+            self.from,
+            self.to.join(", "),
+            encode_header(&subject),
+            result.timestamp.to_rfc2822(),
+            body
+        )
+    }
+
+    fn send(&self, message: &str) -> io::Result<()> {
+        let mut session = SmtpSession::connect(&self.host, self.port, self.security, self.timeout)?;
+        session.expect(&[220])?;
+        let mut extensions = session.command("EHLO localhost", &[250])?;
+
+        if self.security == SmtpSecurity::StartTls {
+            if !extensions.iter().any(|line| line.eq_ignore_ascii_case("STARTTLS")) {
+                return Err(smtp_error("the server does not offer STARTTLS"));
+            }
+            session.command("STARTTLS", &[220])?;
+            session = session.upgrade(&self.host)?;
+            extensions = session.command("EHLO localhost", &[250])?;
+        }
+        if let Some((username, password)) = &self.credentials {
+            if !extensions.iter().any(|line| line.to_ascii_uppercase().starts_with("AUTH")) {
+                return Err(smtp_error("the server does not offer AUTH"));
+            }
+            let token = STANDARD.encode(format!("\0{}\0{}", username, password.expose()));
+            session.command(&format!("AUTH PLAIN {}", token), &[235])?;
+        }
+
+        session.command(&format!("MAIL FROM:<{}>", self.from), &[250])?;
+        for to in &self.to {
+            session.command(&format!("RCPT TO:<{}>", to), &[250, 251])?;
+        }
+        session.command("DATA", &[354])?;
+        let mut data = String::new();
+        for line in message.split("\r\n") {
+            // Dot-stuffing, so a line with a lone `.` can't end the message early
+            if line.starts_with('.') {
+                data.push('.');
+            }
+            data.push_str(line);
+            data.push_str("\r\n");
+        }
+        data.push_str(".\r\n");
+        session.write(&data)?;
+        session.expect(&[250])?;
+        let _ = session.command("QUIT", &[221]);
+        Ok(())
+    }
+}
+
+
+enum SmtpStream {
+    Plain(TcpStream),
+    Tls(Box<SslStream<TcpStream>>),
+}
+
+
+struct SmtpSession {
+    stream: SmtpStream,
+}
+
+
+impl SmtpSession {
+    fn connect(host: &str, port: u16, security: SmtpSecurity, timeout: Duration) -> io::Result<Self> {
+        let mut last_error = io::Error::new(io::ErrorKind::NotFound, "no addresses found");
+        for addr in (host, port).to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, timeout) {
+                Ok(stream) => {
+                    stream.set_read_timeout(Some(timeout))?;
+                    stream.set_write_timeout(Some(timeout))?;
+                    let session = SmtpSession { stream: SmtpStream::Plain(stream) };
+                    return match security {
+                        SmtpSecurity::Tls => session.upgrade(host),
+                        _ => Ok(session),
+                    };
+                }
+                Err(err) => last_error = err,
+            }
+        }
+        Err(last_error)
+    }
+
+    fn upgrade(self, host: &str) -> io::Result<Self> {
+        let SmtpStream::Plain(stream) = self.stream else {
+            return Err(smtp_error("already using TLS"));
+        };
+        // The same trusted roots as the checks
+        let connector = tls::connector().map_err(io::Error::other)?.build();
+        let stream = connector.connect(host, stream).map_err(|err| io::Error::other(err.to_string()))?;
+        Ok(SmtpSession { stream: SmtpStream::Tls(Box::new(stream)) })
+    }
+
+    fn write(&mut self, data: &str) -> io::Result<()> {
+        match &mut self.stream {
+            SmtpStream::Plain(stream) => stream.write_all(data.as_bytes()),
+            SmtpStream::Tls(stream) => stream.write_all(data.as_bytes()),
+        }
+    }
+
+    fn command(&mut self, line: &str, expected: &[u16]) -> io::Result<Vec<String>> {
+        self.write(&format!("{}\r\n", line))?;
+        self.expect(expected)
+    }
+
+    /// Reads a reply, which may span several `250-...` lines, and returns its text
+    /// without the codes if the code is one of `expected`.
+    fn expect(&mut self, expected: &[u16]) -> io::Result<Vec<String>> {
+        let mut lines = Vec::new();
+        loop {
+            let line = self.read_line()?;
+            let code: u16 = line.get(..3).and_then(|code| code.parse().ok())
+                .ok_or_else(|| smtp_error(format!("unexpected reply `{}`", line)))?;
+            let text = line.get(4..).unwrap_or("").to_string();
+            let last = line.as_bytes().get(3) != Some(&b'-');
+            if !expected.contains(&code) {
+                return Err(smtp_error(format!("server replied `{}`", line)));
+            }
+            lines.push(text);
+            if last {
+                return Ok(lines);
+            }
+        }
+    }
+
+    fn read_line(&mut self) -> io::Result<String> {
+        let mut line = Vec::new();
+        let mut byte = [0u8; 1];
+        loop {
+            let read = match &mut self.stream {
+                SmtpStream::Plain(stream) => stream.read(&mut byte)?,
+                SmtpStream::Tls(stream) => stream.read(&mut byte)?,
+            };
+            if read == 0 {
+                return Err(smtp_error("the server closed the connection"));
+            }
+            if byte[0] == b'\n' {
+                break;
+            }
+            line.push(byte[0]);
+        }
+        Ok(String::from_utf8_lossy(&line).trim_end_matches('\r').to_string())
+    }
+}
+
+
+fn smtp_error(message: impl Into<String>) -> io::Error {
+    io::Error::other(message.into())
+}
+
+
+/// Encodes a header value as an RFC 2047 word if it isn't plain ASCII.
+fn encode_header(value: &str) -> String {
+    if value.is_ascii() {
+        value.to_string()
+    } else {
+        format!("=?utf-8?B?{}?=", STANDARD.encode(value))
+    }
+}
+
+
+/// Runs a program for each event, without a shell.
+///
+/// The event is written to its standard input as JSON (the alert object in
+/// [`crate::schema`]) and summarised in `WSC_ALERT_STATE` (`down` or `up`),
+/// `WSC_ALERT_TARGET`, `WSC_ALERT_URL` and `WSC_ALERT_MESSAGE`. A non-zero exit
+/// status counts as a failure, and the program is killed if it runs past `timeout`.
+#[derive(Debug, Clone)]
+pub struct CommandNotifier {
+    pub program: PathBuf,
+    pub args: Vec<String>,
+    pub timeout: Duration,
+}
+
+
+impl Notifier for CommandNotifier {
+    fn notify(&self, event: &AlertEvent) -> Result<(), String> {
+        let input = serde_json::to_vec(event).map_err(|err| err.to_string())?;
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .env("WSC_ALERT_STATE", event.state.to_string())
+            .env("WSC_ALERT_TARGET", event.label())
+            .env("WSC_ALERT_URL", &event.result.url)
+            .env("WSC_ALERT_MESSAGE", event.message())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|err| format!("{}: {}", self.program.display(), err))?;
+
+        // A program that doesn't read its input just gets a broken pipe
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(&input);
+        }
+
+        let started = Instant::now();
+        loop {
+            match child.try_wait().map_err(|err| err.to_string())? {
+                Some(status) if status.success() => return Ok(()),
+                Some(status) => return Err(format!("{} exited with {}", self.program.display(), status)),
+                None if started.elapsed() >= self.timeout => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(format!("{} took longer than {:?} and was killed", self.program.display(), self.timeout));
+                }
+                None => std::thread::sleep(Duration::from_millis(20)),
+            }
+        }
+    }
+
+    fn describe(&self) -> String {
+        format!("command {}", self.program.display())
+    }
+}
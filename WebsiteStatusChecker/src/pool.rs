@@ -0,0 +1,236 @@
+use std::iter;
+use std::sync::mpsc::Sender;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crossbeam_deque::{Injector, Stealer, Worker};
+use tokio::runtime::Runtime;
+
+use crate::check::{check_website, CheckResult};
+use crate::client::HttpClient;
+use crate::target::Target;
+
+
+/// How long an idle worker sleeps before looking for work to steal again.
+const IDLE_POLL: Duration = Duration::from_millis(20);
+
+
+/// A fixed set of worker threads pulling targets from a shared queue.
+///
+/// Each thread blocks on one check at a time, so the number of requests in flight never
+/// exceeds the number of threads; a small shared runtime only drives the sockets. Workers
+/// keep that runtime alive until they exit, so an aborted worker can still finish its
+/// request. New jobs go into a global injector queue. Each worker moves a small batch into
+/// its own local queue and, once both are empty, steals from the other workers, so a slow
+/// target only ever holds up the one worker that is checking it.
+pub(crate) struct WorkerPool {
+    shared: Arc<Shared>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+
+struct Shared {
+    injector: Injector<Target>,
+    stealers: Vec<Stealer<Target>>,
+    closed: Mutex<bool>,
+    cancelled: AtomicBool,
+    wakeup: Condvar,
+}
+
+
+impl WorkerPool {
+    /// Starts `workers` threads that send each finished check to `results`.
+    pub(crate) fn new(
+        workers: usize,
+        runtime: &Arc<Runtime>,
+        client: &HttpClient,
+        results: Sender<CheckResult>,
+    ) -> Self {
+        let locals: Vec<Worker<Target>> = (0..workers.max(1)).map(|_| Worker::new_fifo()).collect();
+        let shared = Arc::new(Shared {
+            injector: Injector::new(),
+            stealers: locals.iter().map(Worker::stealer).collect(),
+            closed: Mutex::new(false),
+            cancelled: AtomicBool::new(false),
+            wakeup: Condvar::new(),
+        });
+
+        let handles = locals
+            .into_iter()
+            .enumerate()
+            .map(|(index, local)| {
+                let shared = Arc::clone(&shared);
+                let runtime = Arc::clone(runtime);
+                let client = client.clone();
+                let results = results.clone();
+                thread::Builder::new()
+                    .name(format!("wsc-worker-{}", index))
+                    .spawn(move || work(local, &shared, &runtime, &client, &results))
+                    .expect("Failed to spawn worker thread")
+            })
+            .collect();
+
+        WorkerPool { shared, handles }
+    }
+
+    /// Queues a target for the next idle worker.
+    pub(crate) fn submit(&self, target: Target) {
+        self.shared.injector.push(target);
+        let _closed = self.shared.closed.lock().unwrap();
+        self.shared.wakeup.notify_one();
+    }
+
+    /// Stops accepting work: each worker exits once the queues are empty.
+    pub(crate) fn close(&self) {
+        *self.shared.closed.lock().unwrap() = true;
+        self.shared.wakeup.notify_all();
+    }
+
+    /// Drops everything still queued; workers exit as soon as their current check is done.
+    pub(crate) fn cancel(&self) {
+        self.shared.cancelled.store(true, Ordering::SeqCst);
+        self.close();
+    }
+
+    /// Cancels the queue and stops waiting for the workers. A worker stuck in a request
+    /// exits when it returns, and its result is discarded.
+    pub(crate) fn abort(self) {
+        self.cancel();
+    }
+
+    /// Lets the workers finish everything already queued, then waits for them to exit.
+    pub(crate) fn join(self) {
+        self.close();
+        for handle in self.handles {
+            if handle.join().is_err() {
+                log::error!("a worker thread panicked");
+            }
+        }
+    }
+}
+
+
+fn work(
+    local: Worker<Target>,
+    shared: &Shared,
+    runtime: &Runtime,
+    client: &HttpClient,
+    results: &Sender<CheckResult>,
+) {
+    while !shared.cancelled.load(Ordering::SeqCst) {
+        if let Some(target) = find_job(&local, shared) {
+            let status = runtime.block_on(check_website(client, &target));
+            log::debug!("sending result for {}", target.url);
+            if results.send(status).is_err() {
+                return; // Nobody is listening for results any more
+            }
+            continue;
+        }
+
+        let closed = shared.closed.lock().unwrap();
+        if !shared.injector.is_empty() {
+            continue;
+        }
+        if *closed && shared.stealers.iter().all(Stealer::is_empty) {
+            return;
+        }
+        let _ = shared.wakeup.wait_timeout(closed, IDLE_POLL).unwrap();
+    }
+}
+
+
+fn find_job(local: &Worker<Target>, shared: &Shared) -> Option<Target> {
+    local.pop().or_else(|| {
+        iter::repeat_with(|| {
+            shared.injector
+                .steal_batch_and_pop(local)
+                .or_else(|| shared.stealers.iter().map(Stealer::steal).collect())
+        })
+        .find(|steal| !steal.is_retry())
+        .and_then(|steal| steal.success())
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::sync::mpsc::{self, Receiver};
+    use std::time::Instant;
+
+    use hyper_util::client::proxy::matcher::Matcher;
+
+    use crate::error::ErrorKind;
+
+    fn pool(workers: usize) -> (WorkerPool, Receiver<CheckResult>) {
+        let runtime = tokio::runtime::Builder::new_multi_thread().worker_threads(1).enable_all().build().unwrap();
+        let runtime = Arc::new(runtime);
+        let client = HttpClient::new(None).unwrap().with_proxies(Matcher::builder().build());
+        let (tx, rx) = mpsc::channel();
+        (WorkerPool::new(workers, &runtime, &client, tx), rx)
+    }
+
+    /// A target on a port nothing listens on, refused straight away.
+    fn refused(index: usize) -> Target {
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        Target::new(format!("http://127.0.0.1:{}/{}", port, index))
+    }
+
+    /// A target on `listener`, which never answers, so the check takes its whole `timeout`.
+    fn unanswered(listener: &TcpListener, timeout: Duration) -> Target {
+        Target::new(format!("http://{}/", listener.local_addr().unwrap())).with_timeout(timeout)
+    }
+
+    #[test]
+    fn join_finishes_everything_queued() {
+        let (pool, rx) = pool(2);
+        for index in 0..5 {
+            pool.submit(refused(index));
+        }
+        pool.join();
+
+        let results: Vec<CheckResult> = rx.try_iter().collect();
+        assert_eq!(results.len(), 5);
+        assert!(results.iter().all(|result| result.error_kind() == Some(ErrorKind::ConnectionRefused)));
+    }
+
+    #[test]
+    fn a_slow_target_holds_up_only_its_own_worker() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let (pool, rx) = pool(2);
+        pool.submit(unanswered(&listener, Duration::from_secs(30)));
+        for index in 0..4 {
+            pool.submit(refused(index));
+        }
+
+        for _ in 0..4 {
+            let result = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+            assert_eq!(result.error_kind(), Some(ErrorKind::ConnectionRefused), "{}", result);
+        }
+        pool.abort();
+    }
+
+    #[test]
+    fn cancel_drops_queued_targets() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let (pool, rx) = pool(1);
+        pool.submit(unanswered(&listener, Duration::from_millis(300)));
+        for index in 0..3 {
+            pool.submit(refused(index));
+        }
+        thread::sleep(Duration::from_millis(100));
+
+        let started = Instant::now();
+        pool.cancel();
+        pool.join();
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        // Only the check that was already running reports back
+        let results: Vec<CheckResult> = rx.try_iter().collect();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].error_kind(), Some(ErrorKind::Timeout));
+    }
+}
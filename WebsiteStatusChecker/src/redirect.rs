@@ -0,0 +1,92 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+
+/// Redirects followed by default before a check fails with
+/// [`ErrorKind::Redirect`](crate::ErrorKind::Redirect).
+pub const DEFAULT_MAX_REDIRECTS: usize = 10;
+
+
+/// Whether a check follows redirects, and how far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirectPolicy {
+    /// Treat a redirect as the final response and check its status.
+    None,
+    /// Follow at most this many redirects; one more fails the check.
+    Follow(usize),
+}
+
+
+impl Default for RedirectPolicy {
+    fn default() -> Self {
+        RedirectPolicy::Follow(DEFAULT_MAX_REDIRECTS)
+    }
+}
+
+
+impl FromStr for RedirectPolicy {
+    type Err = String;
+
+    /// Parses `none`, `follow`, or the maximum number of redirects (`0` meaning `none`).
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim() {
+            "none" | "0" => Ok(RedirectPolicy::None),
+            "follow" => Ok(RedirectPolicy::default()),
+            other => other
+                .parse()
+                .map(RedirectPolicy::Follow)
+                .map_err(|_| format!("`{}` is not a redirect policy (use none, follow or a number of hops)", other)),
+        }
+    }
+}
+
+
+impl fmt::Display for RedirectPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RedirectPolicy::None => f.write_str("none"),
+            RedirectPolicy::Follow(max) => write!(f, "follow up to {}", max),
+        }
+    }
+}
+
+
+/// One redirect that was followed: the URL requested, the status it answered with and
+/// where it pointed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hop {
+    pub url: String,
+    pub status: u16,
+    pub location: String,
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn policies_parse_and_print() {
+        assert_eq!("none".parse(), Ok(RedirectPolicy::None));
+        assert_eq!("0".parse(), Ok(RedirectPolicy::None));
+        assert_eq!(" follow ".parse(), Ok(RedirectPolicy::Follow(DEFAULT_MAX_REDIRECTS)));
+        assert_eq!("3".parse(), Ok(RedirectPolicy::Follow(3)));
+        assert_eq!(RedirectPolicy::default(), RedirectPolicy::Follow(10));
+
+        assert_eq!(RedirectPolicy::None.to_string(), "none");
+        assert_eq!(RedirectPolicy::Follow(3).to_string(), "follow up to 3");
+    }
+
+    #[test]
+    fn policies_reject_anything_else() {
+        for value in ["", "yes", "Follow", "-1", "2.5", "1e3"] {
+            assert!(value.parse::<RedirectPolicy>().is_err(), "{}", value);
+        }
+        assert_eq!(
+            "always".parse::<RedirectPolicy>().unwrap_err(),
+            "`always` is not a redirect policy (use none, follow or a number of hops)"
+        );
+    }
+}
@@ -0,0 +1,270 @@
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+
+use crate::check::CheckResult;
+use crate::history::{History, HistoryOptions};
+use crate::schema::{Document, Line, StatisticsLine};
+use crate::stats::Statistics;
+
+
+/// Receives results while a run is in progress.
+pub trait Reporter {
+    /// Called as soon as each check finishes.
+    fn on_result(&mut self, result: &CheckResult) -> io::Result<()>;
+
+    /// Called once the run is over with every result, in completion order.
+    fn finish(&mut self, _results: &[CheckResult]) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Called periodically in watch mode with the latest result for each target.
+    fn flush(&mut self, _latest: &[CheckResult]) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+
+/// Prints each result to standard output as it arrives, then a summary with the failures
+/// counted by [`ErrorKind`](crate::ErrorKind) and a table of [`Statistics`].
+#[derive(Debug, Default, Clone)]
+pub struct TerminalReporter {
+    statistics: Statistics,
+}
+
+
+impl TerminalReporter {
+    /// Adds statistics for the trailing `windows` to the table for the whole run.
+    pub fn with_windows(mut self, windows: Vec<Duration>) -> Self {
+        self.statistics = Statistics::new(windows);
+        self
+    }
+}
+
+
+impl Reporter for TerminalReporter {
+    fn on_result(&mut self, result: &CheckResult) -> io::Result<()> {
+        self.statistics.record(result);
+        let mut stdout = io::stdout().lock();
+        writeln!(stdout, "{}", result)?;
+        stdout.flush()
+    }
+
+    fn finish(&mut self, results: &[CheckResult]) -> io::Result<()> {
+        let up = results.iter().filter(|result| result.is_up()).count();
+        let average = if results.is_empty() {
+            Duration::ZERO
+        } else {
+            results.iter().map(|result| result.response_time).sum::<Duration>() / results.len() as u32
+        };
+
+        println!(
+            "\n{} checked: {} up, {} down, average response time {:?}",
+            results.len(),
+            up,
+            results.len() - up,
+            average
+        );
+        let warned = results.iter().filter(|result| result.has_warnings()).count();
+        if warned > 0 {
+            println!("{} up with warnings", warned);
+        }
+
+        let mut by_kind = BTreeMap::new();
+        for kind in results.iter().filter_map(CheckResult::error_kind) {
+            *by_kind.entry(kind).or_insert(0) += 1;
+        }
+        if !by_kind.is_empty() {
+            let counts: Vec<String> = by_kind.iter().map(|(kind, count)| format!("{} {}", kind, count)).collect();
+            println!("down by cause: {}", counts.join(", "));
+        }
+
+        if !self.statistics.is_empty() {
+            for summary in self.statistics.summaries() {
+                print!("\n{}", summary);
+            }
+        }
+        Ok(())
+    }
+}
+
+
+/// Writes all results to a JSON file as a [`Document`], replacing it, once the run is over.
+///
+/// In watch mode the file is rewritten on every flush with the latest result per target;
+/// its statistics always cover every result so far.
+#[derive(Debug, Clone)]
+pub struct JsonFileReporter {
+    path: PathBuf,
+    statistics: Statistics,
+}
+
+
+impl JsonFileReporter {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        JsonFileReporter { path: path.into(), statistics: Statistics::default() }
+    }
+
+    /// Adds statistics for the trailing `windows` to those for the whole run.
+    pub fn with_windows(mut self, windows: Vec<Duration>) -> Self {
+        self.statistics = Statistics::new(windows);
+        self
+    }
+}
+
+
+impl Reporter for JsonFileReporter {
+    fn on_result(&mut self, result: &CheckResult) -> io::Result<()> {
+        self.statistics.record(result);
+        Ok(())
+    }
+
+    fn finish(&mut self, results: &[CheckResult]) -> io::Result<()> {
+        let file = BufWriter::new(File::create(&self.path)?);
+        let document = Document::new(results.to_vec()).with_statistics(self.statistics.summaries());
+        serde_json::to_writer_pretty(file, &document)?;
+        Ok(())
+    }
+
+    fn flush(&mut self, latest: &[CheckResult]) -> io::Result<()> {
+        self.finish(latest)
+    }
+}
+
+
+/// Appends one JSON object per line (a schema [`Line`]) as each result arrives, so the
+/// file is usable while the run is still going, and a [`StatisticsLine`] once it's over.
+#[derive(Debug)]
+pub struct JsonLinesReporter {
+    file: BufWriter<File>,
+    statistics: Statistics,
+}
+
+
+impl JsonLinesReporter {
+    /// Creates or truncates the file at `path`.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(JsonLinesReporter { file: BufWriter::new(File::create(path)?), statistics: Statistics::default() })
+    }
+
+    /// Keeps existing lines in the file at `path` and writes after them.
+    pub fn append(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(JsonLinesReporter { file: BufWriter::new(file), statistics: Statistics::default() })
+    }
+
+    /// Adds statistics for the trailing `windows` to those for the whole run.
+    pub fn with_windows(mut self, windows: Vec<Duration>) -> Self {
+        self.statistics = Statistics::new(windows);
+        self
+    }
+}
+
+
+impl Reporter for JsonLinesReporter {
+    fn on_result(&mut self, result: &CheckResult) -> io::Result<()> {
+        self.statistics.record(result);
+        serde_json::to_writer(&mut self.file, &Line::new(result.clone()))?;
+        writeln!(self.file)?;
+        self.file.flush()
+    }
+
+    fn finish(&mut self, _results: &[CheckResult]) -> io::Result<()> {
+        if self.statistics.is_empty() {
+            return Ok(());
+        }
+        serde_json::to_writer(&mut self.file, &StatisticsLine::new(self.statistics.summaries()))?;
+        writeln!(self.file)?;
+        self.file.flush()
+    }
+}
+
+
+/// Writes one line per result to a text file as each result arrives, and a table of
+/// [`Statistics`] once the run is over.
+#[derive(Debug)]
+pub struct TextFileReporter {
+    file: BufWriter<File>,
+    statistics: Statistics,
+}
+
+
+impl TextFileReporter {
+    /// Creates or truncates the file at `path`.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(TextFileReporter { file: BufWriter::new(File::create(path)?), statistics: Statistics::default() })
+    }
+
+    /// Adds statistics for the trailing `windows` to the table for the whole run.
+    pub fn with_windows(mut self, windows: Vec<Duration>) -> Self {
+        self.statistics = Statistics::new(windows);
+        self
+    }
+}
+
+
+impl Reporter for TextFileReporter {
+    fn on_result(&mut self, result: &CheckResult) -> io::Result<()> {
+        self.statistics.record(result);
+        writeln!(self.file, "{}", result)?;
+        self.file.flush()
+    }
+
+    fn finish(&mut self, _results: &[CheckResult]) -> io::Result<()> {
+        if !self.statistics.is_empty() {
+            for summary in self.statistics.summaries() {
+                write!(self.file, "\n{}", summary)?;
+            }
+        }
+        self.file.flush()
+    }
+}
+
+
+/// How often a long-running [`HistoryReporter`] compacts its history.
+const COMPACT_EVERY: Duration = Duration::from_secs(3600);
+
+
+/// Appends each result to a [`History`] as it arrives.
+///
+/// The history is compacted when the reporter is opened and, in watch mode, about once
+/// an hour after that.
+#[derive(Debug)]
+pub struct HistoryReporter {
+    history: History,
+    compacted: Instant,
+}
+
+
+impl HistoryReporter {
+    /// Opens (or creates) the history in `dir` and compacts it.
+    pub fn open(dir: impl Into<PathBuf>, options: HistoryOptions) -> io::Result<Self> {
+        let history = History::open(dir, options)?;
+        history.compact(Utc::now())?;
+        Ok(HistoryReporter { history, compacted: Instant::now() })
+    }
+
+    /// The history written to, for reading it while the run goes on.
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+}
+
+
+impl Reporter for HistoryReporter {
+    fn on_result(&mut self, result: &CheckResult) -> io::Result<()> {
+        self.history.append(std::slice::from_ref(result))
+    }
+
+    fn flush(&mut self, _latest: &[CheckResult]) -> io::Result<()> {
+        if self.compacted.elapsed() >= COMPACT_EVERY {
+            self.history.compact(Utc::now())?;
+            self.compacted = Instant::now();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,253 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+
+/// The most retries a policy loaded from the command line or a config file may ask for.
+pub const MAX_RETRIES: u32 = 10;
+
+
+/// An outcome that may be worth trying again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum RetryOn {
+    /// The connection could not be made (DNS, refused, reset, TLS).
+    #[serde(rename = "connect")]
+    Connect,
+    /// The request timed out.
+    #[serde(rename = "timeout")]
+    Timeout,
+    /// The server answered with a 5xx status.
+    #[serde(rename = "5xx")]
+    ServerError,
+    /// The server answered `429 Too Many Requests`.
+    #[serde(rename = "429")]
+    TooManyRequests,
+}
+
+
+impl FromStr for RetryOn {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim() {
+            "connect" => Ok(RetryOn::Connect),
+            "timeout" => Ok(RetryOn::Timeout),
+            "5xx" => Ok(RetryOn::ServerError),
+            "429" => Ok(RetryOn::TooManyRequests),
+            other => Err(format!("unknown retry class `{}` (use connect, timeout, 5xx or 429)", other)),
+        }
+    }
+}
+
+
+impl fmt::Display for RetryOn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RetryOn::Connect => "connect",
+            RetryOn::Timeout => "timeout",
+            RetryOn::ServerError => "5xx",
+            RetryOn::TooManyRequests => "429",
+        })
+    }
+}
+
+
+/// When and how often a failed attempt is repeated.
+///
+/// The pause before retry `n` (counting from zero) is `initial_backoff * multiplier^n`,
+/// capped at `max_backoff` and then moved randomly by up to `jitter` of itself. A
+/// `Retry-After` header on a retried response replaces the computed pause, as long as it
+/// is no longer than `max_backoff`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Extra attempts after the first one.
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub multiplier: f64,
+    /// Fraction in `0.0..=1.0`.
+    pub jitter: f64,
+    /// No retry is started if it would begin later than this after the first attempt.
+    pub max_elapsed: Option<Duration>,
+    pub retry_on: Vec<RetryOn>,
+}
+
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 0,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            multiplier: 2.0,
+            jitter: 0.2,
+            max_elapsed: None,
+            retry_on: vec![
+                RetryOn::Connect,
+                RetryOn::Timeout,
+                RetryOn::ServerError,
+                RetryOn::TooManyRequests,
+            ],
+        }
+    }
+}
+
+
+impl RetryPolicy {
+    /// A policy with the default backoff that retries up to `max_retries` times.
+    pub fn with_max_retries(max_retries: u32) -> Self {
+        RetryPolicy { max_retries, ..RetryPolicy::default() }
+    }
+
+    /// How long to wait before the next attempt, or `None` to stop retrying.
+    ///
+    /// `retries_done` counts the retries already made, `outcome` classifies the attempt
+    /// that just finished (`None` when it succeeded or isn't retryable) and `elapsed` is the
+    /// time since the first attempt started.
+    pub fn next_delay(
+        &self,
+        retries_done: u32,
+        outcome: Option<RetryOn>,
+        retry_after: Option<Duration>,
+        elapsed: Duration,
+    ) -> Option<Duration> {
+        let outcome = outcome?;
+        if retries_done >= self.max_retries || !self.retry_on.contains(&outcome) {
+            return None;
+        }
+
+        let delay = match retry_after {
+            Some(requested) if requested > self.max_backoff => return None,
+            Some(requested) => requested,
+            None => {
+                // Capped in f64 first, as a Duration this large would overflow
+                let exponent = i32::try_from(retries_done).unwrap_or(i32::MAX);
+                let seconds = self.initial_backoff.as_secs_f64() * self.multiplier.powi(exponent);
+                let backoff = Duration::from_secs_f64(seconds.min(self.max_backoff.as_secs_f64()));
+                backoff.mul_f64(1.0 + self.jitter * (rand::random::<f64>() * 2.0 - 1.0))
+            }
+        };
+
+        match self.max_elapsed {
+            Some(limit) if elapsed + delay > limit => None,
+            _ => Some(delay),
+        }
+    }
+}
+
+
+/// Parses a `Retry-After` value: either seconds or an HTTP date.
+pub(crate) fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?.with_timezone(&Utc);
+    Some((date - Utc::now()).to_std().unwrap_or(Duration::ZERO))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Retries up to `max_retries` times without jitter, so every pause is exact.
+    fn steady(max_retries: u32) -> RetryPolicy {
+        RetryPolicy { jitter: 0.0, ..RetryPolicy::with_max_retries(max_retries) }
+    }
+
+    fn delay(policy: &RetryPolicy, retries_done: u32) -> Option<Duration> {
+        policy.next_delay(retries_done, Some(RetryOn::ServerError), None, Duration::ZERO)
+    }
+
+    #[test]
+    fn backoff_grows_by_the_multiplier() {
+        let policy = steady(5);
+        assert_eq!(delay(&policy, 0), Some(Duration::from_millis(100)));
+        assert_eq!(delay(&policy, 1), Some(Duration::from_millis(200)));
+        assert_eq!(delay(&policy, 2), Some(Duration::from_millis(400)));
+        assert_eq!(delay(&policy, 4), Some(Duration::from_millis(1600)));
+        assert_eq!(delay(&policy, 5), None);
+    }
+
+    #[test]
+    fn backoff_stops_at_the_cap() {
+        let policy = RetryPolicy { max_backoff: Duration::from_millis(300), ..steady(5) };
+        assert_eq!(delay(&policy, 1), Some(Duration::from_millis(200)));
+        assert_eq!(delay(&policy, 2), Some(Duration::from_millis(300)));
+        assert_eq!(delay(&policy, 4), Some(Duration::from_millis(300)));
+    }
+
+    #[test]
+    fn a_large_retry_count_stays_at_the_cap() {
+        let policy = steady(u32::MAX);
+        assert_eq!(delay(&policy, 64), Some(Duration::from_secs(10)));
+        assert_eq!(delay(&policy, u32::MAX - 1), Some(Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn jitter_stays_within_its_fraction() {
+        let policy = RetryPolicy { jitter: 0.5, ..steady(3) };
+        for _ in 0..200 {
+            let pause = delay(&policy, 1).unwrap();
+            assert!(pause >= Duration::from_millis(100) && pause <= Duration::from_millis(300), "{:?}", pause);
+        }
+    }
+
+    #[test]
+    fn no_retry_starts_past_max_elapsed() {
+        let policy = RetryPolicy { max_elapsed: Some(Duration::from_secs(1)), ..steady(3) };
+        let after = |elapsed| policy.next_delay(1, Some(RetryOn::Timeout), None, Duration::from_millis(elapsed));
+        assert_eq!(after(800), Some(Duration::from_millis(200)));
+        assert_eq!(after(801), None);
+    }
+
+    #[test]
+    fn only_listed_outcomes_are_retried() {
+        let policy = RetryPolicy { retry_on: vec![RetryOn::Connect, RetryOn::TooManyRequests], ..steady(3) };
+        let retried = |outcome| policy.next_delay(0, outcome, None, Duration::ZERO).is_some();
+        assert!(retried(Some(RetryOn::Connect)));
+        assert!(retried(Some(RetryOn::TooManyRequests)));
+        assert!(!retried(Some(RetryOn::Timeout)));
+        assert!(!retried(Some(RetryOn::ServerError)));
+        assert!(!retried(None));
+    }
+
+    #[test]
+    fn retry_after_replaces_the_backoff_up_to_the_cap() {
+        let policy = RetryPolicy { max_elapsed: Some(Duration::from_secs(6)), ..steady(3) };
+        let after = |requested, elapsed| {
+            policy.next_delay(0, Some(RetryOn::TooManyRequests), Some(requested), Duration::from_secs(elapsed))
+        };
+        assert_eq!(after(Duration::from_secs(5), 0), Some(Duration::from_secs(5)));
+        assert_eq!(after(Duration::from_secs(5), 2), None);
+        assert_eq!(after(Duration::from_secs(11), 0), None);
+    }
+
+    #[test]
+    fn retry_after_takes_seconds_or_a_date() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after(" 0 "), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("soon"), None);
+        assert_eq!(parse_retry_after("-5"), None);
+
+        let later = (Utc::now() + chrono::Duration::seconds(30)).to_rfc2822();
+        let pause = parse_retry_after(&later).unwrap();
+        assert!(pause > Duration::from_secs(28) && pause <= Duration::from_secs(30), "{:?}", pause);
+    }
+
+    #[test]
+    fn classes_parse_and_print() {
+        for class in [RetryOn::Connect, RetryOn::Timeout, RetryOn::ServerError, RetryOn::TooManyRequests] {
+            assert_eq!(class.to_string().parse(), Ok(class));
+        }
+        assert_eq!(
+            "4xx".parse::<RetryOn>(),
+            Err("unknown retry class `4xx` (use connect, timeout, 5xx or 429)".to_string())
+        );
+    }
+}
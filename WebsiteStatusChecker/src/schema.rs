@@ -0,0 +1,822 @@
+//! The JSON format results are saved in.
+//!
+//! A `json` results file is a single [`Document`]:
+//!
+//! ```json
+//! {
+//!   "schema_version": 2,
+//!   "generated_at": "2025-05-15T04:04:51.642745448Z",
+//!   "results": [
+//!     {
+//!       "url": "https://www.target.com/",
+//!       "name": "target",
+//!       "tags": ["retail"],
+//!       "up": true,
+//!       "status": 200,
+//!       "error": null,
+//!       "response_time_ms": 482.969727,
+//!       "timings": {
+//!         "dns_ms": 12.1,
+//!         "connect_ms": 20.4,
+//!         "tls_ms": 61.8,
+//!         "ttfb_ms": 388.6,
+//!         "download_ms": 35.2
+//!       },
+//!       "timestamp": "2025-05-15T04:04:48.386781686Z",
+//!       "attempts": [
+//!         { "status": 503, "error": null, "duration_ms": 120.5, "timings": { ... }, "backoff_ms": 104.2 },
+//!         { "status": 200, "error": null, "duration_ms": 518.1, "timings": { ... }, "backoff_ms": null }
+//!       ]
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! A `jsonl` file holds one result object per line, each with its own `schema_version`.
+//! When the run is over a [`StatisticsLine`] with the same `statistics` as a document is
+//! appended; readers that only want results should skip lines with a `statistics` key.
+//!
+//! Document `statistics` are a list of summaries, first the whole run and then each
+//! requested window, measured back from the newest result:
+//!
+//! - `window_ms`: the window's length, or `null` for the whole run.
+//! - `from`, `to`: timestamps of the oldest and newest result included.
+//! - `overall`, and `targets` by target name (or URL): `checks`, `up`,
+//!   `uptime_percent`, `latency_ms` (`min`, `max`, `mean`, `p50`, `p90` and `p99` of the
+//!   response times of checks that got a response, or `null`), `errors` and
+//!   `error_rate_percent` by error kind, and `longest_outage_ms`, the longest a target
+//!   stayed down until its next successful check (or its last check), or `null`.
+//!   Checks aborted at shutdown are not counted.
+//!
+//! Result fields:
+//!
+//! - `url`: the URL that was checked.
+//! - `name`, `tags`: copied from the target; omitted when not set.
+//! - `up`: whether the check succeeded.
+//! - `status`: the final response's HTTP status code, or `null` if no response was
+//!   received. It is set even when the check failed on that status.
+//! - `error`: why the check failed, or `null` if it succeeded.
+//! - `error_kind`: what broke, present only on failures: one of `dns`,
+//!   `connection_refused`, `connect`, `tls`, `timeout`, `redirect`, `unexpected_status`,
+//!   `assertion`, `aborted` or `other`. Results read from files without it get `other`.
+//! - `redirects`: the redirects the last attempt followed, in order, each with the `url`
+//!   requested, the `status` it answered with and the `location` it pointed to; omitted
+//!   when there were none.
+//! - `headers`: the target's captured response headers, by lowercase name; omitted when
+//!   none were captured.
+//! - `certificate`: the `subject`, `issuer`, `sans` and `not_after` (RFC 3339) of the
+//!   server's certificate, for `https` targets that reached the TLS handshake, including
+//!   ones whose certificate was rejected; omitted otherwise. Its `chain` lists the same
+//!   fields for each further certificate the server sent, in order, and is omitted when
+//!   the server sent none.
+//! - `warnings`: problems that don't make the target down, such as a certificate expiring
+//!   within the target's `cert_expiry_warning`; omitted when there are none.
+//! - `response_time_ms`: milliseconds from sending the last attempt until its response's
+//!   headers arrived.
+//! - `timings`: milliseconds the last attempt spent resolving the host (`dns_ms`, zero
+//!   for IP addresses), connecting (`connect_ms`), in the TLS handshake (`tls_ms`), waiting
+//!   for the response headers (`ttfb_ms`) and reading the body (`download_ms`). Phases
+//!   that weren't reached, and `tls_ms` for plain `http`, are `null`; with redirects each
+//!   phase is summed over every hop.
+//! - `timestamp`: when the check finished, as an RFC 3339 UTC timestamp.
+//! - `attempts`: every request made, in order, with its `status`, `error` and
+//!   `error_kind` as above, its `duration_ms` (including reading the body), its
+//!   `timings`, and the `backoff_ms` waited before the next attempt (`null` on the last
+//!   one).
+//!
+//! A history directory's `rollups.jsonl` holds one object per target and period with
+//! its `schema_version`, `url`, `name` (omitted when not set), `start` (RFC 3339),
+//! `period_ms`, `checks`, `up`, `errors` by error kind, and `responses`, the number of
+//! checks that got a response, with their `mean_response_time_ms`,
+//! `min_response_time_ms` and `max_response_time_ms` (`null` when there were none).
+//!
+//! Webhook and command notifiers receive an alert object each time a target goes down
+//! or comes back up: `schema_version`, `event` (`down` or `up`), `target` (the name or
+//! URL), `url`, `tags` (omitted when empty), `message` (one line for humans),
+//! `down_since` (the first failed check of the outage), `downtime_ms` (how long it
+//! lasted, on `up` events; `null` otherwise), `consecutive` (the checks in a row that
+//! caused the transition) and `result`, the check that caused it, as described below.
+//!
+//! Version 1 measured `response_time_ms` across all attempts and had no `attempts`;
+//! version 1 files are still read, with an empty `attempts` list.
+//!
+//! [`SCHEMA_VERSION`] is bumped whenever a field is removed or changes meaning; new
+//! fields may be added without a bump, so readers should ignore fields they don't know.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::alert::{AlertEvent, AlertState};
+use crate::check::{Attempt, CheckResult};
+use crate::client::Timings;
+use crate::error::{CheckError, ErrorKind};
+use crate::history::Rollup;
+use crate::redirect::Hop;
+use crate::stats::{Latency, Stats, Summary};
+use crate::tls::Certificate;
+
+
+/// Version written to, and required in, every results file.
+pub const SCHEMA_VERSION: u32 = 2;
+
+
+/// Oldest version [`read_results`] still accepts.
+const OLDEST_READABLE_VERSION: u32 = 1;
+
+
+/// The contents of a `json` results file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Document {
+    pub schema_version: u32,
+    pub generated_at: DateTime<Utc>,
+    pub results: Vec<CheckResult>,
+    /// Written for readers of the file; not read back, since it can be recomputed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty", skip_deserializing)]
+    pub statistics: Vec<Summary>,
+}
+
+
+impl Document {
+    pub fn new(results: Vec<CheckResult>) -> Self {
+        Document { schema_version: SCHEMA_VERSION, generated_at: Utc::now(), results, statistics: Vec::new() }
+    }
+
+    pub fn with_statistics(mut self, statistics: Vec<Summary>) -> Self {
+        self.statistics = statistics;
+        self
+    }
+}
+
+
+/// One line of a `jsonl` results file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Line {
+    pub schema_version: u32,
+    #[serde(flatten)]
+    pub result: CheckResult,
+}
+
+
+impl Line {
+    pub fn new(result: CheckResult) -> Self {
+        Line { schema_version: SCHEMA_VERSION, result }
+    }
+}
+
+
+/// The last line of a `jsonl` file once the run is over.
+#[derive(Debug, Clone, Serialize)]
+pub struct StatisticsLine {
+    pub schema_version: u32,
+    pub statistics: Vec<Summary>,
+}
+
+
+impl StatisticsLine {
+    pub fn new(statistics: Vec<Summary>) -> Self {
+        StatisticsLine { schema_version: SCHEMA_VERSION, statistics }
+    }
+}
+
+
+/// How a [`CheckResult`] is laid out in JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Record {
+    url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(default)]
+    up: bool,
+    status: Option<u16>,
+    error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error_kind: Option<ErrorKind>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    redirects: Vec<Hop>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    headers: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    certificate: Option<Certificate>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<String>,
+    response_time_ms: f64,
+    #[serde(default)]
+    timings: TimingsRecord,
+    timestamp: DateTime<Utc>,
+    #[serde(default)]
+    attempts: Vec<AttemptRecord>,
+}
+
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AttemptRecord {
+    status: Option<u16>,
+    error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error_kind: Option<ErrorKind>,
+    duration_ms: f64,
+    #[serde(default)]
+    timings: TimingsRecord,
+    backoff_ms: Option<f64>,
+}
+
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct TimingsRecord {
+    dns_ms: Option<f64>,
+    connect_ms: Option<f64>,
+    tls_ms: Option<f64>,
+    ttfb_ms: Option<f64>,
+    download_ms: Option<f64>,
+}
+
+
+impl From<CheckResult> for Record {
+    fn from(result: CheckResult) -> Self {
+        let (error, error_kind) = split_error(result.error);
+
+        Record {
+            url: result.url,
+            name: result.name,
+            tags: result.tags,
+            up: error.is_none(),
+            status: result.status,
+            error,
+            error_kind,
+            redirects: result.redirects,
+            headers: result.headers,
+            certificate: result.certificate,
+            warnings: result.warnings,
+            response_time_ms: millis(result.response_time),
+            timings: result.timings.into(),
+            timestamp: result.timestamp,
+            attempts: result.attempts.into_iter().map(AttemptRecord::from).collect(),
+        }
+    }
+}
+
+
+impl TryFrom<Record> for CheckResult {
+    type Error = String;
+
+    fn try_from(record: Record) -> Result<Self, String> {
+        let error = join_error(record.status, record.error, record.error_kind);
+
+        Ok(CheckResult {
+            url: record.url,
+            name: record.name,
+            tags: record.tags,
+            status: record.status,
+            error,
+            redirects: record.redirects,
+            headers: record.headers,
+            certificate: record.certificate,
+            warnings: record.warnings,
+            response_time: from_millis(record.response_time_ms)?,
+            timings: record.timings.try_into()?,
+            timestamp: record.timestamp,
+            attempts: record.attempts.into_iter().map(Attempt::try_from).collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+
+impl From<Attempt> for AttemptRecord {
+    fn from(attempt: Attempt) -> Self {
+        let (error, error_kind) = split_error(attempt.error);
+        AttemptRecord {
+            status: attempt.status,
+            error,
+            error_kind,
+            duration_ms: millis(attempt.duration),
+            timings: attempt.timings.into(),
+            backoff_ms: attempt.backoff.map(millis),
+        }
+    }
+}
+
+
+impl TryFrom<AttemptRecord> for Attempt {
+    type Error = String;
+
+    fn try_from(record: AttemptRecord) -> Result<Self, String> {
+        Ok(Attempt {
+            status: record.status,
+            error: join_error(record.status, record.error, record.error_kind),
+            duration: from_millis(record.duration_ms)?,
+            timings: record.timings.try_into()?,
+            backoff: record.backoff_ms.map(from_millis).transpose()?,
+        })
+    }
+}
+
+
+impl From<Timings> for TimingsRecord {
+    fn from(timings: Timings) -> Self {
+        TimingsRecord {
+            dns_ms: timings.dns.map(millis),
+            connect_ms: timings.connect.map(millis),
+            tls_ms: timings.tls.map(millis),
+            ttfb_ms: timings.ttfb.map(millis),
+            download_ms: timings.download.map(millis),
+        }
+    }
+}
+
+
+impl TryFrom<TimingsRecord> for Timings {
+    type Error = String;
+
+    fn try_from(record: TimingsRecord) -> Result<Self, String> {
+        Ok(Timings {
+            dns: record.dns_ms.map(from_millis).transpose()?,
+            connect: record.connect_ms.map(from_millis).transpose()?,
+            tls: record.tls_ms.map(from_millis).transpose()?,
+            ttfb: record.ttfb_ms.map(from_millis).transpose()?,
+            download: record.download_ms.map(from_millis).transpose()?,
+        })
+    }
+}
+
+
+/// How a [`Summary`] is laid out in JSON.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct SummaryRecord {
+    window_ms: Option<f64>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    overall: StatsRecord,
+    targets: BTreeMap<String, StatsRecord>,
+}
+
+
+#[derive(Debug, Clone, Serialize)]
+struct StatsRecord {
+    checks: usize,
+    up: usize,
+    uptime_percent: f64,
+    latency_ms: Option<LatencyRecord>,
+    errors: BTreeMap<ErrorKind, usize>,
+    error_rate_percent: BTreeMap<ErrorKind, f64>,
+    longest_outage_ms: Option<f64>,
+}
+
+
+#[derive(Debug, Clone, Serialize)]
+struct LatencyRecord {
+    min: f64,
+    max: f64,
+    mean: f64,
+    p50: f64,
+    p90: f64,
+    p99: f64,
+}
+
+
+impl From<Summary> for SummaryRecord {
+    fn from(summary: Summary) -> Self {
+        SummaryRecord {
+            window_ms: summary.window.map(millis),
+            from: summary.from,
+            to: summary.to,
+            overall: summary.overall.into(),
+            targets: summary.targets.into_iter().map(|(label, stats)| (label, stats.into())).collect(),
+        }
+    }
+}
+
+
+impl From<Stats> for StatsRecord {
+    fn from(stats: Stats) -> Self {
+        StatsRecord {
+            checks: stats.checks,
+            up: stats.up,
+            uptime_percent: stats.uptime(),
+            latency_ms: stats.latency.map(LatencyRecord::from),
+            error_rate_percent: stats.errors.keys().map(|kind| (*kind, stats.error_rate(*kind))).collect(),
+            errors: stats.errors,
+            longest_outage_ms: stats.longest_outage.map(millis),
+        }
+    }
+}
+
+
+impl From<Latency> for LatencyRecord {
+    fn from(latency: Latency) -> Self {
+        LatencyRecord {
+            min: millis(latency.min),
+            max: millis(latency.max),
+            mean: millis(latency.mean),
+            p50: millis(latency.p50),
+            p90: millis(latency.p90),
+            p99: millis(latency.p99),
+        }
+    }
+}
+
+
+/// How a [`Rollup`] is laid out in JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct RollupRecord {
+    schema_version: u32,
+    url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    start: DateTime<Utc>,
+    period_ms: f64,
+    checks: usize,
+    up: usize,
+    #[serde(default)]
+    errors: BTreeMap<ErrorKind, usize>,
+    responses: usize,
+    mean_response_time_ms: Option<f64>,
+    min_response_time_ms: Option<f64>,
+    max_response_time_ms: Option<f64>,
+}
+
+
+impl From<Rollup> for RollupRecord {
+    fn from(rollup: Rollup) -> Self {
+        RollupRecord {
+            schema_version: SCHEMA_VERSION,
+            url: rollup.url,
+            name: rollup.name,
+            start: rollup.start,
+            period_ms: millis(rollup.period),
+            checks: rollup.checks,
+            up: rollup.up,
+            errors: rollup.errors,
+            responses: rollup.responses,
+            mean_response_time_ms: rollup.mean_response_time.map(millis),
+            min_response_time_ms: rollup.min_response_time.map(millis),
+            max_response_time_ms: rollup.max_response_time.map(millis),
+        }
+    }
+}
+
+
+impl TryFrom<RollupRecord> for Rollup {
+    type Error = String;
+
+    fn try_from(record: RollupRecord) -> Result<Self, String> {
+        Ok(Rollup {
+            url: record.url,
+            name: record.name,
+            start: record.start,
+            period: from_millis(record.period_ms)?,
+            checks: record.checks,
+            up: record.up,
+            errors: record.errors,
+            responses: record.responses,
+            mean_response_time: record.mean_response_time_ms.map(from_millis).transpose()?,
+            min_response_time: record.min_response_time_ms.map(from_millis).transpose()?,
+            max_response_time: record.max_response_time_ms.map(from_millis).transpose()?,
+        })
+    }
+}
+
+
+/// How an [`AlertEvent`] is laid out in JSON.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct AlertRecord {
+    schema_version: u32,
+    event: AlertState,
+    target: String,
+    url: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    message: String,
+    down_since: DateTime<Utc>,
+    downtime_ms: Option<f64>,
+    consecutive: u32,
+    result: CheckResult,
+}
+
+
+impl From<AlertEvent> for AlertRecord {
+    fn from(event: AlertEvent) -> Self {
+        AlertRecord {
+            schema_version: SCHEMA_VERSION,
+            event: event.state,
+            target: event.label().to_string(),
+            url: event.result.url.clone(),
+            tags: event.result.tags.clone(),
+            message: event.message(),
+            down_since: event.down_since,
+            downtime_ms: event.downtime.map(millis),
+            consecutive: event.consecutive,
+            result: event.result,
+        }
+    }
+}
+
+
+fn split_error(error: Option<CheckError>) -> (Option<String>, Option<ErrorKind>) {
+    match error {
+        Some(err) => (Some(err.message().to_string()), Some(err.kind())),
+        None => (None, None),
+    }
+}
+
+
+fn join_error(status: Option<u16>, error: Option<String>, kind: Option<ErrorKind>) -> Option<CheckError> {
+    match (status, error) {
+        (_, Some(err)) => Some(CheckError::new(kind.unwrap_or(ErrorKind::Other), err)),
+        (Some(_), None) => None,
+        (None, None) => Some(CheckError::new(ErrorKind::Other, "no status recorded")),
+    }
+}
+
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+
+fn from_millis(millis: f64) -> Result<Duration, String> {
+    Duration::try_from_secs_f64(millis.max(0.0) / 1000.0).map_err(|_| format!("{:e}ms is out of range", millis))
+}
+
+
+/// Reads a `json` or `jsonl` results file.
+pub fn read_results(path: &Path) -> io::Result<Vec<CheckResult>> {
+    let contents = std::fs::read_to_string(path)?;
+
+    // A whole-file object with `results` is a document; anything else is read as lines
+    match serde_json::from_str::<serde_json::Value>(&contents) {
+        Ok(value) if value.get("results").is_some() => {
+            let document: Document = serde_json::from_value(value).map_err(invalid_data)?;
+            check_version(document.schema_version)?;
+            return Ok(document.results);
+        }
+        Ok(serde_json::Value::Array(_)) => {
+            return Err(invalid_data("unversioned results from an older release; run `check` again"));
+        }
+        _ => {}
+    }
+
+    let mut results = Vec::new();
+    for (number, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value: serde_json::Value = serde_json::from_str(line)
+            .map_err(|err| invalid_data(format!("line {}: {}", number + 1, err)))?;
+        if value.get("statistics").is_some() {
+            continue;
+        }
+        let line: Line = serde_json::from_value(value)
+            .map_err(|err| invalid_data(format!("line {}: {}", number + 1, err)))?;
+        check_version(line.schema_version)?;
+        results.push(line.result);
+    }
+    Ok(results)
+}
+
+
+pub(crate) fn check_version(version: u32) -> io::Result<()> {
+    if (OLDEST_READABLE_VERSION..=SCHEMA_VERSION).contains(&version) {
+        Ok(())
+    } else {
+        Err(invalid_data(format!(
+            "unsupported schema_version {} (this build reads versions {} to {})",
+            version, OLDEST_READABLE_VERSION, SCHEMA_VERSION
+        )))
+    }
+}
+
+
+fn invalid_data(err: impl ToString) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::error::{CheckError, ErrorKind};
+
+    /// A `json` results file as version 1 wrote it.
+    const V1_DOCUMENT: &str = r#"{
+        "schema_version": 1,
+        "generated_at": "2025-05-15T04:04:51.642745448Z",
+        "results": [
+            {
+                "url": "https://www.target.com/",
+                "name": "target",
+                "tags": ["retail"],
+                "up": true,
+                "status": 200,
+                "error": null,
+                "response_time_ms": 482.5,
+                "timestamp": "2025-05-15T04:04:48.386781686Z"
+            },
+            {
+                "url": "https://www.walmart.com/",
+                "up": false,
+                "status": null,
+                "error": "connection refused",
+                "response_time_ms": 3.0,
+                "timestamp": "2025-05-15T04:04:49Z"
+            }
+        ]
+    }"#;
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_747_281_888 + seconds, 0).unwrap()
+    }
+
+    fn timings(millis: u64) -> Timings {
+        Timings {
+            dns: Some(Duration::from_millis(millis)),
+            connect: Some(Duration::from_millis(millis * 2)),
+            tls: None,
+            ttfb: Some(Duration::from_millis(millis * 3)),
+            download: Some(Duration::from_millis(millis * 4)),
+        }
+    }
+
+    /// A check that passed on its second attempt, with every optional field set.
+    fn full_result() -> CheckResult {
+        let failed = Attempt {
+            status: Some(503),
+            error: Some(CheckError::new(ErrorKind::UnexpectedStatus, "status 503 is not in 200-299")),
+            duration: Duration::from_millis(120),
+            timings: timings(1),
+            backoff: Some(Duration::from_millis(250)),
+        };
+        let passed = Attempt {
+            status: Some(200),
+            error: None,
+            duration: Duration::from_millis(80),
+            timings: timings(2),
+            backoff: None,
+        };
+        let certificate = Certificate {
+            subject: "CN=www.target.com".to_string(),
+            issuer: "CN=Example CA".to_string(),
+            sans: vec!["www.target.com".to_string()],
+            not_after: at(86400),
+            chain: vec![Certificate {
+                subject: "CN=Example CA".to_string(),
+                issuer: "CN=Example Root".to_string(),
+                sans: Vec::new(),
+                not_after: at(2 * 86400),
+                chain: Vec::new(),
+            }],
+        };
+        CheckResult {
+            url: "http://target.com/".to_string(),
+            name: Some("target".to_string()),
+            tags: vec!["retail".to_string()],
+            status: Some(200),
+            error: None,
+            redirects: vec![Hop {
+                url: "http://target.com/".to_string(),
+                status: 301,
+                location: "https://www.target.com/".to_string(),
+            }],
+            headers: BTreeMap::from([("server".to_string(), "nginx".to_string())]),
+            certificate: Some(certificate),
+            warnings: vec!["the certificate expires in 1 day".to_string()],
+            response_time: Duration::from_millis(60),
+            timings: timings(2),
+            timestamp: at(0),
+            attempts: vec![failed, passed],
+        }
+    }
+
+    /// A check that never got a response.
+    fn down_result() -> CheckResult {
+        let error = CheckError::new(ErrorKind::ConnectionRefused, "connection refused");
+        CheckResult {
+            url: "https://www.walmart.com/".to_string(),
+            name: None,
+            tags: Vec::new(),
+            status: None,
+            error: Some(error.clone()),
+            redirects: Vec::new(),
+            headers: BTreeMap::new(),
+            certificate: None,
+            warnings: Vec::new(),
+            response_time: Duration::ZERO,
+            timings: Timings { dns: Some(Duration::from_millis(1)), ..Timings::default() },
+            timestamp: at(1),
+            attempts: vec![Attempt {
+                status: None,
+                error: Some(error),
+                duration: Duration::from_millis(3),
+                timings: Timings { dns: Some(Duration::from_millis(1)), ..Timings::default() },
+                backoff: None,
+            }],
+        }
+    }
+
+    fn write(name: &str, contents: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("wsc-schema-{}-{}", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    /// Asserts two results would be saved identically.
+    fn assert_same(left: &CheckResult, right: &CheckResult) {
+        assert_eq!(serde_json::to_value(left).unwrap(), serde_json::to_value(right).unwrap());
+    }
+
+    #[test]
+    fn documents_survive_a_round_trip() {
+        let document = Document::new(vec![full_result(), down_result()]);
+        let json = serde_json::to_string(&document).unwrap();
+        let read: Document = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(read.schema_version, SCHEMA_VERSION);
+        assert_eq!(read.generated_at, document.generated_at);
+        assert_eq!(read.results.len(), 2);
+        assert_same(&read.results[0], &document.results[0]);
+        assert_same(&read.results[1], &document.results[1]);
+
+        let (full, down) = (&read.results[0], &read.results[1]);
+        assert_eq!(full.attempts, full_result().attempts);
+        assert_eq!(full.timings, timings(2));
+        assert_eq!(full.certificate, full_result().certificate);
+        assert_eq!(full.redirects, full_result().redirects);
+        assert_eq!(down.error, down_result().error);
+        assert_eq!(down.status, None);
+    }
+
+    #[test]
+    fn lines_survive_a_round_trip() {
+        let json = serde_json::to_string(&Line::new(full_result())).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["schema_version"], SCHEMA_VERSION);
+        assert_eq!(value["url"], "http://target.com/");
+
+        let read: Line = serde_json::from_str(&json).unwrap();
+        assert_eq!(read.schema_version, SCHEMA_VERSION);
+        assert_same(&read.result, &full_result());
+    }
+
+    #[test]
+    fn version_1_files_are_read_and_upgraded() {
+        let path = write("v1.json", V1_DOCUMENT);
+        let results = read_results(&path);
+        std::fs::remove_file(&path).unwrap();
+        let results = results.unwrap();
+
+        let [up, down] = &results[..] else { panic!("{:?}", results) };
+        assert!(up.is_up());
+        assert_eq!(up.name.as_deref(), Some("target"));
+        assert_eq!(up.response_time, Duration::from_micros(482_500));
+        assert_eq!(up.timings, Timings::default());
+        assert!(up.attempts.is_empty());
+        assert_eq!(down.error, Some(CheckError::new(ErrorKind::Other, "connection refused")));
+
+        // Saved again, they are written as the current version
+        let upgraded = serde_json::to_value(Document::new(results)).unwrap();
+        assert_eq!(upgraded["schema_version"], SCHEMA_VERSION);
+        assert_eq!(upgraded["results"][1]["error_kind"], "other");
+        assert_eq!(upgraded["results"][0]["attempts"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn version_1_lines_are_read() {
+        let line = concat!(
+            r#"{"schema_version":1,"url":"https://www.target.com/","up":true,"status":204,"error":null,"#,
+            r#""response_time_ms":12.0,"timestamp":"2025-05-15T04:04:48Z"}"#,
+        );
+        let path = write("v1.jsonl", &format!("{}\n\n{}\n", line, line));
+        let results = read_results(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let results = results.unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].status, Some(204));
+        assert!(results[0].attempts.is_empty());
+    }
+
+    #[test]
+    fn out_of_range_durations_are_rejected() {
+        let path = write("huge.json", &V1_DOCUMENT.replacen("482.5", "1e300", 1));
+        let err = read_results(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("1e300ms is out of range"), "{}", err);
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let path = write("v3.json", &V1_DOCUMENT.replacen("\"schema_version\": 1", "\"schema_version\": 3", 1));
+        let err = read_results(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "unsupported schema_version 3 (this build reads versions 1 to 2)");
+    }
+}
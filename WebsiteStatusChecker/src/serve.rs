@@ -0,0 +1,451 @@
+//! A small HTTP server with the latest results, their history and statistics.
+//!
+//! Every route answers `GET` (and `HEAD`):
+//!
+//! - `/`: an HTML status page with a row per target, refreshed every 30 seconds.
+//! - `/api/status`: the latest result per target, as a schema
+//!   [`Document`](crate::schema::Document).
+//! - `/api/history`: `{"schema_version", "results", "rollups"}` with full-resolution
+//!   results and downsampled [`Rollup`]s, oldest first. `target=` keeps one target (by
+//!   name or URL) and `since=` only what is newer than a duration such as `24h`.
+//! - `/api/statistics`: summaries like a document's `statistics`, for the whole history
+//!   and each `window=` given (the server's windows if none are).
+//! - `/metrics`: Prometheus metrics, described in [`crate::metrics`].
+//!
+//! Errors are `{"error": "..."}` with a 4xx or 5xx status. History and statistics come
+//! from the [`History`] when there is one; otherwise they only cover the last
+//! [`RECENT_RESULTS`] results since the server started.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::convert::Infallible;
+use std::fmt::Write as _;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use http_body_util::Full;
+use hyper::body::{Bytes, Incoming};
+use hyper::header::{HeaderValue, ALLOW, CACHE_CONTROL, CONTENT_TYPE};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use serde::Serialize;
+
+use crate::check::CheckResult;
+use crate::config::parse_duration;
+use crate::history::{Contents, History, Rollup};
+use crate::metrics::Metrics;
+use crate::report::Reporter;
+use crate::schema::{Document, SCHEMA_VERSION};
+use crate::shutdown::Shutdown;
+use crate::stats::{window_label, Statistics, Summary};
+
+
+/// Results kept in memory for the API when there is no [`History`].
+pub const RECENT_RESULTS: usize = 10_000;
+
+/// How often the server checks whether it should shut down.
+const TICK: Duration = Duration::from_secs(1);
+
+
+/// The latest results, shared between a run and the server.
+///
+/// Hand a clone to the run as a [`Reporter`] and another to [`serve`].
+#[derive(Debug, Clone, Default)]
+pub struct StatusBoard(Arc<Mutex<Board>>);
+
+
+#[derive(Debug, Default)]
+struct Board {
+    latest: BTreeMap<String, CheckResult>,
+    recent: VecDeque<CheckResult>,
+    metrics: Metrics,
+}
+
+
+impl StatusBoard {
+    pub fn new() -> Self {
+        StatusBoard::default()
+    }
+
+    /// The latest result per target, ordered by name (or URL).
+    pub fn latest(&self) -> Vec<CheckResult> {
+        self.lock().latest.values().cloned().collect()
+    }
+
+    /// The metrics in the Prometheus text format.
+    pub fn metrics(&self) -> String {
+        let board = self.lock();
+        let latest: Vec<CheckResult> = board.latest.values().cloned().collect();
+        board.metrics.render(&latest)
+    }
+
+    fn recent(&self, since: Option<DateTime<Utc>>) -> Vec<CheckResult> {
+        self.lock()
+            .recent
+            .iter()
+            .filter(|result| since.is_none_or(|since| result.timestamp >= since))
+            .cloned()
+            .collect()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Board> {
+        self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+
+impl Reporter for StatusBoard {
+    fn on_result(&mut self, result: &CheckResult) -> io::Result<()> {
+        let mut board = self.lock();
+        board.metrics.record(result);
+        board.latest.insert(result.key(), result.clone());
+        board.recent.push_back(result.clone());
+        if board.recent.len() > RECENT_RESULTS {
+            board.recent.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Forgets targets that were removed by a reload.
+    fn flush(&mut self, latest: &[CheckResult]) -> io::Result<()> {
+        let mut board = self.lock();
+        board.latest = latest.iter().map(|result| (result.key(), result.clone())).collect();
+        board.metrics.retain(latest);
+        Ok(())
+    }
+}
+
+
+/// Options for [`serve`].
+#[derive(Debug, Clone)]
+pub struct ServeOptions {
+    pub listen: SocketAddr,
+    /// Windows the status page shows uptime for, and the API summarises by default.
+    pub windows: Vec<Duration>,
+    /// Where history and statistics are read from, if results are being kept.
+    pub history: Option<History>,
+    /// Stops the server when triggered.
+    pub shutdown: Shutdown,
+}
+
+
+impl Default for ServeOptions {
+    fn default() -> Self {
+        ServeOptions {
+            listen: SocketAddr::from(([127, 0, 0, 1], 8080)),
+            windows: vec![Duration::from_secs(24 * 3600)],
+            history: None,
+            shutdown: Shutdown::new(),
+        }
+    }
+}
+
+
+/// A running server; see [`serve`].
+#[derive(Debug)]
+pub struct Server {
+    local_addr: SocketAddr,
+    thread: JoinHandle<()>,
+}
+
+
+impl Server {
+    /// The address the server is listening on, useful when `listen` had port 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Waits until the server has stopped after its shutdown was triggered.
+    pub fn join(self) {
+        if self.thread.join().is_err() {
+            log::error!("the status server panicked");
+        }
+    }
+}
+
+
+struct State {
+    board: StatusBoard,
+    windows: Vec<Duration>,
+    history: Option<History>,
+}
+
+
+/// Starts serving `board` on `options.listen` from a thread of its own.
+///
+/// Fails straight away if the address can't be bound.
+pub fn serve(board: StatusBoard, options: ServeOptions) -> io::Result<Server> {
+    let listener = std::net::TcpListener::bind(options.listen)?;
+    listener.set_nonblocking(true)?;
+    let local_addr = listener.local_addr()?;
+
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+    let listener = {
+        let _context = runtime.enter();
+        tokio::net::TcpListener::from_std(listener)?
+    };
+
+    let state = Arc::new(State { board, windows: options.windows, history: options.history });
+    let shutdown = options.shutdown;
+    let thread = std::thread::Builder::new()
+        .name("wsc-serve".to_string())
+        .spawn(move || runtime.block_on(accept(listener, state, shutdown)))?;
+
+    log::info!("serving the status page on http://{}/", local_addr);
+    Ok(Server { local_addr, thread })
+}
+
+
+async fn accept(listener: tokio::net::TcpListener, state: Arc<State>, shutdown: Shutdown) {
+    while !shutdown.is_triggered() {
+        let stream = match tokio::time::timeout(TICK, listener.accept()).await {
+            Ok(Ok((stream, _))) => stream,
+            Ok(Err(err)) => {
+                log::warn!("status server: {}", err);
+                continue;
+            }
+            Err(_) => continue,
+        };
+
+        let state = Arc::clone(&state);
+        tokio::spawn(async move {
+            let service = service_fn(move |request| {
+                let state = Arc::clone(&state);
+                async move {
+                    // Reading the history blocks, which would hold up every other connection
+                    let response = tokio::task::spawn_blocking(move || handle(&state, &request)).await;
+                    let response = response.unwrap_or_else(|err| {
+                        error(StatusCode::INTERNAL_SERVER_ERROR, format!("the request failed: {}", err))
+                    });
+                    Ok::<_, Infallible>(response)
+                }
+            });
+            if let Err(err) = http1::Builder::new().serve_connection(TokioIo::new(stream), service).await {
+                log::debug!("status server connection: {}", err);
+            }
+        });
+    }
+}
+
+
+type Outcome = Result<Response<Full<Bytes>>, (StatusCode, String)>;
+
+
+fn handle(state: &State, request: &Request<Incoming>) -> Response<Full<Bytes>> {
+    if request.method() != Method::GET && request.method() != Method::HEAD {
+        let mut response = error(StatusCode::METHOD_NOT_ALLOWED, "only GET is supported".to_string());
+        response.headers_mut().insert(ALLOW, HeaderValue::from_static("GET, HEAD"));
+        return response;
+    }
+
+    let query: Vec<(String, String)> = url::form_urlencoded::parse(request.uri().query().unwrap_or("").as_bytes())
+        .into_owned()
+        .collect();
+    let outcome = match request.uri().path() {
+        "/" => status_page(state),
+        "/api/status" => json(&Document::new(state.board.latest())),
+        "/api/history" => history(state, &query),
+        "/api/statistics" => statistics(state, &query),
+        "/metrics" => Ok(response(StatusCode::OK, "text/plain; version=0.0.4; charset=utf-8", state.board.metrics().into_bytes())),
+        path => Err((StatusCode::NOT_FOUND, format!("no such page: {}", path))),
+    };
+    outcome.unwrap_or_else(|(status, message)| error(status, message))
+}
+
+
+#[derive(Serialize)]
+struct HistoryResponse {
+    schema_version: u32,
+    results: Vec<CheckResult>,
+    rollups: Vec<Rollup>,
+}
+
+
+fn history(state: &State, query: &[(String, String)]) -> Outcome {
+    let since = match param(query, "since") {
+        Some(value) => Some(duration_param("since", value)?.1),
+        None => None,
+    };
+    let mut contents = read(state, since)?;
+    if let Some(target) = param(query, "target") {
+        contents.results.retain(|result| result.label() == target || result.url == target);
+        contents.rollups.retain(|rollup| rollup.label() == target || rollup.url == target);
+    }
+    json(&HistoryResponse { schema_version: SCHEMA_VERSION, results: contents.results, rollups: contents.rollups })
+}
+
+
+fn statistics(state: &State, query: &[(String, String)]) -> Outcome {
+    let windows: Vec<Duration> = query
+        .iter()
+        .filter(|(key, _)| key == "window")
+        .map(|(_, value)| duration_param("window", value).map(|(window, _)| window))
+        .collect::<Result<_, _>>()?;
+    let windows = if windows.is_empty() { state.windows.clone() } else { windows };
+    json(&summarize(state, windows, None)?)
+}
+
+
+/// Summaries of the whole history (or of what is newer than `since`) and each window.
+fn summarize(state: &State, windows: Vec<Duration>, since: Option<DateTime<Utc>>) -> Result<Vec<Summary>, (StatusCode, String)> {
+    let contents = read(state, since)?;
+    let mut statistics = Statistics::new(windows);
+    for rollup in &contents.rollups {
+        statistics.record_rollup(rollup);
+    }
+    for result in &contents.results {
+        statistics.record(result);
+    }
+    Ok(statistics.summaries())
+}
+
+
+fn read(state: &State, since: Option<DateTime<Utc>>) -> Result<Contents, (StatusCode, String)> {
+    match &state.history {
+        Some(history) => history.read(since).map_err(|err| {
+            log::error!("status server: reading history in {}: {}", history.dir().display(), err);
+            (StatusCode::INTERNAL_SERVER_ERROR, format!("could not read the history: {}", err))
+        }),
+        None => Ok(Contents { results: state.board.recent(since), rollups: Vec::new() }),
+    }
+}
+
+
+fn status_page(state: &State) -> Outcome {
+    let latest = state.board.latest();
+    // Only the longest window is read, since the page shows nothing older
+    let since = state.windows.iter().max().and_then(|window| ago(*window));
+    let summaries = summarize(state, state.windows.clone(), since)?;
+    let windowed = &summaries[1..];
+
+    let up = latest.iter().filter(|result| result.is_up()).count();
+    let mut page = String::new();
+    let _ = write!(
+        page,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta http-equiv=\"refresh\" content=\"30\">\n<title>Status: {} of {} up</title>\n\
+         <style>{}</style>\n</head>\n<body>\n<h1>{} of {} targets up</h1>\n\
+         <p>Updated {} UTC</p>\n<table>\n<tr><th>Status</th><th>Target</th><th>HTTP</th>\
+         <th>Response time</th><th>Checked</th>",
+        up,
+        latest.len(),
+        STYLE,
+        up,
+        latest.len(),
+        Utc::now().format("%Y-%m-%d %H:%M:%S")
+    );
+    for summary in windowed {
+        let window = summary.window.map(|window| escape(&window_label(window))).unwrap_or_default();
+        let _ = write!(page, "<th>Uptime {}</th>", window);
+    }
+    page.push_str("<th>Details</th></tr>\n");
+
+    for result in &latest {
+        let (class, status) = match (result.is_up(), result.has_warnings()) {
+            (false, _) => ("down", "DOWN"),
+            (true, true) => ("warn", "WARNING"),
+            (true, false) => ("up", "UP"),
+        };
+        let details = match &result.error {
+            Some(err) => format!("{}: {}", err.kind(), err),
+            None => result.warnings.join("; "),
+        };
+        let _ = write!(
+            page,
+            "<tr class=\"{}\"><td>{}</td><td><a href=\"{}\">{}</a></td><td>{}</td><td>{:.1} ms</td><td>{}</td>",
+            class,
+            status,
+            escape(&result.url),
+            escape(result.label()),
+            result.status.map_or("-".to_string(), |code| code.to_string()),
+            result.response_time.as_secs_f64() * 1000.0,
+            result.timestamp.format("%Y-%m-%d %H:%M:%S")
+        );
+        for summary in windowed {
+            match summary.targets.get(result.label()) {
+                Some(stats) => { let _ = write!(page, "<td>{:.2}%</td>", stats.uptime()); }
+                None => page.push_str("<td>-</td>"),
+            }
+        }
+        let _ = writeln!(page, "<td>{}</td></tr>", escape(&details));
+    }
+    page.push_str("</table>\n<p><a href=\"/api/status\">status</a> \u{b7} <a href=\"/api/history?since=24h\">history</a> \
+                   \u{b7} <a href=\"/api/statistics\">statistics</a> \
+                   \u{b7} <a href=\"/metrics\">metrics</a></p>\n</body>\n</html>\n");
+
+    Ok(response(StatusCode::OK, "text/html; charset=utf-8", page.into_bytes()))
+}
+
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em}table{border-collapse:collapse}\
+                     th,td{padding:.3em .8em;border-bottom:1px solid #ddd;text-align:left}\
+                     tr.up td:first-child{color:#1a7f37}tr.warn td:first-child{color:#9a6700}\
+                     tr.down td:first-child{color:#cf222e;font-weight:bold}";
+
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+
+fn param<'a>(query: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    query.iter().find(|(name, _)| name == key).map(|(_, value)| value.as_str())
+}
+
+
+/// How long ago `duration` was, unless that is before the earliest time that can be represented.
+fn ago(duration: Duration) -> Option<DateTime<Utc>> {
+    Utc::now().checked_sub_signed(TimeDelta::from_std(duration).ok()?)
+}
+
+
+/// The duration given as the query parameter `key`, and how long ago that was; it can't
+/// reach back past the earliest time that can be represented.
+fn duration_param(key: &str, value: &str) -> Result<(Duration, DateTime<Utc>), (StatusCode, String)> {
+    let duration = parse_duration(value).map_err(|err| bad_request(key, err))?;
+    let start = ago(duration).ok_or_else(|| bad_request(key, format!("`{}` reaches back too far", value)))?;
+    Ok((duration, start))
+}
+
+
+fn bad_request(key: &str, err: String) -> (StatusCode, String) {
+    (StatusCode::BAD_REQUEST, format!("{}: {}", key, err))
+}
+
+
+fn json(value: &impl Serialize) -> Outcome {
+    let body = serde_json::to_vec_pretty(value)
+        .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+    Ok(response(StatusCode::OK, "application/json", body))
+}
+
+
+fn error(status: StatusCode, message: String) -> Response<Full<Bytes>> {
+    let body = serde_json::to_vec(&serde_json::json!({ "error": message })).unwrap_or_default();
+    response(status, "application/json", body)
+}
+
+
+fn response(status: StatusCode, content_type: &'static str, body: Vec<u8>) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(Bytes::from(body)));
+    *response.status_mut() = status;
+    response.headers_mut().insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+    response.headers_mut().insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    response
+}
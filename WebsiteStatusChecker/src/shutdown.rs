@@ -0,0 +1,148 @@
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use signal_hook::consts::{SIGINT, SIGTERM};
+
+use crate::check::CheckResult;
+use crate::target::Target;
+
+
+/// A shared "please stop" flag.
+///
+/// Runs that are given a `Shutdown` stop starting new checks once it is triggered, wait
+/// for the checks already running up to their grace period, and report whatever is left
+/// as aborted.
+#[derive(Debug, Clone, Default)]
+pub struct Shutdown(Arc<AtomicBool>);
+
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Shutdown::default()
+    }
+
+    /// A flag triggered by SIGINT or SIGTERM. A second signal while shutting down exits
+    /// the process immediately.
+    pub fn on_signals() -> io::Result<Self> {
+        let shutdown = Shutdown::new();
+        for signal in [SIGINT, SIGTERM] {
+            signal_hook::flag::register_conditional_shutdown(signal, 130, Arc::clone(&shutdown.0))?;
+            signal_hook::flag::register(signal, Arc::clone(&shutdown.0))?;
+        }
+        Ok(shutdown)
+    }
+
+    pub fn trigger(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+
+/// The checks that have been submitted but haven't reported back yet.
+#[derive(Debug, Default)]
+pub(crate) struct InFlight {
+    pending: HashMap<String, VecDeque<(Target, Instant)>>,
+    count: usize,
+}
+
+
+impl InFlight {
+    pub(crate) fn started(&mut self, target: &Target) {
+        self.pending.entry(target.key()).or_default().push_back((target.clone(), Instant::now()));
+        self.count += 1;
+    }
+
+    pub(crate) fn finished(&mut self, result: &CheckResult) {
+        let key = result.key();
+        if let Some(queue) = self.pending.get_mut(&key)
+            && queue.pop_front().is_some()
+        {
+            self.count -= 1;
+            if queue.is_empty() {
+                self.pending.remove(&key);
+            }
+        }
+    }
+
+    pub(crate) fn contains(&self, key: &str) -> bool {
+        self.pending.contains_key(key)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.count
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Gives up on everything still pending, returning an aborted result for each.
+    pub(crate) fn abort_all(&mut self) -> Vec<CheckResult> {
+        self.count = 0;
+        self.pending
+            .drain()
+            .flat_map(|(_, queue)| queue)
+            .map(|(target, started)| CheckResult::aborted(&target, started.elapsed()))
+            .collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use crate::error::ErrorKind;
+
+    #[test]
+    fn every_clone_sees_the_trigger() {
+        let shutdown = Shutdown::new();
+        let clone = shutdown.clone();
+        assert!(!clone.is_triggered());
+        shutdown.trigger();
+        assert!(clone.is_triggered());
+    }
+
+    #[test]
+    fn in_flight_counts_each_submission() {
+        let shop = Target::new("https://shop.example/");
+        let blog = Target::new("https://blog.example/");
+        let mut in_flight = InFlight::default();
+        in_flight.started(&shop);
+        in_flight.started(&shop);
+        in_flight.started(&blog);
+        assert_eq!(in_flight.len(), 3);
+
+        in_flight.finished(&CheckResult::aborted(&shop, Duration::ZERO));
+        in_flight.finished(&CheckResult::aborted(&blog, Duration::ZERO));
+        // A result nobody is waiting for changes nothing
+        in_flight.finished(&CheckResult::aborted(&blog, Duration::ZERO));
+        assert_eq!(in_flight.len(), 1);
+        assert!(in_flight.contains(&shop.key()));
+        assert!(!in_flight.contains(&blog.key()));
+    }
+
+    #[test]
+    fn abort_all_reports_what_is_left() {
+        let shop = Target::new("https://shop.example/").with_name("shop");
+        let mut in_flight = InFlight::default();
+        in_flight.started(&shop);
+        std::thread::sleep(Duration::from_millis(20));
+
+        let aborted = in_flight.abort_all();
+        assert!(in_flight.is_empty());
+        assert!(!in_flight.contains(&shop.key()));
+        let [result] = &aborted[..] else { panic!("{:?}", aborted) };
+        assert_eq!(result.name.as_deref(), Some("shop"));
+        assert_eq!(result.error_kind(), Some(ErrorKind::Aborted));
+        assert!(result.response_time >= Duration::from_millis(20));
+    }
+}
@@ -0,0 +1,679 @@
+//! Uptime, latency and error statistics over a run's results.
+//!
+//! [`Statistics`] collects results as they arrive and summarises them per target and
+//! overall, for the whole run and for any number of trailing windows. A window ends at
+//! the newest result rather than the current time, so summaries of an old results file
+//! still cover its last hour or day.
+//!
+//! Downsampled [`Rollup`]s from a history count towards checks, uptime and error rates.
+//! They carry no individual response times, so latency percentiles only cover
+//! full-resolution results, and a rollup counts towards an outage only if every check
+//! in it failed.
+//!
+//! Results that no window reaches any more (and that are over an hour older than the
+//! newest) are folded into running totals per target, so a long watch doesn't keep
+//! every result in memory. The whole run's counts and outages stay exact; its latency
+//! percentiles come from a histogram of the folded response times, accurate to 1%.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::time::Duration;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::Serialize;
+
+use crate::check::CheckResult;
+use crate::error::ErrorKind;
+use crate::history::Rollup;
+use crate::schema::SummaryRecord;
+
+
+/// Results this recent are kept whole even when no window reaches back that far.
+const KEEP_AT_LEAST: Duration = Duration::from_secs(3600);
+
+
+/// The ratio between the bounds of neighbouring buckets of the folded response times.
+const BUCKET_GROWTH: f64 = 1.02;
+
+
+/// Aggregates over a set of checks.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    pub checks: usize,
+    pub up: usize,
+    /// Response times of the checks that got a response; `None` if none did.
+    pub latency: Option<Latency>,
+    /// Failed checks by what broke.
+    pub errors: BTreeMap<ErrorKind, usize>,
+    /// The longest time a target stayed down, from its first failed check until the next
+    /// successful one (or its last check, if it never came back up).
+    pub longest_outage: Option<Duration>,
+}
+
+
+/// Response time distribution; percentiles use the nearest-rank method.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Latency {
+    pub min: Duration,
+    pub max: Duration,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+}
+
+
+impl Stats {
+    /// Percentage of checks that were up; 100 when there were no checks.
+    pub fn uptime(&self) -> f64 {
+        if self.checks == 0 {
+            100.0
+        } else {
+            self.up as f64 * 100.0 / self.checks as f64
+        }
+    }
+
+    /// Percentage of checks that failed with `kind`.
+    pub fn error_rate(&self, kind: ErrorKind) -> f64 {
+        let failed = self.errors.get(&kind).copied().unwrap_or(0);
+        if self.checks == 0 {
+            0.0
+        } else {
+            failed as f64 * 100.0 / self.checks as f64
+        }
+    }
+}
+
+
+/// Statistics for one window: overall and by target label.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(into = "SummaryRecord")]
+pub struct Summary {
+    /// How far back from the newest result this summary reaches; `None` for everything.
+    pub window: Option<Duration>,
+    /// Timestamps of the oldest and newest result included, if any were.
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub overall: Stats,
+    pub targets: BTreeMap<String, Stats>,
+}
+
+
+/// What is kept of each result, or of each rollup.
+#[derive(Debug, Clone)]
+struct Sample {
+    label: String,
+    timestamp: DateTime<Utc>,
+    /// The end of a rollup's period; the same as `timestamp` for a single result.
+    end: DateTime<Utc>,
+    checks: usize,
+    up: usize,
+    errors: BTreeMap<ErrorKind, usize>,
+    /// `None` when no response was received, and always for rollups.
+    response_time: Option<Duration>,
+}
+
+
+impl Sample {
+    fn is_down(&self) -> bool {
+        self.up == 0
+    }
+}
+
+
+/// One target's samples that no window reaches any more, summed up in time order.
+#[derive(Debug, Clone, Default)]
+struct Folded {
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    checks: usize,
+    up: usize,
+    errors: BTreeMap<ErrorKind, usize>,
+    /// Response times by histogram bucket; see [`bucket`].
+    buckets: BTreeMap<i32, usize>,
+    responses: usize,
+    fastest: Duration,
+    slowest: Duration,
+    total_response_time: Duration,
+    outage: Outage,
+}
+
+
+impl Folded {
+    fn add(&mut self, sample: &Sample) {
+        self.from = Some(self.from.map_or(sample.timestamp, |from| from.min(sample.timestamp)));
+        self.to = self.to.max(Some(sample.end));
+        self.checks += sample.checks;
+        self.up += sample.up;
+        for (kind, count) in &sample.errors {
+            *self.errors.entry(*kind).or_insert(0) += count;
+        }
+        if let Some(time) = sample.response_time {
+            *self.buckets.entry(bucket(time)).or_insert(0) += 1;
+            self.fastest = if self.responses == 0 { time } else { self.fastest.min(time) };
+            self.slowest = self.slowest.max(time);
+            self.total_response_time += time;
+            self.responses += 1;
+        }
+        self.outage.step(sample);
+    }
+}
+
+
+/// The longest outage so far, and the one still going on, of samples taken in time order.
+#[derive(Debug, Clone, Copy, Default)]
+struct Outage {
+    longest: Option<TimeDelta>,
+    down_since: Option<DateTime<Utc>>,
+}
+
+
+impl Outage {
+    fn step(&mut self, sample: &Sample) {
+        match (sample.is_down(), self.down_since) {
+            (true, None) => self.down_since = Some(sample.timestamp),
+            (false, Some(since)) => {
+                self.longest = self.longest.max(Some(sample.timestamp - since));
+                self.down_since = None;
+            }
+            _ => {}
+        }
+    }
+}
+
+
+/// Collects results and summarises them.
+///
+/// Checks that were aborted at shutdown are left out, since they say nothing about the target.
+#[derive(Debug, Clone, Default)]
+pub struct Statistics {
+    windows: Vec<Duration>,
+    /// Samples some window may still reach, sorted by timestamp.
+    samples: VecDeque<Sample>,
+    /// Older samples, by target label.
+    folded: BTreeMap<String, Folded>,
+    /// When the newest sample ended.
+    newest: Option<DateTime<Utc>>,
+}
+
+
+impl Statistics {
+    /// Summarises the whole run, plus the trailing `windows` (such as the last hour).
+    pub fn new(windows: Vec<Duration>) -> Self {
+        Statistics { windows, ..Statistics::default() }
+    }
+
+    pub fn record(&mut self, result: &CheckResult) {
+        if result.error_kind() == Some(ErrorKind::Aborted) {
+            return;
+        }
+        self.push(Sample {
+            label: result.label().to_string(),
+            timestamp: result.timestamp,
+            end: result.timestamp,
+            checks: 1,
+            up: usize::from(result.error_kind().is_none()),
+            errors: result.error_kind().map(|kind| (kind, 1)).into_iter().collect(),
+            response_time: result.status.map(|_| result.response_time),
+        });
+    }
+
+    /// Counts a downsampled period read from a history; see the [module docs](self).
+    pub fn record_rollup(&mut self, rollup: &Rollup) {
+        let mut errors = rollup.errors.clone();
+        errors.remove(&ErrorKind::Aborted);
+        let checks = rollup.checks - rollup.errors.get(&ErrorKind::Aborted).copied().unwrap_or(0);
+        if checks == 0 {
+            return;
+        }
+        self.push(Sample {
+            label: rollup.label().to_string(),
+            timestamp: rollup.start,
+            end: rollup.end(),
+            checks,
+            up: rollup.up,
+            errors,
+            response_time: None,
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty() && self.folded.is_empty()
+    }
+
+    fn push(&mut self, sample: Sample) {
+        self.newest = self.newest.max(Some(sample.end));
+        let at = self.samples.partition_point(|kept| kept.timestamp <= sample.timestamp);
+        self.samples.insert(at, sample);
+
+        let keep = self.windows.iter().max().copied().unwrap_or_default().max(KEEP_AT_LEAST);
+        let Some(cutoff) = self.start(keep) else { return };
+        while let Some(oldest) = self.samples.pop_front() {
+            if oldest.end > cutoff {
+                self.samples.push_front(oldest);
+                break;
+            }
+            self.folded.entry(oldest.label.clone()).or_default().add(&oldest);
+        }
+    }
+
+    /// Where `window` starts, measured back from the newest sample; `None` if there are no
+    /// samples or the window reaches back past the earliest time that can be represented.
+    fn start(&self, window: Duration) -> Option<DateTime<Utc>> {
+        let window = TimeDelta::from_std(window).ok()?;
+        self.newest?.checked_sub_signed(window)
+    }
+
+    /// A summary of the whole run followed by one per window, shortest first.
+    pub fn summaries(&self) -> Vec<Summary> {
+        let mut windows = self.windows.clone();
+        windows.sort();
+        windows.dedup();
+
+        let mut summaries = vec![self.summarize(None)];
+        summaries.extend(windows.into_iter().map(|window| self.summarize(Some(window))));
+        summaries
+    }
+
+    fn summarize(&self, window: Option<Duration>) -> Summary {
+        // Folded samples are older than any window, but a window too long to start
+        // anywhere covers them too
+        let start = window.and_then(|window| self.start(window));
+        let folded: Vec<(&str, &Folded)> = match start {
+            Some(_) => Vec::new(),
+            None => self.folded.iter().map(|(label, folded)| (label.as_str(), folded)).collect(),
+        };
+        let samples: Vec<&Sample> = self
+            .samples
+            .iter()
+            .filter(|sample| start.is_none_or(|start| sample.end > start))
+            .collect();
+
+        let mut by_target: BTreeMap<&str, (Option<&Folded>, Vec<&Sample>)> = BTreeMap::new();
+        for (label, folded) in &folded {
+            by_target.entry(label).or_default().0 = Some(folded);
+        }
+        for sample in &samples {
+            by_target.entry(sample.label.as_str()).or_default().1.push(sample);
+        }
+
+        let targets: BTreeMap<String, Stats> = by_target
+            .into_iter()
+            .map(|(label, (folded, samples))| {
+                let mut stats = aggregate(folded.as_slice(), &samples);
+                stats.longest_outage = longest_outage(folded, &samples);
+                (label.to_string(), stats)
+            })
+            .collect();
+
+        let folded: Vec<&Folded> = folded.into_iter().map(|(_, folded)| folded).collect();
+        let mut overall = aggregate(&folded, &samples);
+        overall.longest_outage = targets.values().filter_map(|stats| stats.longest_outage).max();
+
+        let from = folded.iter().filter_map(|folded| folded.from).chain(samples.first().map(|sample| sample.timestamp));
+        let to = folded.iter().filter_map(|folded| folded.to).chain(samples.iter().map(|sample| sample.end));
+        Summary { window, from: from.min(), to: to.max(), overall, targets }
+    }
+}
+
+
+/// Renders one summary as a table with a row per target and an `all` row.
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.window {
+            Some(window) => write!(f, "Last {}", window_label(window))?,
+            None => f.write_str("Whole run")?,
+        }
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            write!(f, " ({} to {} UTC)", from.format("%Y-%m-%d %H:%M:%S"), to.format("%Y-%m-%d %H:%M:%S"))?;
+        }
+        writeln!(f)?;
+
+        let width = self.targets.keys().map(|label| label.chars().count()).max().unwrap_or(0).clamp(6, 40);
+        writeln!(
+            f,
+            "{:<width$} {:>6} {:>7} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9}  errors",
+            "target", "checks", "uptime", "p50", "p90", "p99", "min", "max", "outage",
+            width = width
+        )?;
+        for (label, stats) in &self.targets {
+            write_row(f, label, stats, width)?;
+        }
+        if self.targets.len() > 1 {
+            write_row(f, "all", &self.overall, width)?;
+        }
+        Ok(())
+    }
+}
+
+
+fn write_row(f: &mut fmt::Formatter<'_>, label: &str, stats: &Stats, width: usize) -> fmt::Result {
+    let latency = |pick: fn(&Latency) -> Duration| stats.latency.as_ref().map_or("-".to_string(), |latency| short(pick(latency)));
+    let errors: Vec<String> = stats
+        .errors
+        .keys()
+        .map(|kind| format!("{} {:.1}%", kind, stats.error_rate(*kind)))
+        .collect();
+    let label: String = label.chars().take(width).collect();
+
+    writeln!(
+        f,
+        "{:<width$} {:>6} {:>6.1}% {:>9} {:>9} {:>9} {:>9} {:>9} {:>9}  {}",
+        label,
+        stats.checks,
+        stats.uptime(),
+        latency(|latency| latency.p50),
+        latency(|latency| latency.p90),
+        latency(|latency| latency.p99),
+        latency(|latency| latency.min),
+        latency(|latency| latency.max),
+        stats.longest_outage.map_or("-".to_string(), short),
+        if errors.is_empty() { "-".to_string() } else { errors.join(", ") },
+        width = width
+    )
+}
+
+
+fn aggregate(folded: &[&Folded], samples: &[&Sample]) -> Stats {
+    let mut errors = BTreeMap::new();
+    let folded_errors = folded.iter().flat_map(|folded| &folded.errors);
+    for (kind, count) in folded_errors.chain(samples.iter().flat_map(|sample| &sample.errors)) {
+        *errors.entry(*kind).or_insert(0) += count;
+    }
+
+    // Each response time with how often it occurred, a folded bucket standing for all of its times
+    let mut times: Vec<(Duration, usize)> =
+        samples.iter().filter_map(|sample| sample.response_time).map(|time| (time, 1)).collect();
+    let mut total: Duration = times.iter().map(|(time, _)| *time).sum();
+    for folded in folded.iter().filter(|folded| folded.responses > 0) {
+        let buckets = folded.buckets.iter().map(|(bucket, count)| (bucket_time(*bucket), *count));
+        times.extend(buckets.map(|(time, count)| (time.clamp(folded.fastest, folded.slowest), count)));
+        total += folded.total_response_time;
+    }
+    times.sort();
+    let count: usize = times.iter().map(|(_, count)| count).sum();
+    let fastest = folded.iter().filter(|folded| folded.responses > 0).map(|folded| folded.fastest);
+    let slowest = folded.iter().map(|folded| folded.slowest);
+    let latency = (!times.is_empty()).then(|| Latency {
+        min: fastest.chain([times[0].0]).min().unwrap_or_default(),
+        max: slowest.chain([times[times.len() - 1].0]).max().unwrap_or_default(),
+        mean: total / count as u32,
+        p50: percentile(&times, count, 50.0),
+        p90: percentile(&times, count, 90.0),
+        p99: percentile(&times, count, 99.0),
+    });
+
+    let folded_checks: usize = folded.iter().map(|folded| folded.checks).sum();
+    let folded_up: usize = folded.iter().map(|folded| folded.up).sum();
+    Stats {
+        checks: folded_checks + samples.iter().map(|sample| sample.checks).sum::<usize>(),
+        up: folded_up + samples.iter().map(|sample| sample.up).sum::<usize>(),
+        latency,
+        errors,
+        longest_outage: None,
+    }
+}
+
+
+/// The nearest-rank percentile of non-empty `times`, sorted and counted `count` times in all.
+fn percentile(times: &[(Duration, usize)], count: usize, percent: f64) -> Duration {
+    let rank = ((percent / 100.0 * count as f64).ceil() as usize).clamp(1, count);
+    let mut seen = 0;
+    for (time, times_seen) in times {
+        seen += times_seen;
+        if seen >= rank {
+            return *time;
+        }
+    }
+    times[times.len() - 1].0
+}
+
+
+/// The histogram bucket of a folded response time.
+fn bucket(time: Duration) -> i32 {
+    let micros = (time.as_secs_f64() * 1e6).max(1.0);
+    (micros.ln() / BUCKET_GROWTH.ln()).floor() as i32
+}
+
+
+/// The response time a bucket stands for: the geometric middle of its bounds, which is
+/// within 1% of every time in it.
+fn bucket_time(bucket: i32) -> Duration {
+    let micros = BUCKET_GROWTH.powf(f64::from(bucket) + 0.5);
+    Duration::try_from_secs_f64(micros / 1e6).unwrap_or(Duration::MAX)
+}
+
+
+/// The longest run of failures of one target: in its folded samples, if any, continued
+/// through its later samples, sorted by time.
+fn longest_outage(folded: Option<&Folded>, samples: &[&Sample]) -> Option<Duration> {
+    let mut outage = folded.map(|folded| folded.outage).unwrap_or_default();
+    for sample in samples {
+        outage.step(sample);
+    }
+    let last = samples.last().map(|sample| sample.end).or(folded.and_then(|folded| folded.to));
+    let mut longest = outage.longest;
+    if let (Some(since), Some(last)) = (outage.down_since, last) {
+        longest = longest.max(Some(last - since));
+    }
+    longest.map(|outage| outage.to_std().unwrap_or_default())
+}
+
+
+/// `482.9ms`, `12.5s`, `4.2m` or `3.1h`.
+pub(crate) fn short(duration: Duration) -> String {
+    // The thresholds sit just below each unit boundary so rounding never prints `1000.0ms`
+    let seconds = duration.as_secs_f64();
+    if seconds < 0.99995 {
+        format!("{:.1}ms", seconds * 1000.0)
+    } else if seconds < 119.95 {
+        format!("{:.1}s", seconds)
+    } else if seconds < 7197.0 {
+        format!("{:.1}m", seconds / 60.0)
+    } else {
+        format!("{:.1}h", seconds / 3600.0)
+    }
+}
+
+
+/// The largest whole unit the window is written in, e.g. `7d`, `90m` or `45s`.
+pub(crate) fn window_label(window: Duration) -> String {
+    let seconds = window.as_secs();
+    for (unit, size) in [("d", 86_400), ("h", 3600), ("m", 60)] {
+        if seconds >= size && seconds.is_multiple_of(size) {
+            return format!("{}{}", seconds / size, unit);
+        }
+    }
+    format!("{:?}", window)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::CheckError;
+    use crate::target::Target;
+
+    /// A check of `label` finished `minute` minutes into the run: up with a response
+    /// time in milliseconds, or down with no response.
+    fn result(label: &str, minute: i64, outcome: Result<u64, ErrorKind>) -> CheckResult {
+        let mut result = CheckResult::aborted(&Target::new(format!("https://{}.example/", label)), Duration::ZERO);
+        result.name = Some(label.to_string());
+        result.timestamp = DateTime::from_timestamp(1_700_000_000 + minute * 60, 0).unwrap();
+        match outcome {
+            Ok(millis) => {
+                result.error = None;
+                result.status = Some(200);
+                result.response_time = Duration::from_millis(millis);
+            }
+            Err(kind) => result.error = Some(CheckError::new(kind, "failed")),
+        }
+        result
+    }
+
+    fn statistics(windows: Vec<Duration>, results: &[CheckResult]) -> Statistics {
+        let mut statistics = Statistics::new(windows);
+        for result in results {
+            statistics.record(result);
+        }
+        statistics
+    }
+
+    fn minutes(minutes: u64) -> Duration {
+        Duration::from_secs(minutes * 60)
+    }
+
+    #[test]
+    fn percentiles_use_the_nearest_rank() {
+        let results: Vec<CheckResult> = (1..=100).rev().map(|millis| result("web", 0, Ok(millis))).collect();
+        let latency = statistics(Vec::new(), &results).summaries()[0].overall.latency.unwrap();
+
+        let millis = Duration::from_millis;
+        assert_eq!((latency.min, latency.max), (millis(1), millis(100)));
+        assert_eq!(latency.mean, Duration::from_micros(50_500));
+        assert_eq!((latency.p50, latency.p90, latency.p99), (millis(50), millis(90), millis(99)));
+
+        let single = statistics(Vec::new(), &[result("web", 0, Ok(7))]).summaries()[0].overall.latency.unwrap();
+        assert_eq!((single.min, single.p50, single.p99, single.max), (millis(7), millis(7), millis(7), millis(7)));
+    }
+
+    #[test]
+    fn uptime_and_error_rates_are_percentages_of_checks() {
+        assert_eq!(Stats::default().uptime(), 100.0);
+        assert_eq!(Stats::default().error_rate(ErrorKind::Timeout), 0.0);
+
+        let results = [
+            result("web", 0, Ok(10)),
+            result("web", 1, Err(ErrorKind::Timeout)),
+            result("web", 2, Ok(10)),
+            result("web", 3, Err(ErrorKind::Aborted)),
+            result("web", 4, Err(ErrorKind::Dns)),
+            result("web", 5, Ok(10)),
+        ];
+        let stats = &statistics(Vec::new(), &results).summaries()[0].overall;
+        // The aborted check says nothing about the target
+        assert_eq!((stats.checks, stats.up), (5, 3));
+        assert_eq!(stats.uptime(), 60.0);
+        assert_eq!(stats.error_rate(ErrorKind::Timeout), 20.0);
+        assert_eq!(stats.error_rate(ErrorKind::Tls), 0.0);
+        assert_eq!(stats.errors, BTreeMap::from([(ErrorKind::Dns, 1), (ErrorKind::Timeout, 1)]));
+    }
+
+    #[test]
+    fn outages_last_until_the_next_success_or_the_last_check() {
+        let down = || Err(ErrorKind::Connect);
+        let results = [
+            result("web", 0, Ok(10)),
+            result("web", 1, down()),
+            result("web", 2, down()),
+            result("web", 4, Ok(10)),
+            result("api", 5, down()),
+            result("web", 6, down()),
+            // Out of order, as results arrive from several workers
+            result("api", 3, Ok(10)),
+            result("api", 9, down()),
+        ];
+        let summary = &statistics(Vec::new(), &results).summaries()[0];
+        assert_eq!(summary.targets["web"].longest_outage, Some(minutes(3)));
+        assert_eq!(summary.targets["api"].longest_outage, Some(minutes(4)));
+        assert_eq!(summary.overall.longest_outage, Some(minutes(4)));
+
+        let up = statistics(Vec::new(), &[result("web", 0, Ok(10))]);
+        assert_eq!(up.summaries()[0].overall.longest_outage, None);
+    }
+
+    #[test]
+    fn windows_end_at_the_newest_result() {
+        let results: Vec<CheckResult> = (0..=10).map(|minute| result("web", minute, Ok(10))).collect();
+        let summaries = statistics(vec![minutes(5), minutes(2), minutes(5)], &results).summaries();
+
+        let windows: Vec<Option<Duration>> = summaries.iter().map(|summary| summary.window).collect();
+        assert_eq!(windows, [None, Some(minutes(2)), Some(minutes(5))]);
+        assert_eq!(summaries[0].overall.checks, 11);
+        assert_eq!(summaries[1].overall.checks, 2);
+        assert_eq!(summaries[2].overall.checks, 5);
+        assert_eq!(summaries[2].from, Some(results[6].timestamp));
+        assert_eq!(summaries[2].to, Some(results[10].timestamp));
+    }
+
+    #[test]
+    fn windows_reaching_past_representable_times_cover_everything() {
+        let results: Vec<CheckResult> = (0..3).map(|minute| result("web", minute, Ok(10))).collect();
+        // Too long for a TimeDelta, and too long to subtract from the newest result
+        let summaries = statistics(vec![Duration::MAX, Duration::from_secs(1 << 50)], &results).summaries();
+        assert!(summaries.iter().all(|summary| summary.overall.checks == 3));
+    }
+
+    #[test]
+    fn old_results_are_folded_into_the_whole_run() {
+        // Three hours, one check a minute, down from 1:50 to 2:10
+        let results: Vec<CheckResult> = (0..180)
+            .map(|minute| match minute {
+                110..130 => result("web", minute, Err(ErrorKind::Timeout)),
+                _ => result("web", minute, Ok(minute as u64 * 7 % 500 + 1)),
+            })
+            .collect();
+        let statistics = statistics(vec![minutes(10)], &results);
+        assert!(statistics.samples.len() <= 61, "{} samples kept", statistics.samples.len());
+
+        let summaries = statistics.summaries();
+        let whole = &summaries[0].overall;
+        assert_eq!((whole.checks, whole.up), (180, 160));
+        assert_eq!(whole.errors, BTreeMap::from([(ErrorKind::Timeout, 20)]));
+        assert_eq!(whole.longest_outage, Some(minutes(20)));
+        assert_eq!((summaries[0].from, summaries[0].to), (Some(results[0].timestamp), Some(results[179].timestamp)));
+        assert_eq!(summaries[1].overall.checks, 10);
+
+        let mut times: Vec<(Duration, usize)> =
+            results.iter().filter(|result| result.is_up()).map(|result| (result.response_time, 1)).collect();
+        times.sort();
+        let latency = whole.latency.unwrap();
+        assert_eq!((latency.min, latency.max), (times[0].0, times[159].0));
+        assert_eq!(latency.mean, times.iter().map(|(time, _)| *time).sum::<Duration>() / 160);
+        for (percent, approximate) in [(50.0, latency.p50), (90.0, latency.p90), (99.0, latency.p99)] {
+            let exact = percentile(&times, 160, percent);
+            let error = (approximate.as_secs_f64() / exact.as_secs_f64() - 1.0).abs();
+            assert!(error < 0.01, "p{}: {:?} for {:?}", percent, approximate, exact);
+        }
+    }
+
+    #[test]
+    fn rollups_count_checks_but_not_latency() {
+        let rollup = Rollup {
+            url: "https://web.example/".to_string(),
+            name: Some("web".to_string()),
+            start: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+            period: minutes(60),
+            checks: 10,
+            up: 7,
+            errors: BTreeMap::from([(ErrorKind::Timeout, 2), (ErrorKind::Aborted, 1)]),
+            responses: 7,
+            mean_response_time: Some(Duration::from_millis(20)),
+            min_response_time: Some(Duration::from_millis(10)),
+            max_response_time: Some(Duration::from_millis(30)),
+        };
+        let mut statistics = Statistics::default();
+        statistics.record_rollup(&rollup);
+        statistics.record(&result("web", 61, Ok(10)));
+
+        let summary = &statistics.summaries()[0];
+        assert_eq!((summary.overall.checks, summary.overall.up), (10, 8));
+        assert_eq!(summary.overall.errors, BTreeMap::from([(ErrorKind::Timeout, 2)]));
+        assert_eq!(summary.overall.latency.unwrap().max, Duration::from_millis(10));
+        assert_eq!(summary.to, Some(rollup.start + TimeDelta::minutes(61)));
+    }
+
+    #[test]
+    fn durations_print_in_their_largest_unit() {
+        assert_eq!(short(Duration::from_micros(482_940)), "482.9ms");
+        assert_eq!(short(Duration::from_micros(999_990)), "1.0s");
+        assert_eq!(short(Duration::from_secs(90)), "90.0s");
+        assert_eq!(short(Duration::from_secs(250)), "4.2m");
+        assert_eq!(short(Duration::from_secs(11_160)), "3.1h");
+
+        assert_eq!(window_label(Duration::from_secs(7 * 86_400)), "7d");
+        assert_eq!(window_label(minutes(90)), "90m");
+        assert_eq!(window_label(Duration::from_secs(45)), "45s");
+        assert_eq!(window_label(Duration::from_millis(1500)), "1.5s");
+    }
+}
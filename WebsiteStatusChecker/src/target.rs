@@ -0,0 +1,200 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use http::Method;
+
+use crate::auth::{Auth, ClientCert};
+use crate::expect::{BodyAssertions, HeaderAssertions, JsonAssertion, RedirectAssertions, StatusSet};
+use crate::redirect::RedirectPolicy;
+use crate::retry::RetryPolicy;
+
+
+/// Request timeout used when neither the config nor the target sets one.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Pause between checks of a target in watch mode when nothing else is configured.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How close to expiry a certificate has to be before the check warns about it.
+pub const DEFAULT_CERT_EXPIRY_WARNING: Duration = Duration::from_secs(14 * 24 * 3600);
+
+
+/// One endpoint to probe and how to probe it.
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub url: String,
+    /// Label used in output instead of the URL.
+    pub name: Option<String>,
+    pub method: Method,
+    pub timeout: Duration,
+    /// When failed requests are tried again.
+    pub retry: RetryPolicy,
+    /// Status codes counted as up.
+    pub expected_status: StatusSet,
+    /// Whether redirects are followed, and how many.
+    pub redirects: RedirectPolicy,
+    /// Checks on where the redirects end up.
+    pub redirect_assertions: RedirectAssertions,
+    /// Checks on the response headers.
+    pub header_assertions: HeaderAssertions,
+    /// Response headers copied into the result, by name.
+    pub capture_headers: Vec<String>,
+    /// Checks on the response body, and how much of it to read.
+    pub body_assertions: BodyAssertions,
+    /// Checks on the response body parsed as JSON.
+    pub json_assertions: Vec<JsonAssertion>,
+    /// A certificate expiring within this long makes the result a warning.
+    pub cert_expiry_warning: Duration,
+    pub headers: BTreeMap<String, String>,
+    /// Query parameters added to the URL.
+    pub query: BTreeMap<String, String>,
+    /// Credentials sent in the `Authorization` header.
+    pub auth: Option<Auth>,
+    /// Certificate offered when the server asks for one (mutual TLS).
+    pub client_cert: Option<ClientCert>,
+    /// Sent as the request body; dropped when a redirect turns the request into a `GET`.
+    pub body: Option<Vec<u8>>,
+    pub tags: Vec<String>,
+    /// Pause between checks in watch mode.
+    pub interval: Duration,
+}
+
+
+impl Target {
+    /// A `GET` target with the built-in defaults.
+    pub fn new(url: impl Into<String>) -> Self {
+        Target {
+            url: url.into(),
+            name: None,
+            method: Method::GET,
+            timeout: DEFAULT_TIMEOUT,
+            retry: RetryPolicy::default(),
+            expected_status: StatusSet::default(),
+            redirects: RedirectPolicy::default(),
+            redirect_assertions: RedirectAssertions::default(),
+            header_assertions: HeaderAssertions::default(),
+            capture_headers: Vec::new(),
+            body_assertions: BodyAssertions::default(),
+            json_assertions: Vec::new(),
+            cert_expiry_warning: DEFAULT_CERT_EXPIRY_WARNING,
+            headers: BTreeMap::new(),
+            query: BTreeMap::new(),
+            auth: None,
+            client_cert: None,
+            body: None,
+            tags: Vec::new(),
+            interval: DEFAULT_INTERVAL,
+        }
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn with_method(mut self, method: Method) -> Self {
+        self.method = method;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets how many times a failed request is retried, keeping the rest of the policy.
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retry.max_retries = retries;
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_expected_status(mut self, expected: StatusSet) -> Self {
+        self.expected_status = expected;
+        self
+    }
+
+    pub fn with_redirects(mut self, policy: RedirectPolicy) -> Self {
+        self.redirects = policy;
+        self
+    }
+
+    pub fn with_redirect_assertions(mut self, assertions: RedirectAssertions) -> Self {
+        self.redirect_assertions = assertions;
+        self
+    }
+
+    pub fn with_header_assertions(mut self, assertions: HeaderAssertions) -> Self {
+        self.header_assertions = assertions;
+        self
+    }
+
+    pub fn with_captured_header(mut self, name: impl Into<String>) -> Self {
+        self.capture_headers.push(name.into());
+        self
+    }
+
+    pub fn with_body_assertions(mut self, assertions: BodyAssertions) -> Self {
+        self.body_assertions = assertions;
+        self
+    }
+
+    pub fn with_json_assertion(mut self, assertion: JsonAssertion) -> Self {
+        self.json_assertions.push(assertion);
+        self
+    }
+
+    pub fn with_cert_expiry_warning(mut self, threshold: Duration) -> Self {
+        self.cert_expiry_warning = threshold;
+        self
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn with_query(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.query.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn with_auth(mut self, auth: Auth) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    pub fn with_client_cert(mut self, cert: ClientCert) -> Self {
+        self.client_cert = Some(cert);
+        self
+    }
+
+    pub fn with_body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = Some(body.into());
+        self
+    }
+
+    pub fn with_tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// The name if one is set, otherwise the URL.
+    pub fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.url)
+    }
+
+    /// Identifies the target across reloads and matches it to its results.
+    pub(crate) fn key(&self) -> String {
+        format!("{}\n{}", self.label(), self.url)
+    }
+}
@@ -0,0 +1,98 @@
+use std::sync::mpsc::Sender;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use tokio::runtime::Runtime;
+use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
+
+use crate::check::{check_website, CheckResult};
+use crate::client::HttpClient;
+use crate::target::Target;
+
+
+/// Runs every check as a task on a shared tokio runtime.
+///
+/// Tasks are cheap, so all submitted targets are spawned immediately; a semaphore keeps at
+/// most `concurrency` requests in flight at once.
+pub(crate) struct TaskPool {
+    runtime: Arc<Runtime>,
+    client: HttpClient,
+    permits: Arc<Semaphore>,
+    cancelled: Arc<AtomicBool>,
+    results: Mutex<Option<Sender<CheckResult>>>,
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+}
+
+
+impl TaskPool {
+    pub(crate) fn new(
+        runtime: Arc<Runtime>,
+        client: HttpClient,
+        concurrency: usize,
+        results: Sender<CheckResult>,
+    ) -> Self {
+        TaskPool {
+            runtime,
+            client,
+            permits: Arc::new(Semaphore::new(concurrency.max(1))),
+            cancelled: Arc::new(AtomicBool::new(false)),
+            results: Mutex::new(Some(results)),
+            tasks: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Spawns a check for `target`; ignored once the pool is closed.
+    pub(crate) fn submit(&self, target: Target) {
+        let Some(results) = self.results.lock().unwrap().clone() else { return };
+        let client = self.client.clone();
+        let permits = Arc::clone(&self.permits);
+        let cancelled = Arc::clone(&self.cancelled);
+
+        let task = self.runtime.spawn(async move {
+            let Ok(_permit) = permits.acquire_owned().await else { return };
+            if cancelled.load(Ordering::SeqCst) {
+                return;
+            }
+            let status = check_website(&client, &target).await;
+            log::debug!("sending result for {}", target.url);
+            let _ = results.send(status);
+        });
+
+        let mut tasks = self.tasks.lock().unwrap();
+        tasks.retain(|task| !task.is_finished());
+        tasks.push(task);
+    }
+
+    /// Stops accepting work; the results channel closes once the running checks finish.
+    pub(crate) fn close(&self) {
+        self.results.lock().unwrap().take();
+    }
+
+    /// Drops checks still waiting for a permit; running checks carry on.
+    pub(crate) fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.close();
+    }
+
+    /// Cancels every check, including the ones with a request in flight.
+    pub(crate) fn abort(self) {
+        self.cancel();
+        for task in self.tasks.lock().unwrap().drain(..) {
+            task.abort();
+        }
+    }
+
+    /// Waits for every submitted check to finish.
+    pub(crate) fn join(self) {
+        self.close();
+        let tasks = std::mem::take(&mut *self.tasks.lock().unwrap());
+        self.runtime.block_on(async {
+            for task in tasks {
+                if task.await.is_err() {
+                    log::error!("a check task panicked");
+                }
+            }
+        });
+    }
+}
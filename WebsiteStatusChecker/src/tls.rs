@@ -0,0 +1,256 @@
+use std::fmt;
+use std::io;
+use std::net::IpAddr;
+use std::pin::Pin;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use openssl::asn1::{Asn1Time, Asn1TimeRef};
+use openssl::error::ErrorStack;
+use openssl::ssl::{SslConnector, SslConnectorBuilder, SslMethod, SslRef};
+use openssl::x509::{X509NameRef, X509Ref, X509VerifyResult};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_openssl::SslStream;
+
+
+/// The protocols offered in the TLS handshake, in the wire format of ALPN.
+const ALPN: &[u8] = b"\x02h2\x08http/1.1";
+
+
+/// The parts of a server's certificate that decide whether it can be trusted much longer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Certificate {
+    /// The distinguished name, e.g. `CN=www.example.com, O=Example Inc`.
+    pub subject: String,
+    pub issuer: String,
+    /// The DNS names and IP addresses the certificate covers.
+    pub sans: Vec<String>,
+    pub not_after: DateTime<Utc>,
+    /// The rest of the chain the server sent after its own certificate, in that order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chain: Vec<Certificate>,
+}
+
+
+impl Certificate {
+    /// Reads the server's certificate and the chain sent with it from a finished handshake.
+    fn from_peer(ssl: &SslRef) -> Result<Option<Self>, String> {
+        let Some(leaf) = ssl.peer_certificate() else { return Ok(None) };
+        let mut certificate = Certificate::from_x509(&leaf)?;
+        // A client's peer chain starts with the server's own certificate
+        if let Some(chain) = ssl.peer_cert_chain() {
+            certificate.chain = chain.iter().skip(1).map(Certificate::from_x509).collect::<Result<_, _>>()?;
+        }
+        Ok(Some(certificate))
+    }
+
+    fn from_x509(cert: &X509Ref) -> Result<Self, String> {
+        let sans = cert
+            .subject_alt_names()
+            .map(|names| {
+                names
+                    .iter()
+                    .filter_map(|name| match (name.dnsname(), name.ipaddress()) {
+                        (Some(dns), _) => Some(dns.to_string()),
+                        (None, Some(ip)) => ip_address(ip).map(|ip| ip.to_string()),
+                        (None, None) => None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(Certificate {
+            subject: distinguished_name(cert.subject_name()),
+            issuer: distinguished_name(cert.issuer_name()),
+            sans,
+            not_after: to_chrono(cert.not_after())
+                .map_err(|err| format!("{}: invalid expiry date: {}", distinguished_name(cert.subject_name()), err))?,
+            chain: Vec::new(),
+        })
+    }
+
+    /// The certificate in the chain that expires first, which may be this one.
+    pub fn expires_first(&self) -> &Certificate {
+        self.chain.iter().fold(self, |first, cert| if cert.not_after < first.not_after { cert } else { first })
+    }
+
+    /// Time left until the certificate expires; negative once it has.
+    pub fn expires_in(&self) -> TimeDelta {
+        self.not_after - Utc::now()
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_in() <= TimeDelta::zero()
+    }
+
+    /// Whether one of the SANs covers `host`, allowing a leading `*` for one label.
+    pub fn covers(&self, host: &str) -> bool {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        self.sans.iter().any(|san| {
+            let san = san.to_ascii_lowercase();
+            match san.strip_prefix("*.") {
+                Some(parent) => host.split_once('.').is_some_and(|(_, rest)| rest == parent),
+                None => san == host,
+            }
+        })
+    }
+}
+
+
+impl fmt::Display for Certificate {
+    /// E.g. `CN=example.com, issued by CN=R3, O=Let's Encrypt, expires 2025-08-01`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}, issued by {}, expires {}", self.subject, self.issuer, self.not_after.format("%Y-%m-%d"))
+    }
+}
+
+
+/// A connector that trusts the system's root certificates, as found by openssl-probe
+/// (which honours `SSL_CERT_FILE` and `SSL_CERT_DIR`). Checks and email share it.
+pub(crate) fn connector() -> Result<SslConnectorBuilder, ErrorStack> {
+    let mut builder = SslConnector::builder(SslMethod::tls_client())?;
+    let probe = openssl_probe::probe();
+    // Loaded one at a time, so a broken file doesn't keep the directory from loading
+    if let Some(file) = &probe.cert_file
+        && let Err(err) = builder.load_verify_locations(Some(file), None)
+    {
+        log::debug!("could not load {}: {}", file.display(), err);
+    }
+    if let Some(dir) = &probe.cert_dir
+        && let Err(err) = builder.load_verify_locations(None, Some(dir))
+    {
+        log::debug!("could not load {}: {}", dir.display(), err);
+    }
+    Ok(builder)
+}
+
+
+/// Why a certificate that failed the handshake was rejected, as far as can be told from
+/// the certificate itself.
+pub(crate) fn rejection(cert: &Certificate, host: &str) -> String {
+    if cert.is_expired() {
+        format!(
+            "certificate for {} expired {} days ago, on {}",
+            host,
+            -cert.expires_in().num_days(),
+            cert.not_after.format("%Y-%m-%d")
+        )
+    } else if !cert.covers(host) {
+        format!("certificate is for {}, not {}", cert.sans.join(", "), host)
+    } else {
+        format!("certificate for {} issued by {} is not trusted", host, cert.issuer)
+    }
+}
+
+
+fn distinguished_name(name: &X509NameRef) -> String {
+    let parts: Vec<String> = name
+        .entries()
+        .map(|entry| {
+            let key = entry.object().nid().short_name().unwrap_or("?");
+            let value = entry.data().to_string().unwrap_or_default();
+            format!("{}={}", key, value)
+        })
+        .collect();
+    parts.join(", ")
+}
+
+
+fn ip_address(bytes: &[u8]) -> Option<IpAddr> {
+    match bytes.len() {
+        4 => Some(IpAddr::from(<[u8; 4]>::try_from(bytes).ok()?)),
+        16 => Some(IpAddr::from(<[u8; 16]>::try_from(bytes).ok()?)),
+        _ => None,
+    }
+}
+
+
+fn to_chrono(time: &Asn1TimeRef) -> Result<DateTime<Utc>, String> {
+    let diff = Asn1Time::from_unix(0).and_then(|epoch| epoch.diff(time)).map_err(|err| err.to_string())?;
+    let seconds = i64::from(diff.days) * 86_400 + i64::from(diff.secs);
+    DateTime::from_timestamp(seconds, 0).ok_or_else(|| format!("{} is out of range", time))
+}
+
+
+/// Runs the TLS handshake with `domain` over `stream`, verifying its certificate as far as
+/// `connector` is set up to, and offers HTTP/2 alongside HTTP/1.1.
+///
+/// OpenSSL is used directly rather than through native-tls, which only hands out the
+/// server's own certificate, so that [`peer_certificate`] can read the whole chain.
+pub(crate) async fn connect<S>(connector: &SslConnector, domain: &str, stream: S) -> Result<SslStream<S>, io::Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut config = connector.configure().map_err(io::Error::other)?;
+    config.set_alpn_protos(ALPN).map_err(io::Error::other)?;
+    let ssl = config.into_ssl(domain).map_err(io::Error::other)?;
+    let mut stream = SslStream::new(ssl, stream).map_err(io::Error::other)?;
+    if let Err(err) = Pin::new(&mut stream).connect().await {
+        return Err(match stream.ssl().verify_result() {
+            X509VerifyResult::OK => io::Error::other(err),
+            verify => io::Error::other(format!("{} ({})", err, verify.error_string())),
+        });
+    }
+    Ok(stream)
+}
+
+
+/// The certificate and chain the server sent in a finished handshake, unless it sent none
+/// or they can't be read.
+pub(crate) fn peer_certificate(ssl: &SslRef) -> Option<Certificate> {
+    Certificate::from_peer(ssl).unwrap_or_else(|err| {
+        log::warn!("could not read the server's certificate: {}", err);
+        None
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn certificate(sans: &[&str], days_left: i64) -> Certificate {
+        Certificate {
+            subject: format!("CN={}", sans.first().unwrap_or(&"")),
+            issuer: "CN=Test CA".to_string(),
+            sans: sans.iter().map(|san| san.to_string()).collect(),
+            not_after: Utc::now() + TimeDelta::days(days_left) + TimeDelta::hours(1),
+            chain: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn sans_cover_hosts_and_one_wildcard_label() {
+        let cert = certificate(&["example.com", "*.example.org", "127.0.0.1"], 30);
+        for host in ["example.com", "EXAMPLE.com.", "www.example.org", "127.0.0.1"] {
+            assert!(cert.covers(host), "{}", host);
+        }
+        for host in ["www.example.com", "example.org", "a.b.example.org", "127.0.0.2"] {
+            assert!(!cert.covers(host), "{}", host);
+        }
+    }
+
+    #[test]
+    fn the_chain_may_expire_first() {
+        let mut cert = certificate(&["example.com"], 60);
+        assert!(std::ptr::eq(cert.expires_first(), &cert));
+
+        cert.chain = vec![certificate(&["intermediate"], 10), certificate(&["root"], 20)];
+        assert_eq!(cert.expires_first().subject, "CN=intermediate");
+    }
+
+    #[test]
+    fn rejections_name_the_likely_reason() {
+        let expired = certificate(&["example.com"], -4);
+        assert!(rejection(&expired, "example.com").starts_with("certificate for example.com expired 3 days ago"));
+
+        let other = certificate(&["example.org", "www.example.org"], 30);
+        assert_eq!(rejection(&other, "example.com"), "certificate is for example.org, www.example.org, not example.com");
+
+        let untrusted = certificate(&["example.com"], 30);
+        assert_eq!(
+            rejection(&untrusted, "example.com"),
+            "certificate for example.com issued by CN=Test CA is not trusted"
+        );
+    }
+}
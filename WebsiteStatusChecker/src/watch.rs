@@ -0,0 +1,350 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use crate::check::CheckResult;
+use crate::checker::{Checker, Executor};
+use crate::report::Reporter;
+use crate::shutdown::{InFlight, Shutdown};
+use crate::target::Target;
+
+
+/// How often watch mode asks for reloaded targets while it has nothing else to do.
+const TICK: Duration = Duration::from_secs(1);
+
+/// Minimum pause between two [`Reporter::flush`] calls.
+const FLUSH_EVERY: Duration = Duration::from_secs(1);
+
+
+/// Options for [`watch`].
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    /// Fraction of each target's interval, in `0.0..=1.0`, by which its checks are randomly
+    /// moved so targets with the same interval don't all fire together.
+    pub jitter: f64,
+    /// Ends the watch when triggered.
+    pub shutdown: Shutdown,
+    /// How long running checks get to finish after `shutdown` is triggered.
+    pub grace: Duration,
+}
+
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        WatchOptions { jitter: 0.1, shutdown: Shutdown::new(), grace: Duration::from_secs(10) }
+    }
+}
+
+
+/// Checks every target on its own interval until `options.shutdown` is triggered.
+///
+/// Results go to `reporters` as they arrive; about once a second the latest result for
+/// each target is also passed to [`Reporter::flush`]. `reload` is called at least once a
+/// second and may return a new set of targets: targets that are still present keep their
+/// schedule, new ones are scheduled straight away, and checks already running finish and
+/// are reported as usual.
+///
+/// On shutdown no new checks are started, running checks get `options.grace` to finish,
+/// the rest are reported as aborted, and every reporter's [`Reporter::finish`] receives
+/// the latest result for each target.
+pub fn watch(
+    checker: &Checker,
+    targets: Vec<Target>,
+    reporters: &mut [Box<dyn Reporter>],
+    options: &WatchOptions,
+    mut reload: impl FnMut() -> Option<Vec<Target>>,
+) -> io::Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let executor = checker.executor(usize::MAX, tx);
+    let mut schedule = Schedule::new(targets, options.jitter);
+    let mut latest: BTreeMap<String, CheckResult> = BTreeMap::new();
+    let mut last_flush = Instant::now();
+    let mut dirty = false;
+
+    while !options.shutdown.is_triggered() {
+        if let Some(targets) = reload() {
+            schedule.replace(targets);
+            latest.retain(|key, _| schedule.contains(key));
+            dirty = true;
+        }
+
+        for target in schedule.take_due(Instant::now()) {
+            executor.submit(target);
+        }
+
+        let wait = schedule.next_due()
+            .map_or(TICK, |due| due.saturating_duration_since(Instant::now()).min(TICK));
+        let first = match rx.recv_timeout(wait) {
+            Ok(result) => Some(result),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+
+        for result in first.into_iter().chain(rx.try_iter()) {
+            schedule.in_flight.finished(&result);
+            record(result, &schedule, reporters, &mut latest)?;
+            dirty = true;
+        }
+
+        if dirty && last_flush.elapsed() >= FLUSH_EVERY {
+            let snapshot: Vec<CheckResult> = latest.values().cloned().collect();
+            for reporter in reporters.iter_mut() {
+                reporter.flush(&snapshot)?;
+            }
+            last_flush = Instant::now();
+            dirty = false;
+        }
+    }
+
+    drain(executor, &rx, &mut schedule, reporters, &mut latest, options.grace)?;
+
+    let snapshot: Vec<CheckResult> = latest.into_values().collect();
+    for reporter in reporters.iter_mut() {
+        reporter.finish(&snapshot)?;
+    }
+    Ok(())
+}
+
+
+/// Waits up to `grace` for the checks still running, then reports the rest as aborted.
+fn drain(
+    executor: Executor,
+    rx: &mpsc::Receiver<CheckResult>,
+    schedule: &mut Schedule,
+    reporters: &mut [Box<dyn Reporter>],
+    latest: &mut BTreeMap<String, CheckResult>,
+    grace: Duration,
+) -> io::Result<()> {
+    log::warn!("shutting down: waiting up to {:?} for {} running checks", grace, schedule.in_flight.len());
+    executor.cancel();
+    let deadline = Instant::now() + grace;
+
+    while !schedule.in_flight.is_empty() {
+        match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok(result) => {
+                schedule.in_flight.finished(&result);
+                record(result, schedule, reporters, latest)?;
+            }
+            Err(_) => break,
+        }
+    }
+
+    let unfinished = schedule.in_flight.abort_all();
+    if unfinished.is_empty() {
+        executor.join();
+    } else {
+        executor.abort();
+    }
+    for result in unfinished {
+        record(result, schedule, reporters, latest)?;
+    }
+    Ok(())
+}
+
+
+fn record(
+    result: CheckResult,
+    schedule: &Schedule,
+    reporters: &mut [Box<dyn Reporter>],
+    latest: &mut BTreeMap<String, CheckResult>,
+) -> io::Result<()> {
+    for reporter in reporters.iter_mut() {
+        reporter.on_result(&result)?;
+    }
+    let key = result.key();
+    if schedule.contains(&key) {
+        latest.insert(key, result);
+    }
+    Ok(())
+}
+
+
+/// When each target is next due and which targets are being checked right now.
+struct Schedule {
+    entries: Vec<Entry>,
+    in_flight: InFlight,
+    jitter: f64,
+}
+
+
+struct Entry {
+    key: String,
+    target: Target,
+    next_due: Instant,
+}
+
+
+impl Schedule {
+    fn new(targets: Vec<Target>, jitter: f64) -> Self {
+        let mut schedule = Schedule { entries: Vec::new(), in_flight: InFlight::default(), jitter };
+        schedule.replace(targets);
+        schedule
+    }
+
+    /// Swaps in a new target list, keeping the due time of targets that were already known.
+    fn replace(&mut self, targets: Vec<Target>) {
+        let now = Instant::now();
+        let mut previous: BTreeMap<String, Instant> =
+            self.entries.drain(..).map(|entry| (entry.key, entry.next_due)).collect();
+
+        for target in targets {
+            let key = target.key();
+            // New targets start within the first jitter window rather than all at once
+            let next_due = previous.remove(&key).unwrap_or_else(|| {
+                now + target.interval.mul_f64(self.jitter * rand::random::<f64>())
+            });
+            self.entries.push(Entry { key, target, next_due });
+        }
+    }
+
+    fn contains(&self, key: &str) -> bool {
+        self.entries.iter().any(|entry| entry.key == key)
+    }
+
+    /// Returns the targets due at `now` that aren't still being checked, and reschedules them.
+    fn take_due(&mut self, now: Instant) -> Vec<Target> {
+        let mut due = Vec::new();
+        for entry in &mut self.entries {
+            if entry.next_due > now || self.in_flight.contains(&entry.key) {
+                continue;
+            }
+            let spread = self.jitter * (rand::random::<f64>() * 2.0 - 1.0);
+            entry.next_due = now + entry.target.interval.mul_f64(1.0 + spread);
+            self.in_flight.started(&entry.target);
+            due.push(entry.target.clone());
+        }
+        due
+    }
+
+    fn next_due(&self) -> Option<Instant> {
+        self.entries
+            .iter()
+            .filter(|entry| !self.in_flight.contains(&entry.key))
+            .map(|entry| entry.next_due)
+            .min()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    use hyper_util::client::proxy::matcher::Matcher;
+
+    use crate::error::ErrorKind;
+
+    fn target(name: &str, interval: Duration) -> Target {
+        Target::new(format!("https://{}.example/", name)).with_name(name).with_interval(interval)
+    }
+
+    fn due_in(schedule: &Schedule, name: &str, now: Instant) -> Duration {
+        let entry = schedule.entries.iter().find(|entry| entry.target.label() == name).unwrap();
+        entry.next_due.saturating_duration_since(now)
+    }
+
+    #[test]
+    fn new_targets_start_within_the_jitter_window() {
+        let now = Instant::now();
+        let targets = (0..20).map(|index| target(&index.to_string(), Duration::from_secs(100))).collect();
+        let schedule = Schedule::new(targets, 0.1);
+        for entry in &schedule.entries {
+            assert!(entry.next_due.saturating_duration_since(now) <= Duration::from_secs(10));
+        }
+
+        // Without jitter they are all due at once
+        let mut schedule = Schedule::new(vec![target("shop", Duration::from_secs(100))], 0.0);
+        assert_eq!(schedule.take_due(Instant::now()).len(), 1);
+    }
+
+    #[test]
+    fn checks_repeat_within_the_jitter_of_their_interval() {
+        let mut schedule = Schedule::new(vec![target("shop", Duration::from_secs(100))], 0.25);
+        for round in 1..=20 {
+            let now = Instant::now() + Duration::from_secs(1000 * round);
+            let due = schedule.take_due(now);
+            assert_eq!(due.len(), 1);
+            let next = due_in(&schedule, "shop", now);
+            assert!(next >= Duration::from_secs(75) && next <= Duration::from_secs(125), "{:?}", next);
+            schedule.in_flight.finished(&CheckResult::aborted(&due[0], Duration::ZERO));
+        }
+    }
+
+    #[test]
+    fn running_targets_are_not_due_again() {
+        let mut schedule = Schedule::new(vec![target("shop", Duration::from_secs(1))], 0.0);
+        let later = Instant::now() + Duration::from_secs(10);
+        let due = schedule.take_due(later);
+        assert_eq!(due.len(), 1);
+        assert!(schedule.take_due(later + Duration::from_secs(10)).is_empty());
+        assert_eq!(schedule.next_due(), None);
+
+        schedule.in_flight.finished(&CheckResult::aborted(&due[0], Duration::ZERO));
+        assert_eq!(schedule.take_due(later + Duration::from_secs(10)).len(), 1);
+    }
+
+    #[test]
+    fn reload_keeps_known_targets_and_adds_new_ones() {
+        let interval = Duration::from_secs(60);
+        let mut schedule = Schedule::new(vec![target("shop", interval), target("blog", interval)], 0.0);
+        let now = Instant::now() + Duration::from_secs(1);
+        assert_eq!(schedule.take_due(now).len(), 2);
+        let shop_due = due_in(&schedule, "shop", now);
+
+        schedule.replace(vec![target("shop", interval), target("docs", interval)]);
+        assert!(schedule.contains(&target("shop", interval).key()));
+        assert!(!schedule.contains(&target("blog", interval).key()));
+        assert_eq!(due_in(&schedule, "shop", now), shop_due);
+        // The new target is due straight away, the kept one only after its interval
+        let due = schedule.take_due(now + Duration::from_secs(1));
+        assert_eq!(due.iter().map(Target::label).collect::<Vec<_>>(), ["docs"]);
+    }
+
+    /// Keeps the results [`Reporter::finish`] is given.
+    struct Finished(Arc<Mutex<Vec<CheckResult>>>);
+
+    impl Reporter for Finished {
+        fn on_result(&mut self, _result: &CheckResult) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn finish(&mut self, results: &[CheckResult]) -> io::Result<()> {
+            self.0.lock().unwrap().extend_from_slice(results);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn shutdown_waits_out_the_grace_then_aborts() {
+        // Connections are accepted by the kernel but never answered
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let hour = Duration::from_secs(3600);
+        let targets = vec![
+            Target::new(&url).with_name("quick").with_timeout(Duration::from_millis(300)).with_interval(hour),
+            Target::new(&url).with_name("stuck").with_timeout(Duration::from_secs(60)).with_interval(hour),
+        ];
+
+        let checker = Checker::builder().proxies(Matcher::builder().build()).build().unwrap();
+        let finished = Arc::new(Mutex::new(Vec::new()));
+        let mut reporters: Vec<Box<dyn Reporter>> = vec![Box::new(Finished(Arc::clone(&finished)))];
+        let options = WatchOptions { jitter: 0.0, grace: Duration::from_secs(1), ..WatchOptions::default() };
+        let shutdown = options.shutdown.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            shutdown.trigger();
+        });
+
+        let started = Instant::now();
+        watch(&checker, targets, &mut reporters, &options, || None).unwrap();
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_secs(1) && elapsed < Duration::from_secs(5), "{:?}", elapsed);
+
+        let finished = finished.lock().unwrap();
+        let kinds: Vec<_> = finished.iter().map(|result| (result.label(), result.error_kind())).collect();
+        assert_eq!(kinds, [("quick", Some(ErrorKind::Timeout)), ("stuck", Some(ErrorKind::Aborted))]);
+    }
+}
@@ -0,0 +1,432 @@
+//! Alert state transitions and the built-in notifiers, against local mock servers.
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use chrono::TimeDelta;
+use website_status_checker::{
+    config, AlertEvent, AlertPolicy, AlertState, Alerter, Alerts, CheckError, CheckResult, CommandNotifier,
+    ErrorKind, Notifier, Reporter, Secret, SlackNotifier, SmtpNotifier, SmtpSecurity, Timings, WebhookNotifier,
+};
+
+
+/// A result for the target `shop`, `seconds` after a fixed start.
+fn result(seconds: i64, error: Option<ErrorKind>) -> CheckResult {
+    let start = chrono::DateTime::parse_from_rfc3339("2025-05-15T04:00:00Z").unwrap().to_utc();
+    CheckResult {
+        url: "http://shop.example/".to_string(),
+        name: Some("shop".to_string()),
+        tags: vec!["retail".to_string()],
+        status: if error.is_some() { None } else { Some(200) },
+        error: error.map(|kind| CheckError::new(kind, "it broke")),
+        redirects: Vec::new(),
+        headers: BTreeMap::new(),
+        certificate: None,
+        warnings: Vec::new(),
+        response_time: Duration::from_millis(40),
+        timings: Timings::default(),
+        timestamp: start + TimeDelta::seconds(seconds),
+        attempts: Vec::new(),
+    }
+}
+
+
+fn up(seconds: i64) -> CheckResult {
+    result(seconds, None)
+}
+
+
+fn down(seconds: i64) -> CheckResult {
+    result(seconds, Some(ErrorKind::Timeout))
+}
+
+
+/// Keeps every event it is given.
+#[derive(Debug, Default)]
+struct Recorder(Mutex<Vec<AlertEvent>>);
+
+
+impl Notifier for Recorder {
+    fn notify(&self, event: &AlertEvent) -> Result<(), String> {
+        self.0.lock().unwrap().push(event.clone());
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        "recorder".to_string()
+    }
+}
+
+
+/// Feeds `results` to an alerter with `notifiers` and waits for the notifications.
+fn run(policy: AlertPolicy, notifiers: Vec<Arc<dyn Notifier>>, results: &[CheckResult]) {
+    let mut alerter = Alerter::new(Alerts { policy, notifiers });
+    for result in results {
+        alerter.on_result(result).unwrap();
+    }
+    alerter.finish(results).unwrap();
+}
+
+
+fn down_event() -> Vec<CheckResult> {
+    vec![down(0), down(60), down(120)]
+}
+
+
+struct Request {
+    request_line: String,
+    headers: BTreeMap<String, String>,
+    body: Vec<u8>,
+}
+
+
+/// Starts an HTTP server answering every request with `status` and passing it on.
+fn mock_webhook(status: u16) -> (SocketAddr, Receiver<Request>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+
+            let mut headers = BTreeMap::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let line = line.trim_end();
+                if line.is_empty() {
+                    break;
+                }
+                let (name, value) = line.split_once(':').unwrap();
+                headers.insert(name.to_ascii_lowercase(), value.trim().to_string());
+            }
+            let length = headers.get("content-length").map_or(0, |value| value.parse().unwrap());
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+
+            let mut stream = stream;
+            write!(stream, "HTTP/1.1 {} Mock\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status).unwrap();
+            let _ = tx.send(Request { request_line: request_line.trim_end().to_string(), headers, body });
+        }
+    });
+
+    (addr, rx)
+}
+
+
+/// Starts an SMTP server that accepts one message and passes on every line it received.
+fn mock_smtp() -> (SocketAddr, Receiver<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut writer = stream;
+        let mut transcript = Vec::new();
+        let mut in_data = false;
+
+        writer.write_all(b"220 mock ESMTP\r\n").unwrap();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap() == 0 {
+                break;
+            }
+            let line = line.trim_end_matches(['\r', '\n']).to_string();
+            transcript.push(line.clone());
+
+            let reply: &[u8] = if in_data {
+                if line != "." {
+                    continue;
+                }
+                in_data = false;
+                b"250 queued\r\n"
+            } else if line.starts_with("EHLO") {
+                b"250-mock\r\n250-AUTH PLAIN\r\n250 8BITMIME\r\n"
+            } else if line.starts_with("AUTH PLAIN") {
+                b"235 ok\r\n"
+            } else if line == "DATA" {
+                in_data = true;
+                b"354 go ahead\r\n"
+            } else if line == "QUIT" {
+                writer.write_all(b"221 bye\r\n").unwrap();
+                break;
+            } else {
+                b"250 ok\r\n"
+            };
+            writer.write_all(reply).unwrap();
+        }
+        let _ = tx.send(transcript);
+    });
+
+    (addr, rx)
+}
+
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("wsc-alerts-{}-{}", std::process::id(), name))
+}
+
+
+#[test]
+fn alerts_only_on_transitions() {
+    let recorder = Arc::new(Recorder::default());
+    let policy = AlertPolicy { fail_after: 2, recover_after: 2 };
+    let results = [down(0), up(60), down(120), down(180), down(240), up(300), down(360), up(420), up(480), up(540)];
+    run(policy, vec![recorder.clone()], &results);
+
+    let events = recorder.0.lock().unwrap();
+    assert_eq!(events.len(), 2);
+
+    assert_eq!(events[0].state, AlertState::Down);
+    assert_eq!(events[0].consecutive, 2);
+    assert_eq!(events[0].down_since, results[2].timestamp);
+    assert_eq!(events[0].result.timestamp, results[3].timestamp);
+    assert_eq!(events[0].message(), "shop is DOWN after 2 failed checks: timeout: it broke");
+
+    assert_eq!(events[1].state, AlertState::Up);
+    assert_eq!(events[1].down_since, results[2].timestamp);
+    assert_eq!(events[1].downtime, Some(Duration::from_secs(360)));
+    assert_eq!(events[1].message(), "shop is UP again after 6.0m down");
+}
+
+
+#[test]
+fn aborted_checks_neither_fail_nor_recover() {
+    let recorder = Arc::new(Recorder::default());
+    let policy = AlertPolicy { fail_after: 2, recover_after: 1 };
+    run(policy, vec![recorder.clone()], &[down(0), result(30, Some(ErrorKind::Aborted)), down(60)]);
+
+    let events = recorder.0.lock().unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].state, AlertState::Down);
+}
+
+
+#[test]
+fn webhook_posts_the_alert_as_json() {
+    let (addr, requests) = mock_webhook(200);
+    let webhook = WebhookNotifier::new(Secret::new(format!("http://{}/hooks/wsc", addr))).with_header("X-Token", "abc");
+    run(AlertPolicy::default(), vec![Arc::new(webhook)], &down_event());
+
+    let request = requests.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(request.request_line, "POST /hooks/wsc HTTP/1.1");
+    assert_eq!(request.headers["content-type"], "application/json");
+    assert_eq!(request.headers["x-token"], "abc");
+
+    let event: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+    assert_eq!(event["event"], "down");
+    assert_eq!(event["target"], "shop");
+    assert_eq!(event["url"], "http://shop.example/");
+    assert_eq!(event["tags"][0], "retail");
+    assert_eq!(event["consecutive"], 3);
+    assert_eq!(event["down_since"], "2025-05-15T04:00:00Z");
+    assert_eq!(event["downtime_ms"], serde_json::Value::Null);
+    assert_eq!(event["result"]["error_kind"], "timeout");
+    assert!(requests.try_recv().is_err());
+}
+
+
+#[test]
+fn slack_gets_the_message_as_text() {
+    let (addr, requests) = mock_webhook(200);
+    let slack = SlackNotifier::new(Secret::new(format!("http://{}/services/T0/B0/x", addr)));
+    let mut results = down_event();
+    results.extend([up(180), up(240)]);
+    run(AlertPolicy::default(), vec![Arc::new(slack)], &results);
+
+    let texts: Vec<String> = (0..2)
+        .map(|_| {
+            let request = requests.recv_timeout(Duration::from_secs(5)).unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+            body["text"].as_str().unwrap().to_string()
+        })
+        .collect();
+    assert!(texts[0].contains("shop is DOWN after 3 failed checks"), "{}", texts[0]);
+    assert!(texts[1].contains("shop is UP again after 4.0m down"), "{}", texts[1]);
+}
+
+
+#[test]
+fn webhook_errors_leave_out_the_url() {
+    // Nothing listens on a port that was just released
+    let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let webhook = WebhookNotifier::new(Secret::new(format!("http://{}/hooks/secret-token", addr)));
+    let results = down_event();
+    let event = AlertEvent {
+        state: AlertState::Down,
+        down_since: results[0].timestamp,
+        downtime: None,
+        consecutive: 3,
+        result: results[2].clone(),
+    };
+
+    let err = webhook.notify(&event).unwrap_err();
+    assert_eq!(err, "the request failed: connection_refused");
+
+    let (addr, _requests) = mock_webhook(500);
+    let webhook = WebhookNotifier::new(Secret::new(format!("http://{}/hooks/secret-token", addr)));
+    assert_eq!(webhook.notify(&event).unwrap_err(), "the server answered 500");
+}
+
+
+#[test]
+fn a_failing_notifier_does_not_stop_the_others() {
+    let (addr, requests) = mock_webhook(500);
+    let recorder = Arc::new(Recorder::default());
+    let webhook = WebhookNotifier::new(Secret::new(format!("http://{}/", addr)));
+    run(AlertPolicy::default(), vec![Arc::new(webhook), recorder.clone()], &down_event());
+
+    // One request plus two retries, then the next notifier still runs
+    assert_eq!(requests.iter().take(3).count(), 3);
+    assert_eq!(recorder.0.lock().unwrap().len(), 1);
+}
+
+
+#[test]
+fn smtp_sends_an_email_per_event() {
+    let (addr, transcripts) = mock_smtp();
+    let smtp = SmtpNotifier {
+        host: addr.ip().to_string(),
+        port: addr.port(),
+        security: SmtpSecurity::None,
+        credentials: Some(("alerts".to_string(), Secret::new("hunter2"))),
+        from: "wsc@example.com".to_string(),
+        to: vec!["oncall@example.com".to_string(), "web@example.com".to_string()],
+        timeout: Duration::from_secs(5),
+    };
+    run(AlertPolicy::default(), vec![Arc::new(smtp)], &down_event());
+
+    let transcript = transcripts.recv_timeout(Duration::from_secs(5)).unwrap();
+    let position = |line: &str| transcript.iter().position(|sent| sent == line).unwrap_or_else(|| panic!("{} not in {:?}", line, transcript));
+    // base64 of "\0alerts\0hunter2"
+    let auth = position("AUTH PLAIN AGFsZXJ0cwBodW50ZXIy");
+    let from = position("MAIL FROM:<wsc@example.com>");
+    let first = position("RCPT TO:<oncall@example.com>");
+    let second = position("RCPT TO:<web@example.com>");
+    let data = position("DATA");
+    let subject = position("Subject: [DOWN] shop");
+    let end = position(".");
+    assert!(auth < from && from < first && first < second && second < data && data < subject && subject < end);
+    assert!(transcript.iter().any(|line| line == "Error: timeout: it broke"));
+    assert_eq!(transcript.last().map(String::as_str), Some("QUIT"));
+}
+
+
+#[test]
+fn command_gets_the_event_on_stdin_and_in_the_environment() {
+    let output = temp_path("command.out");
+    let command = CommandNotifier {
+        program: PathBuf::from("sh"),
+        args: vec![
+            "-c".to_string(),
+            r#"printf '%s %s %s\n' "$WSC_ALERT_STATE" "$WSC_ALERT_TARGET" "$WSC_ALERT_URL" > "$1"; cat >> "$1""#.to_string(),
+            "sh".to_string(),
+            output.display().to_string(),
+        ],
+        timeout: Duration::from_secs(5),
+    };
+    run(AlertPolicy::default(), vec![Arc::new(command)], &down_event());
+
+    let written = std::fs::read_to_string(&output).unwrap();
+    std::fs::remove_file(&output).unwrap();
+    let (summary, json) = written.split_once('\n').unwrap();
+    assert_eq!(summary, "down shop http://shop.example/");
+    let event: serde_json::Value = serde_json::from_str(json).unwrap();
+    assert_eq!(event["message"], "shop is DOWN after 3 failed checks: timeout: it broke");
+}
+
+
+#[test]
+fn command_that_hangs_is_killed() {
+    let recorder = Arc::new(Recorder::default());
+    let command = CommandNotifier {
+        program: PathBuf::from("sleep"),
+        args: vec!["30".to_string()],
+        timeout: Duration::from_millis(200),
+    };
+    run(AlertPolicy::default(), vec![Arc::new(command), recorder.clone()], &down_event());
+    assert_eq!(recorder.0.lock().unwrap().len(), 1);
+}
+
+
+#[test]
+fn config_reads_the_alerts_table() {
+    let path = temp_path("alerts.toml");
+    std::fs::write(
+        &path,
+        r#"
+        [alerts]
+        fail_after = 2
+        recover_after = 4
+
+        [[alerts.notifiers]]
+        type = "webhook"
+        url = "https://hooks.example.com/wsc"
+
+        [[alerts.notifiers]]
+        type = "smtp"
+        host = "smtp.example.com"
+        from = "wsc@example.com"
+        to = ["oncall@example.com"]
+
+        [[alerts.notifiers]]
+        type = "command"
+        command = ["/usr/local/bin/page", "--team", "web"]
+        timeout = "10s"
+
+        [[targets]]
+        url = "https://example.com/"
+        "#,
+    )
+    .unwrap();
+    let loaded = config::load(&path, &config::Overrides::default());
+
+    std::fs::write(&path, "[alerts]\n[[alerts.notifiers]]\ntype = \"slack\"\n").unwrap();
+    let missing_url = config::load(&path, &config::Overrides::default());
+    std::fs::remove_file(&path).unwrap();
+
+    let alerts = loaded.unwrap().alerts.unwrap();
+    assert_eq!(alerts.policy, AlertPolicy { fail_after: 2, recover_after: 4 });
+    let described: Vec<String> = alerts.notifiers.iter().map(|notifier| notifier.describe()).collect();
+    assert_eq!(described, ["webhook", "smtp smtp.example.com:587", "command /usr/local/bin/page"]);
+
+    let err = missing_url.unwrap_err().to_string();
+    assert!(err.ends_with("key `alerts.notifiers[0].url`: set exactly one of `url` and `url_env`"), "{}", err);
+}
+
+
+#[test]
+fn config_rejects_control_characters_in_mail_addresses() {
+    let path = temp_path("smtp.toml");
+    std::fs::write(
+        &path,
+        r#"
+        [[alerts.notifiers]]
+        type = "smtp"
+        host = "smtp.example.com"
+        from = "wsc@example.com"
+        to = ["oncall@example.com\r\nRCPT TO:<everyone@example.com>"]
+
+        [[targets]]
+        url = "https://example.com/"
+        "#,
+    )
+    .unwrap();
+    let loaded = config::load(&path, &config::Overrides::default());
+    std::fs::remove_file(&path).unwrap();
+
+    let err = loaded.unwrap_err().to_string();
+    assert!(err.contains("key `alerts.notifiers[0].to`:"), "{}", err);
+    assert!(err.ends_with("contains a control character"), "{}", err);
+}
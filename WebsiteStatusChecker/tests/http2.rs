@@ -0,0 +1,107 @@
+//! HTTP/2 against a local server that offers it in the TLS handshake.
+//!
+//! It has a file of its own because trusting the server's certificate means setting
+//! `SSL_CERT_FILE` for the whole process.
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::pin::Pin;
+
+use http_body_util::Full;
+use hyper::body::{Bytes, Incoming};
+use hyper::server::conn::http2;
+use hyper::service::service_fn;
+use hyper::{Request, Response};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use openssl::asn1::Asn1Time;
+use openssl::ec::{EcGroup, EcKey};
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Private};
+use openssl::ssl::{select_next_proto, AlpnError, Ssl, SslAcceptor, SslMethod};
+use openssl::x509::extension::SubjectAlternativeName;
+use openssl::x509::{X509, X509Builder, X509NameBuilder};
+use tokio_openssl::SslStream;
+use website_status_checker::{BodyAssertions, Checker, ProxyMatcher, Target};
+
+
+/// A self-signed certificate for `localhost`, valid for a year.
+fn certificate() -> (X509, PKey<Private>) {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+
+    let mut name = X509NameBuilder::new().unwrap();
+    name.append_entry_by_text("CN", "localhost").unwrap();
+    let name = name.build();
+
+    let mut builder = X509Builder::new().unwrap();
+    builder.set_version(2).unwrap();
+    builder.set_subject_name(&name).unwrap();
+    builder.set_issuer_name(&name).unwrap();
+    builder.set_pubkey(&key).unwrap();
+    builder.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+    builder.set_not_after(&Asn1Time::days_from_now(365).unwrap()).unwrap();
+    let san = SubjectAlternativeName::new().dns("localhost").build(&builder.x509v3_context(None, None)).unwrap();
+    builder.append_extension(san).unwrap();
+    builder.sign(&key, MessageDigest::sha256()).unwrap();
+
+    (builder.build(), key)
+}
+
+
+/// Starts an HTTPS server that only speaks HTTP/2 and answers with the protocol version
+/// each request arrived over.
+fn h2_server(cert: &X509, key: &PKey<Private>) -> SocketAddr {
+    let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+    acceptor.set_certificate(cert).unwrap();
+    acceptor.set_private_key(key).unwrap();
+    acceptor.set_alpn_select_callback(|_, offered| select_next_proto(b"\x02h2", offered).ok_or(AlpnError::NOACK));
+    let acceptor = acceptor.build();
+
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let listener = runtime.block_on(tokio::net::TcpListener::bind("127.0.0.1:0")).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    std::thread::spawn(move || {
+        runtime.block_on(async move {
+            loop {
+                let Ok((stream, _)) = listener.accept().await else { continue };
+                let mut stream = SslStream::new(Ssl::new(acceptor.context()).unwrap(), stream).unwrap();
+                tokio::spawn(async move {
+                    if Pin::new(&mut stream).accept().await.is_err() {
+                        return;
+                    }
+                    let service = service_fn(|request: Request<Incoming>| async move {
+                        let body = format!("{:?}", request.version());
+                        Ok::<_, Infallible>(Response::new(Full::new(Bytes::from(body))))
+                    });
+                    let _ = http2::Builder::new(TokioExecutor::new()).serve_connection(TokioIo::new(stream), service).await;
+                });
+            }
+        });
+    });
+
+    addr
+}
+
+
+#[test]
+fn uses_http2_when_the_server_offers_it() {
+    let (cert, key) = certificate();
+    let trusted = std::env::temp_dir().join(format!("wsc-http2-{}.pem", std::process::id()));
+    std::fs::write(&trusted, cert.to_pem().unwrap()).unwrap();
+    // SAFETY: this is the only test in this binary, so nothing else reads the environment meanwhile
+    unsafe { std::env::set_var("SSL_CERT_FILE", &trusted) };
+
+    let addr = h2_server(&cert, &key);
+    let checker = Checker::builder().proxies(ProxyMatcher::builder().build()).build().unwrap();
+    let assertions = BodyAssertions { contains: vec!["HTTP/2.0".to_string()], ..BodyAssertions::default() };
+    let result = checker.check(&Target::new(format!("https://localhost:{}/health?full=1", addr.port()))
+        .with_body_assertions(assertions));
+    std::fs::remove_file(&trusted).unwrap();
+
+    assert!(result.is_up(), "{:?}", result.error);
+    assert_eq!(result.status, Some(200));
+    assert!(result.timings.tls.is_some());
+    assert_eq!(result.certificate.map(|certificate| certificate.subject), Some("CN=localhost".to_string()));
+}
@@ -0,0 +1,164 @@
+//! The status server's routes, served on a free port.
+
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+use chrono::{TimeDelta, Utc};
+use website_status_checker::{
+    serve, CheckError, CheckResult, ErrorKind, Reporter, ServeOptions, Server, Shutdown, StatusBoard, Timings,
+};
+
+
+/// A result for `name`, finished `minutes_ago`.
+fn result(name: &str, minutes_ago: i64, error: Option<ErrorKind>) -> CheckResult {
+    CheckResult {
+        url: format!("https://{}.example/", name),
+        name: Some(name.to_string()),
+        tags: Vec::new(),
+        status: if error.is_some() { None } else { Some(200) },
+        error: error.map(|kind| CheckError::new(kind, "it broke")),
+        redirects: Vec::new(),
+        headers: BTreeMap::new(),
+        certificate: None,
+        warnings: Vec::new(),
+        response_time: Duration::from_millis(40),
+        timings: Timings::default(),
+        timestamp: Utc::now() - TimeDelta::minutes(minutes_ago),
+        attempts: Vec::new(),
+    }
+}
+
+
+/// Serves a board where `shop` was down two hours ago and is up now, and `blog` is down.
+fn server() -> (Server, Shutdown) {
+    let mut board = StatusBoard::new();
+    let results = [
+        result("shop", 120, Some(ErrorKind::Timeout)),
+        result("shop", 1, None),
+        result("blog", 1, Some(ErrorKind::Dns)),
+    ];
+    for result in &results {
+        board.on_result(result).unwrap();
+    }
+    let shutdown = Shutdown::new();
+    let options = ServeOptions {
+        listen: SocketAddr::from(([127, 0, 0, 1], 0)),
+        shutdown: shutdown.clone(),
+        ..ServeOptions::default()
+    };
+    (serve(board, options).unwrap(), shutdown)
+}
+
+
+/// Sends a request and returns the status code, the head and the body of the response.
+fn request(addr: SocketAddr, method: &str, path: &str) -> (u16, String, String) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    write!(stream, "{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n", method, path, addr).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+    (status, head.to_ascii_lowercase(), body.to_string())
+}
+
+
+fn get_json(addr: SocketAddr, path: &str) -> (u16, serde_json::Value) {
+    let (status, head, body) = request(addr, "GET", path);
+    assert!(head.contains("\r\ncontent-type: application/json\r\n"), "{}", head);
+    (status, serde_json::from_str(&body).unwrap())
+}
+
+
+#[test]
+fn serves_the_status_page() {
+    let (server, shutdown) = server();
+    let (status, head, body) = request(server.local_addr(), "GET", "/");
+    shutdown.trigger();
+    server.join();
+
+    assert_eq!(status, 200);
+    assert!(head.contains("\r\ncontent-type: text/html; charset=utf-8\r\n"), "{}", head);
+    assert!(head.contains("\r\ncache-control: no-store\r\n"), "{}", head);
+    assert!(body.contains("<h1>1 of 2 targets up</h1>"), "{}", body);
+    let row = "<tr class=\"down\"><td>DOWN</td><td><a href=\"https://blog.example/\">blog</a></td>";
+    assert!(body.contains(row), "{}", body);
+}
+
+
+#[test]
+fn serves_the_api() {
+    let (server, shutdown) = server();
+    let addr = server.local_addr();
+
+    let (status, document) = get_json(addr, "/api/status");
+    assert_eq!(status, 200);
+    assert_eq!(document["schema_version"], 2);
+    let results = document["results"].as_array().unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!((&results[0]["name"], &results[0]["up"]), (&"blog".into(), &false.into()));
+    assert_eq!((&results[1]["name"], &results[1]["up"]), (&"shop".into(), &true.into()));
+
+    let (status, history) = get_json(addr, "/api/history");
+    assert_eq!(status, 200);
+    assert_eq!(history["results"].as_array().unwrap().len(), 3);
+    let (_, history) = get_json(addr, "/api/history?since=1h");
+    assert_eq!(history["results"].as_array().unwrap().len(), 2);
+    let (_, history) = get_json(addr, "/api/history?since=1h&target=shop");
+    assert_eq!(history["results"].as_array().unwrap().len(), 1);
+    assert_eq!(history["results"][0]["name"], "shop");
+
+    let (status, statistics) = get_json(addr, "/api/statistics?window=1h");
+    assert_eq!(status, 200);
+    let summaries = statistics.as_array().unwrap();
+    assert_eq!(summaries.len(), 2);
+    assert_eq!(summaries[0]["overall"]["checks"], 3);
+    assert_eq!(summaries[1]["window_ms"], 3_600_000.0);
+    assert_eq!(summaries[1]["overall"]["checks"], 2);
+
+    shutdown.trigger();
+    server.join();
+}
+
+
+#[test]
+fn serves_metrics() {
+    let (server, shutdown) = server();
+    let (status, head, body) = request(server.local_addr(), "GET", "/metrics");
+    shutdown.trigger();
+    server.join();
+
+    assert_eq!(status, 200);
+    assert!(head.contains("\r\ncontent-type: text/plain; version=0.0.4; charset=utf-8\r\n"), "{}", head);
+    let labels = "target=\"shop\",url=\"https://shop.example/\"";
+    assert!(body.contains(&format!("\nwsc_up{{{}}} 1\n", labels)), "{}", body);
+    assert!(body.contains(&format!("\nwsc_checks_total{{{},result=\"timeout\"}} 1\n", labels)), "{}", body);
+}
+
+
+#[test]
+fn rejects_bad_requests() {
+    let (server, shutdown) = server();
+    let addr = server.local_addr();
+
+    let (status, error) = get_json(addr, "/api/history?since=soon");
+    assert_eq!(status, 400);
+    assert_eq!(error["error"], "since: `soon` is not a duration like `500ms`, `3s` or `2m`");
+    let (status, error) = get_json(addr, "/api/statistics?window=1h&window=1000000d");
+    assert_eq!(status, 400);
+    assert_eq!(error["error"], "window: `1000000d` is longer than 100 years");
+
+    let (status, error) = get_json(addr, "/api/nothing");
+    assert_eq!(status, 404);
+    assert_eq!(error["error"], "no such page: /api/nothing");
+
+    let (status, head, _) = request(addr, "POST", "/api/status");
+    assert_eq!(status, 405);
+    assert!(head.contains("\r\nallow: get, head\r\n"), "{}", head);
+
+    shutdown.trigger();
+    server.join();
+}